@@ -4,6 +4,7 @@ pm_index = "/mnt/pmem0/pmem_hash.data"
 pm_btree_index = "/mnt/pmem0/pmem_btree.data"
 TPCC_WAREHOUSE = 2048
 YCSB_TOTAL = "256 * 1024 * 1024"
+MAX_THREADS = 256
 
 if __name__ == "__main__":
     with open('tpcc.sh', "w") as script:
@@ -29,6 +30,7 @@ if __name__ == "__main__":
         rust_code.write('pub const NVM_FILE_PATH: &str = "%s";\n'%db_file_path)
         rust_code.write('pub const INDEX_FILE_PATH: &str = "%s";\n'%pm_index)
         rust_code.write('pub const BTREE_FILE_PATH: &str = "%s";\n'%pm_btree_index)
+        rust_code.write('pub const MAX_THREADS: usize = %d;\n'%MAX_THREADS)
 
         
 
@@ -3,3 +3,4 @@ pub const YCSB_TOTAL: u64 = 128 * 1024 * 1024;
 pub const NVM_FILE_PATH: &str = "/mnt/pmem0/_test_persist";
 pub const INDEX_FILE_PATH: &str = "/mnt/pmem0/pmem_hash.data";
 pub const BTREE_FILE_PATH: &str = "/mnt/pmem0/btree";
+pub const MAX_THREADS: usize = 256;
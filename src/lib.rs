@@ -46,10 +46,28 @@ pub enum Error {
     TxNeedAbort,
     #[error("transaction conflict")]
     TxConflict,
+    #[error("isolation level not supported")]
+    IsolationNotSupported,
     #[error("io error")]
     IO(#[from] std::io::Error),
     #[error("tuple error")]
     Tuple(#[from] crate::storage::row::TupleError),
+    #[error("transaction aborted: {0}")]
+    Aborted(crate::transaction::AbortReason),
+    #[error("pool {field} is {found:#x}, this build expects {expected:#x}")]
+    PoolMismatch {
+        field: &'static str,
+        found: u64,
+        expected: u64,
+    },
+}
+
+impl Error {
+    /// Whether the transaction failed on a conflict with another one and
+    /// may commit if run again.
+    pub fn is_conflict(&self) -> bool {
+        crate::transaction::AbortKind::from_error(self).is_conflict()
+    }
 }
 
 /// A specialized `Result` type for this crate.
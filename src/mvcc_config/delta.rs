@@ -11,6 +11,8 @@ pub const TUPLE_ADDRESS: Range<u64> = range!(NEXT_DELTA_ADDRESS.end, U64_OFFSET)
 pub const DELTA_TABLE_ID: Range<u64> = range!(TUPLE_ADDRESS.end, size_of::<u32>() as u64);
 pub const DELTA_COLUMN_OFFSET: Range<u64> = range!(DELTA_TABLE_ID.end, size_of::<u32>() as u64);
 pub const DELTA_DATA_OFFSET: u64 = DELTA_COLUMN_OFFSET.end;
+/// Column offset of the record of an insert, which carries no data.
+pub const INSERT_COLUMN_OFFSET: u32 = u32::MAX;
 
 // delta
 // |----------------------------------------------------|
@@ -1,5 +1,33 @@
 pub mod delta;
-pub const THREAD_COUNT: usize = 48;
+use once_cell::sync::OnceCell;
+
+/// Worker threads when `N2DB_THREADS` is not set.
+pub const DEFAULT_THREAD_COUNT: usize = 48;
 pub const TEST_THREAD_COUNT: usize = 48;
-pub const TRANSACTION_COUNT: usize = THREAD_COUNT;
+/// Thread ids, each with its own slot of the per-thread state, set by
+/// `configure.py`. It sizes the catalog header, a database reloads only with
+/// the value it was made with.
+pub const TRANSACTION_COUNT: usize = crate::customer_config::MAX_THREADS;
+/// Low bits of a tid holding the thread that took it.
+pub const THREAD_ID_BITS: u64 = (usize::BITS - (TRANSACTION_COUNT - 1).leading_zeros()) as u64;
+const _: () = assert!(DEFAULT_THREAD_COUNT <= TRANSACTION_COUNT && TEST_THREAD_COUNT <= TRANSACTION_COUNT);
+
+/// Worker threads of the benchmarks and of recovery, read once from the
+/// `N2DB_THREADS` environment variable. They take the thread ids
+/// `0..thread_count()`, so it is at most `TRANSACTION_COUNT`.
+pub fn thread_count() -> usize {
+    static THREAD_COUNT: OnceCell<usize> = OnceCell::new();
+    *THREAD_COUNT.get_or_init(|| {
+        let count = std::env::var("N2DB_THREADS")
+            .ok()
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(DEFAULT_THREAD_COUNT);
+        assert!(
+            count > 0 && count <= TRANSACTION_COUNT,
+            "N2DB_THREADS must be in 1..={}",
+            TRANSACTION_COUNT
+        );
+        count
+    })
+}
 pub const YCSB_SIZE:usize = 2048;
\ No newline at end of file
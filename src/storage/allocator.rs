@@ -1,8 +1,9 @@
 use crate::config::*;
-use crate::mvcc_config::THREAD_COUNT;
 // use crate::index::bplus_tree::BplusTree;
 use crate::storage::nvm_file::{NVMTableStorage, PageId};
 use crate::storage::table::TupleId;
+#[cfg(feature = "append")]
+use crate::transaction::gc::RetiredRegions;
 use crate::utils::persist::persist_array::PersistArray;
 use crate::ycsb::u64_rand_new;
 // use crate::util::persist::persist_bitmap::PersistBitmap;
@@ -123,16 +124,24 @@ pub struct LocalPageAllocator {
     free_list: SegQueue<TupleId>,
     meta_page: PersistArray<u64>,
     free_pages: SegQueue<Address>,
+    /// Slots of the versions updates replaced, by the tid that replaced them.
+    #[cfg(feature = "append")]
+    versions: RetiredRegions<Address>,
 }
 impl LocalPageAllocator {
     pub fn new(meta_page: Address, max_tuple: u32) -> Self {
+        // the first page is taken by the first insert, most thread slots
+        // never insert into a table. Pages stay in the meta page in the
+        // order they were taken, a reload makes no page for a slot either.
         let mut allocator = LocalPageAllocator {
             page_start: 0,
-            offset: 0,
+            offset: max_tuple,
             max_tuple: max_tuple,
             free_list: SegQueue::new(),
             meta_page: PersistArray::<u64>::reload(meta_page),
             free_pages: SegQueue::new(),
+            #[cfg(feature = "append")]
+            versions: RetiredRegions::new(),
         };
 
         // let mut storage = NVMTableStorage::global_mut();
@@ -141,7 +150,6 @@ impl LocalPageAllocator {
         //         .free_pages
         //         .push(storage.alloc_page().unwrap().page_start);
         // }
-        allocator.allocate_new_page();
         // println!("create page count {}", allocator.meta_page.len());
         allocator
     }
@@ -184,31 +192,21 @@ impl LocalPageAllocator {
             self.allocate_new_page();
         }
     }
+    /// A slot for a new version, the one of a version no snapshot reads any
+    /// more if there is one.
     #[cfg(feature = "append")]
-    pub fn allocate_append_tuple(&mut self, size: u64, thread_id: usize) -> Result<TupleId> {
-        loop {
-            if self.offset < self.max_tuple {
-                let tuple_id = TupleId::new(self.page_start, self.offset as u64, size);
-                self.offset += 1;
-                return Ok(tuple_id);
-            }
-
-            match self.free_list.pop() {
-                Some(tid) => {
-                    return Ok(tid);
-                }
-                _ => {}
-            }
-            let page_start = self.page_start + THREAD_COUNT as u64 * PAGE_SIZE as u64;
-            // println!("page allocate {}", page_start);
-
-            unsafe {
-                self.meta_page.push(page_start);
-                self.page_start = page_start;
-                self.offset = 0;
-            };
+    pub fn allocate_append_tuple(&mut self, size: u64) -> Result<TupleId> {
+        match self.versions.reclaim() {
+            Some(address) => Ok(TupleId::from_address(address)),
+            None => self.allocate_tuple(size),
         }
     }
+    /// Keep the slot of a version replaced by `tid` until no snapshot before
+    /// `tid` runs.
+    #[cfg(feature = "append")]
+    pub fn retire_version(&mut self, address: Address, tid: u64) {
+        self.versions.retire(address, tid);
+    }
     pub fn free_tuple(&self, tid: u64) {
         self.free_list.push(TupleId {
             page_start: AtomicU64::new(tid),
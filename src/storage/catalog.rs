@@ -1,29 +1,33 @@
 use super::timestamp::TimeStamp;
 use crate::config::{Address, CATALOG_ADDRESS};
-use crate::config::{PAGE_SIZE, U64_OFFSET};
-use crate::mvcc_config::TRANSACTION_COUNT;
+use crate::config::{PAGE_SIZE, POW_2_63, U64_OFFSET};
+use crate::mvcc_config::{thread_count, TRANSACTION_COUNT};
 use crate::range;
 use crate::storage::nvm_file::NVMTableStorage;
 use crate::storage::schema::TableSchema;
 use crate::storage::table::Table;
 use crate::storage::variable_table::VariableTable;
+use crate::transaction::cc::{ConcurrencyControl, LockWaits};
 use crate::transaction::clog::Clog;
-use crate::transaction::snapshot::SnapShot;
+use crate::transaction::snapshot::{SnapShot, SnapShotEntity};
+use crate::transaction::gc::VersionGc;
+use crate::transaction::session::ThreadIds;
+use crate::transaction::ssi::Ssi;
 use crate::transaction::transaction::Transaction;
-use crate::transaction::transaction_buffer::TransactionBuffer;
+use crate::transaction::transaction_buffer::{PendingRecords, TransactionBuffer};
 use crate::utils::persist::persist_struct::PersistStruct;
 use crate::utils::{file, io};
-use crate::Result;
+use crate::{Error, Result};
 use once_cell::sync::OnceCell;
 use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::ops::Range;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
 use std::sync::RwLock;
 use std::thread;
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 
 pub const TABLE_ID: Range<u64> = range!(0, 1);
 pub const TABLE_NAME: Range<u64> = range!(TABLE_ID.end, 15);
@@ -106,16 +110,36 @@ impl TableDes {
     }
 }
 
-pub const TRANSACTION_PAGE_ADDRESS: Range<u64> =
-    range!(0, 2 * U64_OFFSET * TRANSACTION_COUNT as u64);
+/// Holds `FORMAT_VERSION`, first in the page so that any build finds it.
+pub const CATALOG_FORMAT: Range<u64> = range!(0, U64_OFFSET);
+/// Layout of the pool made by this build, "n2db" over a version bumped on
+/// every change a reload can't read: `Catalog::open` refuses a pool with
+/// another one. Version 1 keeps the value length after each String column
+/// (`STRING_LEN_SIZE`) and `TS_READ_TS` in every tuple header, 2 adds
+/// `CATALOG_MAX_THREADS`.
+pub const FORMAT_VERSION: u64 = 0x6e32_6462_0000_0002;
+/// The `MAX_THREADS` the pool was made with, which sizes the header.
+pub const CATALOG_MAX_THREADS: Range<u64> = range!(CATALOG_FORMAT.end, U64_OFFSET);
+pub const TRANSACTION_PAGE_ADDRESS: Range<u64> = range!(
+    CATALOG_MAX_THREADS.end,
+    2 * U64_OFFSET * TRANSACTION_COUNT as u64
+);
 pub const TRANSACTION_TS_ADDRESS: Range<u64> = range!(
     TRANSACTION_PAGE_ADDRESS.end,
     2 * U64_OFFSET * TRANSACTION_COUNT as u64
 );
 
-pub const CATELOG_HEADER: u64 = TRANSACTION_TS_ADDRESS.end;
-pub const CLOG_MAX_PAGES: u64 = 0;
+/// Above every tick the timestamp oracle handed out, see `ToSnapShot`.
+pub const TICK_HIGH_WATER: Range<u64> = range!(TRANSACTION_TS_ADDRESS.end, U64_OFFSET);
+
+/// The per-thread words come before the tables, so where the table
+/// descriptions start moves with `TRANSACTION_COUNT`: a database reloads
+/// only with the `MAX_THREADS` it was made with.
+pub const CATELOG_HEADER: u64 = TICK_HIGH_WATER.end;
+/// Segments of the commit log kept at once, see `Clog`.
+pub const CLOG_MAX_PAGES: u64 = 32;
 pub const CLOG_SPACE_SIZE: u64 = CLOG_MAX_PAGES * U64_OFFSET + U64_OFFSET;
+const _: () = assert!(CATELOG_HEADER + CLOG_SPACE_SIZE < PAGE_SIZE);
 
 /// The data part of the Catalog is a variable-length table that holds the table's metadata.
 /// Catelog stores a HashMap in memory for quick lookups of table
@@ -126,60 +150,211 @@ pub struct Catalog {
     table_space: RwLock<VariableTable>,
     clog: Clog,
     snapshot: SnapShot,
+    cc: AtomicU8,
+    lock_waits: LockWaits,
+    ssi: Ssi,
+    gc: VersionGc,
+    thread_ids: Arc<ThreadIds>,
+    /// Per thread, the tid of the running read-write transaction or the
+    /// snapshot pinned by a read-only one with `POW_2_63` set, 0 when idle.
+    active: Vec<AtomicU64>,
 }
 unsafe impl Send for Catalog {}
 unsafe impl Sync for Catalog {}
 
 pub static CATALOG: OnceCell<Catalog> = OnceCell::new();
 
+/// Time spent in each step of `Catalog::recover`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RecoveryReport {
+    /// Table descriptions read from the catalog page.
+    pub catalog: Duration,
+    /// Tables reloaded from their pages.
+    pub tables: Duration,
+    /// Transaction buffers rolled back.
+    pub undo: Duration,
+    /// Indexes declared and filled.
+    pub index: Duration,
+    /// Timestamp oracle moved past the tids persisted.
+    pub timestamp: Duration,
+}
+
+impl RecoveryReport {
+    pub fn total(&self) -> Duration {
+        self.catalog + self.tables + self.undo + self.index + self.timestamp
+    }
+}
+
+impl Display for RecoveryReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "catalog {} ns, tables {} ns, undo {} ns, index {} ns, timestamp {} ns, total {} ns",
+            self.catalog.as_nanos(),
+            self.tables.as_nanos(),
+            self.undo.as_nanos(),
+            self.index.as_nanos(),
+            self.timestamp.as_nanos(),
+            self.total().as_nanos()
+        )
+    }
+}
+
 impl Catalog {
     pub fn get_address(&self) -> Address {
         self.address
     }
     pub fn new(address: Address) -> Self {
-        Catalog {
+        let catalog = Catalog {
             address,
             table_index: RwLock::new(HashMap::new()),
             table_space: RwLock::new(VariableTable::new(
                 address + CATELOG_HEADER,
                 PAGE_SIZE - CATELOG_HEADER - CLOG_SPACE_SIZE,
             )),
-            clog: Clog::new(),
+            clog: Clog::new(address + PAGE_SIZE - CLOG_SPACE_SIZE),
             snapshot: SnapShot::new(),
+            cc: AtomicU8::new(ConcurrencyControl::default().to_u8()),
+            lock_waits: LockWaits::new(),
+            ssi: Ssi::new(),
+            gc: VersionGc::new(),
+            thread_ids: Arc::new(ThreadIds::new()),
+            active: std::iter::repeat_with(|| AtomicU64::new(0))
+                .take(TRANSACTION_COUNT)
+                .collect(),
+        };
+        catalog
+            .snapshot
+            .persist_ticks(address + TICK_HIGH_WATER.start, false);
+        let format = address + CATALOG_FORMAT.start;
+        unsafe {
+            io::write(format, FORMAT_VERSION);
+            io::write(address + CATALOG_MAX_THREADS.start, TRANSACTION_COUNT as u64);
+            io::clwb(format as *const u8);
         }
-    }
-
-    pub fn reload(address: Address) {
-        let start = SystemTime::now();
-
+        file::sfence();
+        catalog
+    }
+
+    /// Reload the catalog at `address` as the global one, see
+    /// `reload_with`.
+    pub fn reload(address: Address) -> Result {
+        Catalog::reload_with(address, &mut RecoveryReport::default()).map(|_| ())
+    }
+    /// Reload the catalog at `address` and roll back what was running at the
+    /// crash, then `indexes` declares the indexes of the tables, which are
+    /// filled from their pages. Every step runs on `thread_count()` threads.
+    pub fn recover(address: Address, indexes: impl FnOnce(&Catalog)) -> Result<RecoveryReport> {
+        let mut report = RecoveryReport::default();
+        let catalog = Catalog::reload_with(address, &mut report)?;
+
+        let start = Instant::now();
+        catalog.redo_transaction();
+        report.undo = start.elapsed();
+
+        let start = Instant::now();
+        indexes(catalog);
+        catalog.rebuild_index();
+        report.index = start.elapsed();
+
+        // the tids of the buffers rolled back
+        let start = Instant::now();
+        catalog.reload_timestamp();
+        report.timestamp += start.elapsed();
+        Ok(report)
+    }
+    /// Open the catalog at `address` and install it as the global one. The
+    /// global catalog is set once, so this fails with `Error::Exist` before
+    /// touching the pages if one is already there.
+    fn reload_with(address: Address, report: &mut RecoveryReport) -> Result<&'static Catalog> {
+        if CATALOG.get().is_some() {
+            return Err(Error::Exist);
+        }
+        CATALOG
+            .set(Catalog::open(address, report)?)
+            .map_err(|_| Error::Exist)?;
+        Ok(Catalog::global())
+    }
+    /// The catalog at `address` with its tables, nothing rolled back yet.
+    /// Fails with `Error::PoolMismatch` on a pool of another
+    /// `FORMAT_VERSION` or `MAX_THREADS`.
+    fn open(address: Address, report: &mut RecoveryReport) -> Result<Catalog> {
+        let format = unsafe { io::read::<u64>(address + CATALOG_FORMAT.start) };
+        if format != FORMAT_VERSION {
+            return Err(Error::PoolMismatch {
+                field: "format",
+                found: format,
+                expected: FORMAT_VERSION,
+            });
+        }
+        let max_threads = unsafe { io::read::<u64>(address + CATALOG_MAX_THREADS.start) };
+        if max_threads != TRANSACTION_COUNT as u64 {
+            return Err(Error::PoolMismatch {
+                field: "max_threads",
+                found: max_threads,
+                expected: TRANSACTION_COUNT as u64,
+            });
+        }
+        let start = Instant::now();
         let catalog = Catalog {
             address,
             table_index: RwLock::new(HashMap::new()),
             table_space: RwLock::new(VariableTable::reload(address + CATELOG_HEADER)),
-            clog: Clog::new(),
+            clog: Clog::reload(address + PAGE_SIZE - CLOG_SPACE_SIZE),
             snapshot: SnapShot::new(),
+            cc: AtomicU8::new(ConcurrencyControl::default().to_u8()),
+            lock_waits: LockWaits::new(),
+            ssi: Ssi::new(),
+            gc: VersionGc::new(),
+            thread_ids: Arc::new(ThreadIds::new()),
+            active: std::iter::repeat_with(|| AtomicU64::new(0))
+                .take(TRANSACTION_COUNT)
+                .collect(),
         };
+        catalog
+            .snapshot
+            .persist_ticks(address + TICK_HIGH_WATER.start, true);
         let table_space = catalog.table_space.read().unwrap();
-        let headers = table_space.get_all_headers();
-        let mut table_index = catalog.table_index.write().unwrap();
-        let end = SystemTime::now();
+        let descriptions: Vec<TableDes> = table_space
+            .get_all_headers()
+            .into_iter()
+            .filter(|header| header.free_size == 0)
+            .map(|header| TableDes::reload(header.data_address))
+            .collect();
+        drop(table_space);
+        report.catalog = start.elapsed();
         println!(
             "Reload table des uses {} nano seconds",
-            end.duration_since(start).unwrap().as_nanos()
+            report.catalog.as_nanos()
         );
-        for header in headers {
-            if header.free_size == 0 {
-                let table_des = TableDes::reload(header.data_address);
-                let table = table_des.to_table(table_des.id() as u32);
-                // println!("111 {}, {}", table_des.table_name(), table_des.table_name().len());
-                table_index.insert(table_des.table_name(), Arc::new(table));
-            }
+
+        let start = Instant::now();
+        let tables: Vec<(String, Table)> = thread::scope(|scope| {
+            let handles: Vec<_> = descriptions
+                .iter()
+                .map(|table_des| {
+                    scope.spawn(move || {
+                        // println!("111 {}, {}", table_des.table_name(), table_des.table_name().len());
+                        (
+                            table_des.table_name(),
+                            table_des.to_table(table_des.id() as u32),
+                        )
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let mut table_index = catalog.table_index.write().unwrap();
+        for (name, table) in tables {
+            table_index.insert(name, Arc::new(table));
         }
-        drop(table_space);
         drop(table_index);
-        if CATALOG.get().is_none() {
-            CATALOG.set(catalog).unwrap();
-        }
+        report.tables = start.elapsed();
+
+        let start = Instant::now();
+        catalog.reload_timestamp();
+        report.timestamp = start.elapsed();
+        Ok(catalog)
     }
 
     pub fn add_table(&self, name: &str, schema: TableSchema) -> Result {
@@ -220,29 +395,63 @@ impl Catalog {
             .cloned()
             .unwrap()
     }
+    /// Name of the table with id `id`.
+    pub fn table_name(&self, id: u32) -> Option<String> {
+        self.table_index
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(_, table)| table.id == id)
+            .map(|(name, _)| name.clone())
+    }
     // pub fn get_table_mut(&self, table_name: &str) -> Arc<&mut Table>  {
     //     self.table_index.write().unwrap().get_mut(table_name).unwrap()
     // }
-    #[cfg(feature = "cc_cfg_occ")]
     pub fn get_clog(&self) -> &Clog {
         &self.clog
     }
     pub fn get_snapshot(&self) -> &SnapShot {
         &self.snapshot
     }
+    /// Thread ids held by a `TransactionBuffer`.
+    pub fn thread_ids(&self) -> &Arc<ThreadIds> {
+        &self.thread_ids
+    }
+    /// Lock policy and lock-wait statistics of 2PL.
+    pub fn lock_waits(&self) -> &LockWaits {
+        &self.lock_waits
+    }
+    pub fn get_ssi(&self) -> &Ssi {
+        &self.ssi
+    }
+    pub fn get_gc(&self) -> &VersionGc {
+        &self.gc
+    }
+    /// Protocol of the transactions begun from now on.
+    pub fn concurrency_control(&self) -> ConcurrencyControl {
+        ConcurrencyControl::from_u8(self.cc.load(Ordering::Relaxed))
+    }
+    /// Pick the protocol when opening the database, while no transaction
+    /// runs: the protocols share the tuple header words.
+    pub fn set_concurrency_control(&self, cc: ConcurrencyControl) {
+        self.cc.store(cc.to_u8(), Ordering::Relaxed);
+    }
     // pub fn get_snapshot_mut(&mut self) -> &mut SnapShot {
     //     &mut self.snapshot
     // }
+    /// The word holding the newest log page of `thread_id`.
+    pub fn transaction_page_slot(&self, thread_id: u64) -> Address {
+        self.address + TRANSACTION_PAGE_ADDRESS.start + thread_id * U64_OFFSET
+    }
     pub fn set_transaction_page_start(&self, thread_id: u64, address: Address) {
-        let iter = self.address + thread_id * U64_OFFSET;
+        let iter = self.transaction_page_slot(thread_id);
         unsafe {
             io::write(iter, address);
         }
         file::sfence();
     }
     pub fn get_transaction_page_start(&self, thread_id: u64) -> u64 {
-        let iter = CATALOG_ADDRESS + thread_id * U64_OFFSET;
-        unsafe { io::read(iter) }
+        unsafe { io::read(self.transaction_page_slot(thread_id)) }
     }
 
     #[inline]
@@ -271,80 +480,63 @@ impl Catalog {
             CATALOG.set(Catalog::new(CATALOG_ADDRESS)).unwrap();
         }
     }
+    /// `init_catalog` running transactions under `cc`.
+    pub fn init_catalog_with(cc: ConcurrencyControl) {
+        Catalog::init_catalog();
+        Catalog::global().set_concurrency_control(cc);
+    }
     pub fn global() -> &'static Catalog {
         CATALOG.get().unwrap()
     }
     // pub fn global_mut() -> std::sync::RwLockWriteGuard<'static, Catalog>  {
     //     CATALOG.get().unwrap().write().unwrap()
     // }
-    pub fn set_primary_key(&self, table_name: &str, key: usize) {
+    pub fn set_primary_key(&self, table_name: &str, columns: &[usize]) {
         let table = self.get_table(table_name);
-        let mut storage = NVMTableStorage::global_mut();
-        let table_address = storage.alloc_page().unwrap().page_start;
-        drop(storage);
-        //TODO Reload
-        let mut new_table = Table::new(table.schema.clone(), table.meta_page._address(), table.id);
+        // keep the pages, the indexes are filled by `rebuild_index`
+        let mut new_table =
+            Table::reload(table.schema.clone(), table.meta_page._address(), table.id);
         println!("create table id = {}, name = {}", table.id, table_name);
 
-        for key in table.get_index_key() {
-            new_table.add_index(*key).unwrap();
+        for columns in table.get_index_column_list() {
+            new_table.add_index(&columns).unwrap();
+        }
+        for columns in table.get_unique_index_list() {
+            new_table.add_unique_index(&columns).unwrap();
         }
-        new_table.set_primary_key(key).unwrap();
+        new_table.set_primary_key(columns).unwrap();
 
         let mut table_index = self.table_index.write().unwrap();
         table_index.insert(String::from(table_name), Arc::new(new_table));
     }
-    pub fn set_range_primary_key(&self, table_name: &str, key: usize) {
+    pub fn set_range_primary_key(&self, table_name: &str, columns: &[usize]) {
         let table = self.get_table(table_name);
-        let mut storage = NVMTableStorage::global_mut();
-        let table_address = storage.alloc_page().unwrap().page_start;
-        drop(storage);
-        //TODO Reload
-        let mut new_table = Table::new(table.schema.clone(), table.meta_page._address(), table.id);
+        // keep the pages, the indexes are filled by `rebuild_index`
+        let mut new_table =
+            Table::reload(table.schema.clone(), table.meta_page._address(), table.id);
         println!("create table id = {}, name = {}", table.id, table_name);
 
-        for key in table.get_index_key() {
-            new_table.add_index(*key).unwrap();
+        for columns in table.get_index_column_list() {
+            new_table.add_index(&columns).unwrap();
         }
-        new_table.set_range_primary_key(key).unwrap();
+        for columns in table.get_unique_index_list() {
+            new_table.add_unique_index(&columns).unwrap();
+        }
+        new_table.set_range_primary_key(columns).unwrap();
         // new_table.set_primary_key(table.get_primary_key()).unwrap();
 
         let mut table_index = self.table_index.write().unwrap();
         table_index.insert(String::from(table_name), Arc::new(new_table));
     }
-    pub fn add_index_by_name(&self, table_name: &str, key: &str) {
+    /// Build an index on a table in use, see `Table::create_index`.
+    pub fn add_index_by_name(&self, table_name: &str, keys: &[&str]) {
         let table = self.get_table(table_name);
-        let mut storage = NVMTableStorage::global_mut();
-        let table_address = storage.alloc_page().unwrap().page_start;
-        drop(storage);
-        //TODO Reload
-        let mut new_table = Table::new(table.schema.clone(), table_address, table.id);
-        println!("create table id = {}, name = {}", table.id, table_name);
-        for key in table.get_index_key() {
-            new_table.add_index(*key).unwrap();
-        }
-        new_table.add_index_by_name(key).unwrap();
-        new_table.set_primary_key(table.get_primary_key()).unwrap();
-
-        let mut table_index = self.table_index.write().unwrap();
-        table_index.insert(String::from(table_name), Arc::new(new_table));
+        table.create_index_by_name(keys).unwrap();
     }
-    pub fn add_range_index_by_name(&self, table_name: &str, key: &str) {
+    /// Build an index on a table in use, see `Table::create_range_index`.
+    pub fn add_range_index_by_name(&self, table_name: &str, keys: &[&str]) {
         let table = self.get_table(table_name);
-        let mut storage = NVMTableStorage::global_mut();
-        let table_address = storage.alloc_page().unwrap().page_start;
-        drop(storage);
-        //TODO Reload
-        let mut new_table = Table::new(table.schema.clone(), table_address, table.id);
-        println!("create table id = {}, name = {}", table.id, table_name);
-        for key in table.get_index_key() {
-            new_table.add_index(*key).unwrap();
-        }
-        new_table.add_range_index_by_name(key).unwrap();
-        new_table.set_primary_key(table.get_primary_key()).unwrap();
-
-        let mut table_index = self.table_index.write().unwrap();
-        table_index.insert(String::from(table_name), Arc::new(new_table));
+        table.create_range_index_by_name(keys).unwrap();
     }
     /// after index created
     #[cfg(feature = "buffer_pool")]
@@ -355,52 +547,152 @@ impl Catalog {
         drop(storage);
         //TODO Reload
         let mut new_table = Table::new(table.schema.clone(), table_address, table.id);
-        for key in table.get_index_key() {
+        for columns in table.get_index_column_list() {
             if table_name == "usertable"
             {
                 #[cfg(feature = "ycsb_e")]
-                new_table.add_range_index(*key).unwrap();
+                new_table.add_range_index(&columns).unwrap();
                 #[cfg(not(feature = "ycsb_e"))]
-                new_table.add_index(*key).unwrap();
+                new_table.add_index(&columns).unwrap();
             } else if table_name == "ORDER-LINE" || table_name == "NEW-ORDER" {
-                new_table.add_range_index(*key).unwrap();
+                new_table.add_range_index(&columns).unwrap();
             }
             else {
-                new_table.add_index(*key).unwrap();
+                new_table.add_index(&columns).unwrap();
             }
         }
+        for columns in table.get_unique_index_list() {
+            new_table.add_unique_index(&columns).unwrap();
+        }
         new_table.set_pool_size(size);
-        new_table.set_primary_key(table.get_primary_key()).unwrap();
+        new_table
+            .set_primary_key(&table.get_index_columns(table.get_primary_key()))
+            .unwrap();
         let mut table_index = self.table_index.write().unwrap();
         table_index.insert(String::from(table_name), Arc::new(new_table));
     }
 
-    #[cfg(feature = "cc_cfg_occ")]
     pub fn finished(&self, tid: u64) -> bool {
-        let ts = TimeStamp { tid };
+        let ts = TimeStamp {
+            tid,
+            ..TimeStamp::default()
+        };
         SnapShot::is_finished(ts, &self.clog)
     }
 
+    /// The oldest version any transaction may still read: the oldest running
+    /// transaction or pinned read-only snapshot, the clock while nothing
+    /// runs. Idle threads hold nothing back, 0 before the catalog is made.
     #[inline]
     pub fn get_min_txn() -> u64 {
-        let mut min_txn = Catalog::get_transaction_ts(0);
-        for i in 1..TRANSACTION_COUNT {
-            min_txn = min(min_txn, Catalog::get_transaction_ts(i as u64));
-        }
-        min_txn
-    }
+        let catalog = match CATALOG.get() {
+            Some(catalog) => catalog,
+            None => return 0,
+        };
+        // read before the active ones: a transaction announced after them
+        // takes its snapshot after the clock
+        let mut snapshot = SnapShotEntity::new();
+        catalog.snapshot.get_snapshot(&mut snapshot);
+        Catalog::get_min_active().map_or(snapshot.clock, |min_txn| min_txn.min(snapshot.clock))
+    }
+    /// The oldest tid of a running transaction or pinned snapshot, None
+    /// while nothing runs.
+    pub fn get_min_active() -> Option<u64> {
+        CATALOG
+            .get()?
+            .active
+            .iter()
+            .map(|active| active.load(Ordering::SeqCst) & !POW_2_63)
+            .filter(|ts| *ts != 0)
+            .min()
+    }
+    /// Announce the transaction of `thread_id`: a lower bound of its tid
+    /// before taking it, then the tid.
+    pub fn set_active(&self, thread_id: usize, tid: u64) {
+        self.active[thread_id].store(tid, Ordering::SeqCst);
+    }
+    /// Pin the snapshot of a read-only transaction, the versions it sees
+    /// stay until `clear_active`.
+    pub fn pin_snapshot(&self, thread_id: usize, clock: u64) {
+        self.active[thread_id].store(clock | POW_2_63, Ordering::SeqCst);
+    }
+    pub fn clear_active(&self, thread_id: usize) {
+        self.active[thread_id].store(0, Ordering::SeqCst);
+    }
+    /// The latest snapshot not after `clock` that no running read-write
+    /// transaction commits into: every writer with a tid in it finished.
+    pub fn stable_snapshot(&self, clock: u64) -> u64 {
+        self.active
+            .iter()
+            .map(|active| active.load(Ordering::SeqCst))
+            .filter(|ts| *ts != 0 && *ts & POW_2_63 == 0)
+            .map(|tid| tid - 1)
+            .fold(clock, min)
+    }
+    /// Move the timestamp oracle past every tid persisted, `reload` does.
     pub fn reload_timestamp(&self) {
-        let mut max_txn = Catalog::get_transaction_ts(0);
-        for i in 1..TRANSACTION_COUNT {
-            max_txn = max(max_txn, Catalog::get_transaction_ts(i as u64));
+        let mut max_txn = 0;
+        for i in 0..TRANSACTION_COUNT as u64 {
+            let ts = self.address + TRANSACTION_TS_ADDRESS.start + i * U64_OFFSET;
+            max_txn = max(max_txn, unsafe { io::read::<u64>(ts) });
         }
+        // commit stamps of the clog snapshot run ahead of the tids
+        max_txn = max(max_txn, self.clog.max_status());
         println!("reload timestamp {}", max_txn);
         self.snapshot.reload_clock(max_txn + 1);
     }
-    pub fn redo_transaction(&self) {
-        for i in 0..TRANSACTION_COUNT {
-            TransactionBuffer::reload(self, i as u64);
+    /// Fill the indexes of every table from its pages after a reload, the
+    /// tables at once with `thread_count()` threads between them.
+    pub fn rebuild_index(&self) {
+        let start = SystemTime::now();
+        let table_index = self.table_index.read().unwrap();
+        let threads = max(thread_count() / table_index.len().max(1), 1);
+        let count: u64 = thread::scope(|scope| {
+            let handles: Vec<_> = table_index
+                .values()
+                .map(|table| scope.spawn(move || table.rebuild_index(threads)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        let end = SystemTime::now();
+        println!(
+            "Rebuild index of {} rows uses {} nano seconds",
+            count,
+            end.duration_since(start).unwrap().as_nanos()
+        );
+    }
+    /// Roll back the buffers of every thread and return the number of
+    /// records rolled back. The transactions that did not commit only wrote
+    /// tuples they locked, their records are undone on `thread_count()`
+    /// threads at once. Committed ones may have written the same tuples one
+    /// after the other, so their records are redone one transaction at a
+    /// time in tid order, the newest write last.
+    pub fn redo_transaction(&self) -> u64 {
+        let (mut redo, undo): (Vec<_>, Vec<_>) = (0..TRANSACTION_COUNT as u64)
+            .map(|thread_id| TransactionBuffer::pending(self, thread_id))
+            .partition(|pending| pending.committed().is_some());
+        let threads = min(thread_count(), TRANSACTION_COUNT);
+        let mut batches: Vec<Vec<PendingRecords>> =
+            (0..threads).map(|_| Vec::new()).collect();
+        for (i, pending) in undo.into_iter().enumerate() {
+            batches[i % threads].push(pending);
         }
+        let undone: u64 = thread::scope(|scope| {
+            let handles: Vec<_> = batches
+                .into_iter()
+                .map(|batch| {
+                    scope.spawn(move || {
+                        batch
+                            .into_iter()
+                            .map(|pending| pending.replay(self))
+                            .sum::<u64>()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        redo.sort_by_key(|pending| pending.committed());
+        undone + redo.into_iter().map(|pending| pending.replay(self)).sum::<u64>()
     }
 }
 
@@ -452,11 +744,84 @@ mod tests {
         schema.push(ColumnType::Int64, "a");
         schema.push(ColumnType::Double, "b");
         catalog.add_table(table_name, schema).unwrap();
-        Catalog::reload(NVM_ADDR);
-        let reloaded_catalog = Catalog::global();
+        let reloaded_catalog = Catalog::open(NVM_ADDR, &mut RecoveryReport::default()).unwrap();
         let table = reloaded_catalog.get_table(&String::from(table_name));
         let columns = table.schema.columns();
         assert_eq!(columns[0].type_, ColumnType::Int64);
         assert_eq!(columns[1].type_, ColumnType::Double);
+
+        // the global catalog is not replaced
+        Catalog::init_catalog();
+        assert!(matches!(Catalog::reload(NVM_ADDR), Err(Error::Exist)));
+    }
+
+    #[test]
+    fn test_reload_header() {
+        NVMTableStorage::init_test_database();
+        let address = NVMTableStorage::global_mut()
+            .alloc_page()
+            .unwrap()
+            .page_start;
+        let catalog = Catalog::new(address);
+        // the slot of the last thread id ends just before the tables
+        let last = TRANSACTION_COUNT as u64 - 1;
+        assert!(catalog.transaction_page_slot(last) < address + CATELOG_HEADER);
+        catalog.set_transaction_page_start(last, PAGE_SIZE);
+        let mut schema = TableSchema::new();
+        schema.push(ColumnType::Int64, "a");
+        catalog.add_table("header", schema).unwrap();
+
+        let reloaded = Catalog::open(address, &mut RecoveryReport::default()).unwrap();
+        assert_eq!(reloaded.get_transaction_page_start(last), PAGE_SIZE);
+        let table = reloaded.get_table("header");
+        assert_eq!(table.schema.columns()[0].type_, ColumnType::Int64);
+
+        // a pool of another thread count or layout is refused
+        unsafe { io::write(address + CATALOG_MAX_THREADS.start, 48u64) };
+        assert!(matches!(
+            Catalog::open(address, &mut RecoveryReport::default()),
+            Err(Error::PoolMismatch { field: "max_threads", found: 48, .. })
+        ));
+        unsafe { io::write(address + CATALOG_FORMAT.start, 0u64) };
+        assert!(matches!(
+            Catalog::open(address, &mut RecoveryReport::default()),
+            Err(Error::PoolMismatch { field: "format", .. })
+        ));
+    }
+
+    #[cfg(feature = "ilog")]
+    #[test]
+    fn test_redo_order() {
+        use crate::mvcc_config::{delta, THREAD_ID_BITS};
+        use crate::storage::delta::TupleDelta;
+        use crate::storage::row::{Tuple, TUPLE_HEADER};
+        use std::convert::TryInto;
+        NVMTableStorage::init_test_database();
+        let address = NVMTableStorage::global_mut()
+            .alloc_page()
+            .unwrap()
+            .page_start;
+        let tuple_address = NVMTableStorage::global_mut()
+            .alloc_page()
+            .unwrap()
+            .page_start;
+        let catalog = Catalog::new(address);
+        // two committed writes of one tuple pending at the crash, the newer
+        // on the lower thread id
+        for &(thread_id, tick, value) in [(1u64, 1u64, 1u64), (0, 2, 2)].iter() {
+            let mut buffer = TransactionBuffer::new(&catalog, thread_id);
+            buffer.begin(tick << THREAD_ID_BITS | thread_id);
+            let mut record = TupleDelta::new(buffer.alloc(), 0, tuple_address).unwrap();
+            record.set_meta_data(delta::DELTA_COLUMN_OFFSET, TUPLE_HEADER as u32);
+            record.set_meta_data(delta::TID, TimeStamp::default());
+            record.save(&value.to_le_bytes());
+            buffer.save_redo(record.len() + U64_OFFSET);
+            buffer.commit(true);
+        }
+        assert_eq!(catalog.redo_transaction(), 2);
+        let tuple = Tuple::reload(tuple_address);
+        let data = tuple.get_data_by_column(TUPLE_HEADER..TUPLE_HEADER + 8);
+        assert_eq!(u64::from_le_bytes(data.try_into().unwrap()), 2);
+        assert_eq!(catalog.get_transaction_page_start(0), 0);
     }
 }
@@ -73,14 +73,19 @@ impl TupleDelta {
     pub fn data(&self) -> &[u8] {
         self.delta_data.data()
     }
-    pub fn rollback(&self, committed: u64) {
+    pub fn rollback(&self, committed: bool) {
         let tuple_address: u64 = self.get_meta_data(TUPLE_ADDRESS);
 
         let tuple = Tuple::reload(tuple_address);
         let next_address = tuple.next_address();
         let ts = tuple.ts();
         let index: u32 = self.get_meta_data(DELTA_COLUMN_OFFSET);
-        if committed == 1 {
+        if index == INSERT_COLUMN_OFFSET {
+            // the commit mark may not have made it before the crash
+            if committed {
+                tuple.commit();
+            }
+        } else if committed {
             tuple.update_data_by_column(index as u64, self.data());
         }
         tuple.set_ts_and_next(ts, self.get_meta_data(TID), next_address, 0);
@@ -2,7 +2,7 @@ use std::time::{Duration, Instant, SystemTime};
 
 use once_cell::sync::OnceCell;
 
-use crate::{mvcc_config::THREAD_COUNT, tpcc::u64_rand};
+use crate::tpcc::u64_rand;
 
 pub const BUFFER_SWAP: usize = 0;
 pub const BUFFER: usize = 0;
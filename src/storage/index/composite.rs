@@ -0,0 +1,129 @@
+use std::ops::Bound;
+
+#[cfg(feature = "rust_map")]
+use bztree::BzTree;
+#[cfg(not(feature = "rust_map"))]
+use concurrent_map::ConcurrentMap;
+
+use crate::storage::table::TupleId;
+
+/// Ordered index over encoded composite keys, see `storage::table::key`.
+///
+/// nbtree only orders u64 keys and dash, `DashString` included, only hashes
+/// them, so composite keys, whose prefixes are scanned in order, live in a
+/// rust ordered map in DRAM whatever index backend is selected. Recovery
+/// fills it again with `Table::rebuild_index`.
+pub struct CompositeIndex {
+    #[cfg(feature = "rust_map")]
+    tree: BzTree<Vec<u8>, TupleId>,
+    #[cfg(not(feature = "rust_map"))]
+    tree: ConcurrentMap<Vec<u8>, TupleId>,
+}
+
+impl std::fmt::Debug for CompositeIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CompositeIndex")
+    }
+}
+
+impl CompositeIndex {
+    pub fn new() -> Self {
+        CompositeIndex {
+            tree: Default::default(),
+        }
+    }
+
+    #[cfg(feature = "rust_map")]
+    pub fn insert(&self, key: Vec<u8>, value: TupleId) {
+        self.tree.upsert(key, value, &crossbeam_epoch::pin());
+    }
+    #[cfg(not(feature = "rust_map"))]
+    pub fn insert(&self, key: Vec<u8>, value: TupleId) {
+        self.tree.insert(key, value);
+    }
+
+    #[cfg(feature = "rust_map")]
+    pub fn remove(&self, key: &Vec<u8>) {
+        self.tree.delete(key, &crossbeam_epoch::pin());
+    }
+    #[cfg(not(feature = "rust_map"))]
+    pub fn remove(&self, key: &Vec<u8>) {
+        self.tree.remove(key);
+    }
+
+    #[cfg(feature = "rust_map")]
+    pub fn get(&self, key: &Vec<u8>) -> Option<TupleId> {
+        self.tree
+            .get(key, &crossbeam_epoch::pin())
+            .map(|v| v.clone())
+    }
+    #[cfg(not(feature = "rust_map"))]
+    pub fn get(&self, key: &Vec<u8>) -> Option<TupleId> {
+        self.tree.get(key)
+    }
+
+    /// Replace the address of `key` if it is still `old`.
+    /// On failure returns the current address, or None if the key is missing.
+    #[cfg(feature = "rust_map")]
+    pub fn cas(&self, key: &Vec<u8>, old: u64, new: u64) -> std::result::Result<(), Option<u64>> {
+        match self.tree.get(key, &crossbeam_epoch::pin()) {
+            Some(v) => {
+                let result = v.cas(old, new);
+                if result == old {
+                    Ok(())
+                } else {
+                    Err(Some(result))
+                }
+            }
+            None => Err(None),
+        }
+    }
+    #[cfg(not(feature = "rust_map"))]
+    pub fn cas(&self, key: &Vec<u8>, old: u64, new: u64) -> std::result::Result<(), Option<u64>> {
+        match self.tree.cas(
+            key.clone(),
+            Some(&TupleId::from_address(old)),
+            Some(TupleId::from_address(new)),
+        ) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.actual.map(|v| v.get_address())),
+        }
+    }
+
+    /// Up to `count` entries with keys in (lower, upper), in key order from
+    /// the low end, or from the high end if `descending`.
+    #[cfg(feature = "rust_map")]
+    pub fn scan(
+        &self,
+        lower: Bound<Vec<u8>>,
+        upper: Bound<Vec<u8>>,
+        descending: bool,
+        count: usize,
+    ) -> Vec<(Vec<u8>, TupleId)> {
+        let guard = crossbeam_epoch::pin();
+        let range = self
+            .tree
+            .range((lower, upper), &guard)
+            .map(|(k, v)| (k.clone(), v.clone()));
+        if descending {
+            range.rev().take(count).collect()
+        } else {
+            range.take(count).collect()
+        }
+    }
+    #[cfg(not(feature = "rust_map"))]
+    pub fn scan(
+        &self,
+        lower: Bound<Vec<u8>>,
+        upper: Bound<Vec<u8>>,
+        descending: bool,
+        count: usize,
+    ) -> Vec<(Vec<u8>, TupleId)> {
+        let range = self.tree.range((lower, upper));
+        if descending {
+            range.rev().take(count).collect()
+        } else {
+            range.take(count).collect()
+        }
+    }
+}
@@ -5,8 +5,7 @@ use std::sync::{Arc, Mutex};
 use crate::c::ffi::*;
 use crate::storage::table::TupleId;
 use crate::Result;
-use libc::{c_int, c_void};
-use std::ffi::{CString, NulError};
+use libc::{c_char, c_int, c_void};
 
 #[derive(Debug, Clone)]
 pub struct DashString {
@@ -22,52 +21,38 @@ impl DashString {
             root: unsafe { dashstring_create() },
         }
     }
-    pub fn insert<S>(&self, key: S, length: usize, value: TupleId) -> Option<u64>
-    where
-        S: Into<String>,
-    {
-        let ckey = std::ffi::CString::new(key.into()).expect("CString::new failed");
+    /// Keys are raw bytes, passed to dash with their length, and may hold
+    /// any byte including NUL.
+    pub fn insert(&self, key: &[u8], value: TupleId) -> Option<u64> {
         unsafe {
             dashstring_insert(
                 self.root,
-                ckey.as_ptr(),
-                length as c_int,
+                key.as_ptr() as *const c_char,
+                key.len() as c_int,
                 value.get_address(),
             )
         };
         Some(0)
     }
-    pub fn update<S>(&self, key: S, length: usize, value: TupleId) -> Option<u64>
-    where
-        S: Into<String>,
-    {
-        let ckey = std::ffi::CString::new(key.into()).expect("CString::new failed");
+    pub fn update(&self, key: &[u8], value: TupleId) -> Option<u64> {
         unsafe {
             dashstring_update(
                 self.root,
-                ckey.as_ptr(),
-                length as c_int,
+                key.as_ptr() as *const c_char,
+                key.len() as c_int,
                 value.get_address(),
             )
         };
         Some(0)
     }
-    pub fn get<S>(&self, key: S, length: usize) -> Option<TupleId>
-    where
-        S: Into<String>,
-    {
-        let ckey = std::ffi::CString::new(key.into()).unwrap();
-        let v = unsafe { dashstring_find(self.root, ckey.as_ptr(), length as c_int) };
+    pub fn get(&self, key: &[u8]) -> Option<TupleId> {
+        let v = unsafe { dashstring_find(self.root, key.as_ptr() as *const c_char, key.len() as c_int) };
         Some(TupleId {
             page_start: AtomicU64::new(v & MASK),
         })
     }
-    pub fn remove<S>(&self, key: S, length: usize) -> Option<TupleId>
-    where
-        S: Into<String>,
-    {
-        let ckey = std::ffi::CString::new(key.into()).unwrap();
-        let v = unsafe { dashstring_remove(self.root, ckey.as_ptr(), length as c_int) };
+    pub fn remove(&self, key: &[u8]) -> Option<TupleId> {
+        let v = unsafe { dashstring_remove(self.root, key.as_ptr() as *const c_char, key.len() as c_int) };
         Some(TupleId {
             page_start: AtomicU64::new(v & MASK),
         })
@@ -1,7 +1,8 @@
 // pub mod nbtree;
+pub mod composite;
 #[cfg(feature = "dash")]
 pub mod dash;
 #[cfg(feature = "dash")]
 pub mod dashstring;
 #[cfg(feature = "nbtree")]
-pub mod nbtree;
\ No newline at end of file
+pub mod nbtree;
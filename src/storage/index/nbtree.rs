@@ -51,6 +51,42 @@ impl<T> NBTree<T> {
         
         return result;
     }
+    /// Like `range`, stopping after `count` values.
+    pub fn scan(&self, start: &u64, end: &u64, count: usize) -> Vec<TupleId> {
+        let mut result = Vec::new();
+        if count == 0 {
+            return result;
+        }
+        unsafe {
+            let mut item = btree_scan(self.root, *start, *end);
+            while item > 0 {
+                result.push(TupleId{page_start: AtomicU64::new(item & MASK)});
+                if result.len() == count {
+                    break;
+                }
+                item = btree_next(self.root, *start, *end);
+            }
+        }
+        result
+    }
+    /// Like `scan` from the high end of the range, values in descending key
+    /// order. The tree only iterates forward, so this scans windows of keys
+    /// below `end`, each twice as wide as the one before, until they hold
+    /// `count` values or reach `start`. No value is visited twice.
+    pub fn scan_rev(&self, start: &u64, end: &u64, count: usize) -> Vec<TupleId> {
+        let mut result = Vec::new();
+        let mut upper = *end;
+        let mut width = count as u64;
+        while result.len() < count && *start < upper {
+            let lower = upper.saturating_sub(width).max(*start);
+            let window = self.range(&lower, &upper);
+            let wanted = count - result.len();
+            result.extend(window.into_iter().rev().take(wanted));
+            upper = lower;
+            width = width.saturating_mul(2);
+        }
+        result
+    }
     pub fn last(&self, start: &u64, end: &u64) -> Option<TupleId> {
         unsafe {
             // println!("{}, {}", start, end);
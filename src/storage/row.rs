@@ -23,9 +23,10 @@ use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 
-#[cfg(feature = "read_ts")]
 use crate::storage::timestamp::TS_READ_TS;
 
+/// Tuple header: `TID` (tid and read ts, see `TS_READ_TS`),
+/// `NEXT_DELTA_ADDRESS`, `DELETE_FLAG`, `LOCK_TID`, then the row data.
 pub const TID: Range<u64> = range!(0, size_of::<TimeStamp>() as u64);
 pub const NEXT_DELTA_ADDRESS: Range<u64> = range!(TID.end, U64_OFFSET);
 pub const DELETE_FLAG: Range<u64> = range!(NEXT_DELTA_ADDRESS.end, U64_OFFSET);
@@ -146,7 +147,11 @@ impl Tuple {
                     let zeros = vec![0u8; *len - data_len];
                     self.data.copy_from_slice(offset + data_len as u64, &zeros);
                 }
-                return *len as u64;
+                // the padding is not part of the value, so keep its length
+                // for the indexes, see `TableSchema::get_column_value`
+                self.data
+                    .copy_from_slice(offset + *len as u64, &(data_len as u32).to_le_bytes());
+                return data_type.size() as u64;
             }
         }
     }
@@ -220,23 +225,12 @@ impl Tuple {
         }
     }
 
-    #[cfg(feature = "read_ts")]
     pub fn set_read_ts(&self, read_ts: u64) {
         // debug!("read {} {}", self._address(), read_ts);
         let address: u64 = (self._address() + TID.start + TS_READ_TS.start) as u64;
         let ts = unsafe { &*(address as *const AtomicU64) };
-        loop {
-            let old_ts = ts.load(Ordering::Relaxed);
-            if old_ts >= read_ts {
-                break;
-            }
-            if ts
-                .compare_exchange(old_ts, read_ts, Ordering::SeqCst, Ordering::SeqCst)
-                .is_ok()
-            {
-                break;
-            }
-        }
+        // only ever moves forward, concurrent readers race here
+        ts.fetch_max(read_ts, Ordering::SeqCst);
     }
     pub fn set_ts_and_next(
         &self,
@@ -344,7 +338,15 @@ impl Tuple {
             io::clwb(address as *const u8);
         }
     }
-    #[cfg(all(feature = "cc_cfg_2pl", feature = "read_ts"))]
+    /// Mark the slot of a removed tuple free, recovery skips it.
+    pub fn free(&self) {
+        let address: u64 = (self._address() + DELETE_FLAG.start) as u64;
+        let u = unsafe { &*(address as *const AtomicU64) };
+        u.store(0, Ordering::Relaxed);
+        unsafe {
+            io::clwb(address as *const u8);
+        }
+    }
     pub fn lock_read(&self, reader: u64) -> Result<(), TupleError> {
         let address: u64 = (self._address() + TID.start + TS_READ_TS.start) as u64;
         let ts = unsafe { &*(address as *const AtomicU64) };
@@ -361,7 +363,6 @@ impl Tuple {
         }
         Ok(())
     }
-    #[cfg(all(feature = "cc_cfg_2pl", feature = "read_ts"))]
     pub fn release_read_lock(&self) {
         let address: u64 = (self._address() + TID.start + TS_READ_TS.start) as u64;
         let ts = unsafe { &*(address as *const AtomicU64) };
@@ -371,7 +372,6 @@ impl Tuple {
             ts.fetch_sub(1, Ordering::SeqCst);
         }
     }
-    #[cfg(all(feature = "cc_cfg_2pl", feature = "read_ts"))]
     pub fn lock_write(&self, txn_id: u64, counter: u64) -> Result<(), TupleError> {
         let address: u64 = (self._address() + TID.start + TS_READ_TS.start) as u64;
         let ts = unsafe { &*(address as *const AtomicU64) };
@@ -393,7 +393,6 @@ impl Tuple {
         }
         return Err(TupleError::AcquireWriteLockFalse);
     }
-    #[cfg(all(feature = "cc_cfg_2pl", feature = "read_ts"))]
     pub fn release_write_lock(&self) {
         // println!("{:x} release write_lock", self._address());
         let address: u64 = (self._address() + TID.start + TS_READ_TS.start) as u64;
@@ -665,6 +664,12 @@ pub enum TupleError {
 
     #[error("AcquireWriteLockFalse")]
     AcquireWriteLockFalse,
+
+    #[error("wounded by an older transaction")]
+    Wounded,
+
+    #[error("snapshot older than the versions kept")]
+    SnapshotTooOld,
 }
 
 #[derive(Debug)]
@@ -750,23 +755,11 @@ impl BufferDataVec {
         }
     }
 
-    #[cfg(feature = "read_ts")]
     pub fn set_read_ts(&self, read_ts: u64) {
         let ts = unsafe {
             &*(self._address().add((TID.start + TS_READ_TS.start) as usize) as *const AtomicU64)
         };
-        loop {
-            let old_ts = ts.load(Ordering::Relaxed);
-            if old_ts >= read_ts {
-                break;
-            }
-            if ts
-                .compare_exchange(old_ts, read_ts, Ordering::Relaxed, Ordering::Relaxed)
-                .is_ok()
-            {
-                break;
-            }
-        }
+        ts.fetch_max(read_ts, Ordering::SeqCst);
     }
     pub fn set_ts_and_next(
         &self,
@@ -843,7 +836,6 @@ impl BufferDataVec {
         tuple.set_lock_tid(ts.tid);
         self.data[0..size].copy_from_slice(tuple.get_data(size as u64));
     }
-    #[cfg(all(feature = "cc_cfg_2pl", feature = "read_ts"))]
     pub fn lock_write(&self, txn_id: u64, counter: u64) -> Result<(), TupleError> {
         let address: u64 =
             unsafe { (self._address().add((TID.start + TS_READ_TS.start) as usize)) as u64 };
@@ -869,7 +861,6 @@ impl BufferDataVec {
 
         return Err(TupleError::AcquireWriteLockFalse);
     }
-    #[cfg(all(feature = "cc_cfg_2pl", feature = "read_ts"))]
     pub fn lock_read(&self, reader: u64) -> Result<(), TupleError> {
         let address: u64 =
             unsafe { (self._address().add((TID.start + TS_READ_TS.start) as usize)) as u64 };
@@ -887,7 +878,6 @@ impl BufferDataVec {
         }
         Ok(())
     }
-    #[cfg(all(feature = "cc_cfg_2pl", feature = "read_ts"))]
     pub fn release_read_lock(&self) {
         let address: u64 =
             unsafe { (self._address().add((TID.start + TS_READ_TS.start) as usize)) as u64 };
@@ -899,7 +889,6 @@ impl BufferDataVec {
         }
         // println!("release read lock");
     }
-    #[cfg(all(feature = "cc_cfg_2pl", feature = "read_ts"))]
     pub fn release_write_lock(&self) {
         // println!("{:x} release write_lock", self._address());
         let address: u64 =
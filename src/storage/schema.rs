@@ -24,6 +24,11 @@ pub enum ColumnType {
     String { len: usize },
 }
 
+/// Bytes of the value length stored after a String column. Pools made
+/// before it lay String columns out 4 bytes shorter, their catalog lacks
+/// `FORMAT_VERSION` and does not reload.
+pub const STRING_LEN_SIZE: usize = 4;
+
 impl ColumnType {
     pub fn len(&self) -> usize {
         match *self {
@@ -32,6 +37,14 @@ impl ColumnType {
             ColumnType::String { len } => len,
         }
     }
+    /// Bytes the column takes in a tuple, a String column keeps the length
+    /// of its value after the zero padded data, see `Tuple::push`.
+    pub fn size(&self) -> usize {
+        match *self {
+            ColumnType::String { len } => len + STRING_LEN_SIZE,
+            _ => self.len(),
+        }
+    }
 }
 
 use crate::storage::row::TUPLE_HEADER;
@@ -55,7 +68,7 @@ impl TableSchema {
     pub fn tuple_size(&self) -> usize {
         self.columns
             .last()
-            .map(|c| c.offset + c.type_.len())
+            .map(|c| c.offset + c.type_.size())
             .unwrap_or(TUPLE_HEADER)
     }
 
@@ -69,6 +82,24 @@ impl TableSchema {
         column.offset..column.offset + column.type_.len()
     }
 
+    /// Bytes of the value of column `id` out of the tuple data `data`
+    /// reads, a String column up to the length of its value.
+    pub fn get_column_value<'a, F>(&self, id: usize, data: F) -> &'a [u8]
+    where
+        F: Fn(core::ops::Range<usize>) -> &'a [u8],
+    {
+        let range = self.get_column_offset(id);
+        match self.columns[id].type_ {
+            ColumnType::String { len } => {
+                let end = range.end;
+                let value_len = data(end..end + STRING_LEN_SIZE).try_into().unwrap();
+                let value_len = u32::from_le_bytes(value_len) as usize;
+                &data(range)[..value_len.min(len)]
+            }
+            _ => data(range),
+        }
+    }
+
     pub fn get_column_type(&self, id: usize) -> ColumnType {
         let column = &self.columns[id];
         column.type_
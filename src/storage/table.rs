@@ -16,16 +16,18 @@ use super::index::dash::Dash;
 use super::index::dashstring::DashString;
 #[cfg(feature = "nbtree")]
 use super::index::nbtree::NBTree;
+use super::index::composite::CompositeIndex;
 use super::row::BufferDataVec;
 use super::row::COMMIT_MASK;
 use crate::storage::allocator::{DualPageAllocator, LocalPageAllocator, Page};
 use crate::{Error, Result};
 use crossbeam_epoch::Guard;
 use dashmap::DashMap;
-use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(feature = "rust_hash")]
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::convert::TryInto;
-use std::str;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::{AtomicU64, Ordering};
 use concurrent_map::ConcurrentMap;
@@ -34,7 +36,14 @@ use super::global::*;
 use super::row::BufferVec;
 pub mod buffer;
 pub mod crud;
+pub mod cursor;
 pub mod index;
+pub mod index_map;
+pub mod key;
+pub mod online;
+pub mod rebuild;
+pub use cursor::RangeCursor;
+use index_map::IndexMap;
 
 #[cfg(feature = "index_bplus_tree")]
 type Index<T> = BplusTree<T, TupleId>;
@@ -54,16 +63,23 @@ type RangeIndex<u64> = NBTree<u64>;
 type TupleAllocator = LocalPageAllocator;
 #[cfg(feature = "center_allocator")]
 type TupleAllocator = DualPageAllocator;
+
+/// Lock stripes serializing the duplicate check and insert of unique keys.
+const UNIQUE_LOCK_COUNT: usize = 1024;
+/// Composite indexes a table can hold.
+const MAX_COMPOSITE_INDEX: usize = 32;
 // #[derive(Debug)]
 pub enum TableIndex {
     Int64(Index<u64>),
     #[cfg(feature = "dash")]
     String(DashString),
     #[cfg(not(feature = "dash"))]
-    String(Index<String>),
+    String(Index<Vec<u8>>),
     Int64R(RangeIndex<u64>),
-    StringR(RangeIndex<String>),
-    
+    /// Ordered index over encoded keys, for composite indexes and ordered
+    /// String columns, see `storage::table::key`.
+    Composite(CompositeIndex),
+
     None,
 }
 
@@ -77,10 +93,37 @@ impl std::fmt::Debug for TableIndex {
         }
 	}
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum IndexType {
     Int64(u64),
     String(String),
+    /// Key of a string index that need not be UTF-8.
+    Bytes(Vec<u8>),
+    Double(f64),
+    /// Encoded key of a composite index, see `IndexType::composite`.
+    Composite(Vec<u8>),
+}
+
+impl IndexType {
+    /// Bytes a string index stores for a String or Bytes key, every byte of
+    /// the value, trailing zero bytes included.
+    pub fn string_key(&self) -> Option<&[u8]> {
+        match self {
+            IndexType::String(s) => Some(s.as_bytes()),
+            IndexType::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+    /// Build the key of a composite index from its column values, in index
+    /// column order. Giving only the first columns builds a prefix key for
+    /// `Table::prefix_cursor`.
+    pub fn composite(parts: &[IndexType]) -> Self {
+        let mut key = Vec::new();
+        for part in parts {
+            key::encode_index_type(&mut key, part);
+        }
+        IndexType::Composite(key)
+    }
 }
 #[derive(Debug)]
 pub struct TupleId {
@@ -169,10 +212,18 @@ pub struct Table {
     #[cfg(feature = "center_allocator")]
     allocator: TupleAllocator,
     primary_key: AtomicUsize,
+    /// A single column index uses the column id, composite indexes take ids
+    /// from `schema.columns().len()` on.
     #[cfg(not(feature = "lock_index"))]
-    index: HashMap<usize, TableIndex>,
+    index: IndexMap<TableIndex>,
     #[cfg(feature = "lock_index")]
-    index: HashMap<usize, RwLock<TableIndex>>,
+    index: IndexMap<RwLock<TableIndex>>,
+    /// Indexes added by `add_unique_index`, the primary key is always unique.
+    unique_index: HashSet<usize>,
+    /// Key locks of unique inserts and of writers to an index being built.
+    unique_lock: Vec<Mutex<()>>,
+    /// Serializes online index builds.
+    build_lock: Mutex<()>,
     // index_key: RwLock<Vec<usize>>,
     pub tuple_size: u64,
     max_tuple: u64,
@@ -207,13 +258,21 @@ impl Table {
         return size;
     }
 
+    /// Tuple size, tuples per page and start of the first tuple of the
+    /// pages of a table with `schema`. Slots are allocated from the page
+    /// start on with no bitmap in front, and `reload` has to find them
+    /// where `new` put them.
+    fn layout(schema: &TableSchema) -> (u64, u64, u64) {
+        let tuple_size = Table::align16_tuple_size(schema.tuple_size() as u64);
+        (tuple_size, Table::max_tuple(tuple_size), 0)
+    }
+
     // new&reload
     pub fn new(schema: TableSchema, address: Address, id: u32) -> Self {
         println!("Build table at {:X}", address);
-        let tuple_size = Table::align16_tuple_size(schema.tuple_size() as u64);
-        let max_tuple = Table::max_tuple(tuple_size);
-        let tuple_start = 0;
+        let (tuple_size, max_tuple, tuple_start) = Table::layout(&schema);
         let meta_page = PersistArray::new(address);
+        let index_capacity = schema.columns().len() + MAX_COMPOSITE_INDEX;
         #[cfg(feature = "buffer_pool")]
         let mut buffer_pointer: Vec<RwLock<usize>> = Vec::new();
         #[cfg(feature = "buffer_pool")]
@@ -225,7 +284,12 @@ impl Table {
             id,
             schema,
             primary_key: AtomicUsize::new(0),
-            index: HashMap::new(),
+            index: IndexMap::new(index_capacity),
+            unique_index: HashSet::new(),
+            unique_lock: std::iter::repeat_with(|| Mutex::new(()))
+                .take(UNIQUE_LOCK_COUNT)
+                .collect(),
+            build_lock: Mutex::new(()),
             #[cfg(feature = "local_allocator")]
             allocator: std::iter::from_fn(|| {
                 Some(RwLock::new(TupleAllocator::new(address, max_tuple as u32)))
@@ -255,10 +319,9 @@ impl Table {
     }
     pub fn reload(schema: TableSchema, address: Address, id: u32) -> Self {
         println!("reload table at {}", address);
-        let tuple_size = schema.tuple_size() as u64 + U64_OFFSET;
-        let max_tuple = Table::max_tuple(tuple_size);
-        let tuple_start = Table::tuple_start(max_tuple);
+        let (tuple_size, max_tuple, tuple_start) = Table::layout(&schema);
         let meta_page = PersistArray::reload(address);
+        let index_capacity = schema.columns().len() + MAX_COMPOSITE_INDEX;
         #[cfg(feature = "buffer_pool")]
         let mut buffer_pointer: Vec<RwLock<usize>> = Vec::new();
         #[cfg(feature = "buffer_pool")]
@@ -266,11 +329,16 @@ impl Table {
             buffer_pointer.push(RwLock::new(i * 100));
         }
         let mut table = Table {
-            tuple_size: schema.tuple_size() as u64,
+            tuple_size,
             id,
             schema,
             primary_key: AtomicUsize::new(0),
-            index: HashMap::new(),
+            index: IndexMap::new(index_capacity),
+            unique_index: HashSet::new(),
+            unique_lock: std::iter::repeat_with(|| Mutex::new(()))
+                .take(UNIQUE_LOCK_COUNT)
+                .collect(),
+            build_lock: Mutex::new(()),
             // index_key: RwLock::new(Vec::new()),
             #[cfg(feature = "local_allocator")]
             allocator: std::iter::from_fn(|| {
@@ -361,92 +429,143 @@ impl Table {
         Page::new(page_address, self.max_tuple as u32)
     }
 
-    pub fn set_primary_key(&mut self, key: usize) -> Result {
+    pub fn set_primary_key(&mut self, columns: &[usize]) -> Result {
         // let mut p = self.primary_key.write().unwrap();
+        let key = self.add_index(columns)?;
         self.primary_key
             .store(key, std::sync::atomic::Ordering::SeqCst);
-        if self.index.contains_key(&key) {
-            return Ok(());
-        }
-        self.add_index(key)
+        Ok(())
     }
-    pub fn set_range_primary_key(&mut self, key: usize) -> Result {
+    pub fn set_range_primary_key(&mut self, columns: &[usize]) -> Result {
         // let mut p = self.primary_key.write().unwrap();
+        let key = self.add_range_index(columns)?;
         self.primary_key
             .store(key, std::sync::atomic::Ordering::SeqCst);
-        if self.index.contains_key(&key) {
-            return Ok(());
-        }
-        self.add_range_index(key)
+        Ok(())
     }
     pub fn get_primary_key(&self) -> usize {
         self.primary_key.load(Ordering::Relaxed)
     }
-    pub fn add_index_by_name(&mut self, key: &str) -> Result {
-        self.add_index(self.schema.search_by_name(key).unwrap())
+    /// Add an index on `columns` that rejects duplicate keys with `Error::Exist`.
+    pub fn add_unique_index(&mut self, columns: &[usize]) -> Result<usize> {
+        let key = self.add_index(columns)?;
+        self.unique_index.insert(key);
+        Ok(key)
+    }
+    pub fn is_unique_index(&self, index_id: usize) -> bool {
+        index_id == self.get_primary_key() || self.unique_index.contains(&index_id)
     }
-    pub fn add_index(&mut self, key: usize) -> Result {
-        if self.index.contains_key(&key) {
-            return Ok(());
+    /// Columns of the indexes added by `add_unique_index`.
+    pub fn get_unique_index_list(&self) -> Vec<Vec<usize>> {
+        let mut list: Vec<usize> = self.unique_index.iter().cloned().collect();
+        list.sort();
+        list.into_iter().map(|id| self.get_index_columns(id)).collect()
+    }
+    fn search_columns_by_name(&self, keys: &[&str]) -> Result<Vec<usize>> {
+        let mut columns = Vec::new();
+        for key in keys {
+            columns.push(self.schema.search_by_name(key)?);
         }
-        let key_type = self.schema.columns()[key].type_;
-        println!("add_index_on {}", self.schema.columns()[key].name);
-        let index = &mut self.index;
-        match key_type {
-            ColumnType::Int64 => {
-                #[cfg(not(feature = "lock_index"))]
-                index.insert(key, TableIndex::Int64(Index::<u64>::new()));
-                #[cfg(feature = "lock_index")]
-                index.insert(key, RwLock::new(TableIndex::Int64(Index::<u64>::new())));
-            }
-            ColumnType::String { len: _ } => {
-                #[cfg(not(any(feature = "lock_index", feature = "dash")))]
-                index.insert(key, TableIndex::String(Index::<String>::new()));
-                #[cfg(feature = "dash")]
-                index.insert(key, TableIndex::String(DashString::new()));
-                #[cfg(feature = "lock_index")]
-                index.insert(key, RwLock::new(TableIndex::String(Index::<String>::new())));
-            }
-            _ => return Err(Error::Tuple(TupleError::IndexTypeNotSupported)),
+        Ok(columns)
+    }
+    /// Columns of index `index_id`, in key order.
+    pub fn get_index_columns(&self, index_id: usize) -> Vec<usize> {
+        if index_id < self.schema.columns().len() {
+            vec![index_id]
+        } else {
+            self.index.columns(index_id).unwrap().to_vec()
         }
-        // let mut index_key = self.index_key.write().unwrap();
-        // index_key.push(key);
-        Ok(())
     }
-    pub fn add_range_index_by_name(&mut self, key: &str) -> Result {
-        self.add_range_index(self.schema.search_by_name(key).unwrap())
+    /// Columns of every index by index id, so that re-adding them on a new
+    /// table keeps their index ids.
+    pub fn get_index_column_list(&self) -> Vec<Vec<usize>> {
+        let mut ids: Vec<usize> = self.get_index_key().cloned().collect();
+        ids.sort();
+        ids.into_iter().map(|id| self.get_index_columns(id)).collect()
+    }
+    /// Index id of the index on `columns`.
+    pub fn get_index_id(&self, columns: &[usize]) -> Option<usize> {
+        self.index.id_of(columns)
     }
-    pub fn add_range_index(&mut self, key: usize) -> Result {
-        if self.index.contains_key(&key) {
-            return Ok(());
+    pub fn get_index_id_by_name(&self, keys: &[&str]) -> Option<usize> {
+        match self.search_columns_by_name(keys) {
+            Ok(columns) => self.get_index_id(&columns),
+            Err(_) => None,
         }
-        let key_type = self.schema.columns()[key].type_;
-        println!("add_range_ndex_on {}", self.schema.columns()[key].name);
-        let index = &mut self.index;
-        match key_type {
-            ColumnType::Int64 => {
-                #[cfg(feature = "rust_map")]
-                index.insert(key, TableIndex::Int64R(RangeIndex::<u64>::default()));
-                #[cfg(feature = "nbtree")]
-                index.insert(key, TableIndex::Int64R(RangeIndex::<u64>::new()));
-            }
-            
-            ColumnType::String { len: _ } => {
-                #[cfg(feature = "rust_map")]
-                index.insert(key, TableIndex::StringR(RangeIndex::<String>::default()));
+    }
+    /// Index id and an empty index for `columns`, ordered if `ordered` or
+    /// over more than one column. A Double column always takes the range
+    /// backend, keyed by `key::order_f64`, as there is no hash index over
+    /// floats. The range backends only take u64 keys, so an ordered String
+    /// column is keyed by its encoded value, without the zero padding, like a
+    /// composite index.
+    fn new_index(&self, columns: &[usize], ordered: bool) -> Result<(usize, TableIndex)> {
+        if columns.len() != 1 {
+            return match self.index.vacant_from(self.schema.columns().len()) {
+                Some(id) => Ok((id, TableIndex::Composite(CompositeIndex::new()))),
+                None => Err(Error::NoSpace),
+            };
+        }
+        let key = columns[0];
+        let index = match (self.schema.columns()[key].type_, ordered) {
+            (ColumnType::Int64, false) => TableIndex::Int64(Index::<u64>::new()),
+            #[cfg(feature = "rust_map")]
+            (ColumnType::Int64, true) => TableIndex::Int64R(RangeIndex::<u64>::default()),
+            #[cfg(feature = "nbtree")]
+            (ColumnType::Int64, true) => TableIndex::Int64R(RangeIndex::<u64>::new()),
+            #[cfg(feature = "dash")]
+            (ColumnType::String { len: _ }, false) => TableIndex::String(DashString::new()),
+            #[cfg(not(feature = "dash"))]
+            (ColumnType::String { len: _ }, false) => {
+                TableIndex::String(Index::<Vec<u8>>::new())
             }
+            (ColumnType::String { len: _ }, true) => TableIndex::Composite(CompositeIndex::new()),
+            #[cfg(feature = "rust_map")]
+            (ColumnType::Double, _) => TableIndex::Int64R(RangeIndex::<u64>::default()),
+            #[cfg(feature = "nbtree")]
+            (ColumnType::Double, _) => TableIndex::Int64R(RangeIndex::<u64>::new()),
             _ => return Err(Error::Tuple(TupleError::IndexTypeNotSupported)),
+        };
+        Ok((key, index))
+    }
+    fn add_new_index(&mut self, columns: &[usize], ordered: bool) -> Result<usize> {
+        if let Some(id) = self.get_index_id(columns) {
+            return Ok(id);
         }
-        // let mut index_key = self.index_key.write().unwrap();
-        // index_key.push(key);
-        Ok(())
+        let mut names = Vec::new();
+        for column in columns {
+            names.push(self.schema.columns()[*column].name.clone());
+        }
+        if ordered {
+            println!("add_range_index_on {}", names.join(","));
+        } else {
+            println!("add_index_on {}", names.join(","));
+        }
+        let (id, index) = self.new_index(columns, ordered)?;
+        #[cfg(feature = "lock_index")]
+        let index = RwLock::new(index);
+        self.index.insert(id, columns.to_vec(), index);
+        Ok(id)
     }
-    #[cfg(not(feature = "lock_index"))]
-    pub fn get_index_key(&self) -> std::collections::hash_map::Keys<usize, TableIndex> {
-        self.index.keys()
+    pub fn add_index_by_name(&mut self, keys: &[&str]) -> Result<usize> {
+        let columns = self.search_columns_by_name(keys)?;
+        self.add_index(&columns)
     }
-    #[cfg(feature = "lock_index")]
-    pub fn get_index_key(&self) -> std::collections::hash_map::Keys<usize, RwLock<TableIndex>> {
+    /// Add an index on `columns` and return its index id. More than one column
+    /// builds an ordered composite index, a Double column a range index.
+    /// Composite indexes live in DRAM whatever the index backend, a reload
+    /// fills them again with `rebuild_index`.
+    pub fn add_index(&mut self, columns: &[usize]) -> Result<usize> {
+        self.add_new_index(columns, false)
+    }
+    pub fn add_range_index_by_name(&mut self, keys: &[&str]) -> Result<usize> {
+        let columns = self.search_columns_by_name(keys)?;
+        self.add_range_index(&columns)
+    }
+    pub fn add_range_index(&mut self, columns: &[usize]) -> Result<usize> {
+        self.add_new_index(columns, true)
+    }
+    pub fn get_index_key(&self) -> impl Iterator<Item = &usize> {
         self.index.keys()
     }
     
@@ -461,6 +580,7 @@ impl Table {
 #[cfg(test)]
 mod tests {
     use crate::storage::nvm_file::NVMTableStorage;
+    use std::ops::Bound;
 
     use super::*;
 
@@ -504,7 +624,7 @@ mod tests {
 
         let t2 = Tuple::new(tuple_address, "258,4", &table.schema, ts).expect("");
 
-        table.set_primary_key(0).unwrap();
+        table.set_primary_key(&[0]).unwrap();
         table
             .index_insert(IndexType::Int64(257), &tuple_id)
             .unwrap();
@@ -518,4 +638,409 @@ mod tests {
         // let _t = table.get_tuple(u).unwrap();
         // table.fix_tuple(u, 0, 0, &[0]).unwrap();
     }
+
+    #[test]
+    fn test_composite_index() {
+        let mut schema = TableSchema::new();
+        schema.push(ColumnType::Int64, "w");
+        schema.push(ColumnType::Int64, "o");
+        schema.push(ColumnType::String { len: 8 }, "name");
+
+        NVMTableStorage::init_test_database();
+        let mut storage = NVMTableStorage::global_mut();
+        let page_id = storage.alloc_page().unwrap();
+        drop(storage);
+
+        let mut table = Table::new(schema, page_id.page_start, 0);
+        table.set_range_primary_key(&[0, 1]).unwrap();
+        let name_index = table.add_index_by_name(&["w", "name"]).unwrap();
+        assert_eq!(table.get_index_id_by_name(&["w", "name"]), Some(name_index));
+        assert_eq!(table.get_index_columns(table.get_primary_key()), vec![0, 1]);
+
+        let ts = TimeStamp::default();
+        let mut tuple_ids = Vec::new();
+        for (w, o) in [(1, 2), (1, 0x100), (2, 0), (1, 1)].iter() {
+            let tuple_id = table.allocate_tuple(0).unwrap();
+            let data = format!("{},{},n{}", w, o, o);
+            let tuple = Tuple::new(table.get_address(&tuple_id), &data, &table.schema, ts).unwrap();
+            table.index_insert_by_tuple(&tuple_id, &tuple).unwrap();
+            tuple_ids.push(tuple_id);
+        }
+
+        let key = IndexType::composite(&[IndexType::Int64(1), IndexType::Int64(0x100)]);
+        assert_eq!(table.search_tuple_id(&key).unwrap(), tuple_ids[1]);
+
+        let w1: Vec<TupleId> = table
+            .prefix_cursor(&IndexType::composite(&[IndexType::Int64(1)]))
+            .unwrap()
+            .collect();
+        assert_eq!(w1, vec![tuple_ids[3].clone(), tuple_ids[0].clone(), tuple_ids[1].clone()]);
+        let last = table
+            .prefix_cursor(&IndexType::composite(&[IndexType::Int64(1)]))
+            .unwrap()
+            .descending()
+            .next();
+        assert_eq!(last, Some(tuple_ids[1].clone()));
+
+        let key = IndexType::composite(&[IndexType::Int64(2), IndexType::String(String::from("n0"))]);
+        assert_eq!(table.search_tuple_id_on_index(&key, name_index).unwrap(), tuple_ids[2]);
+    }
+
+    #[test]
+    fn test_ordered_index() {
+        let mut schema = TableSchema::new();
+        schema.push(ColumnType::String { len: 8 }, "name");
+        schema.push(ColumnType::Double, "price");
+
+        NVMTableStorage::init_test_database();
+        let mut storage = NVMTableStorage::global_mut();
+        let page_id = storage.alloc_page().unwrap();
+        drop(storage);
+
+        let mut table = Table::new(schema, page_id.page_start, 0);
+        table.set_range_primary_key(&[0]).unwrap();
+        let price_index = table.add_index(&[1]).unwrap();
+        assert!(matches!(table.index.get(&price_index), Some(TableIndex::Int64R(_))));
+
+        let ts = TimeStamp::default();
+        let mut tuple_ids = Vec::new();
+        for data in ["b,2.5", "ab,-1", "a,0", "abc,-7.25", "abcdefgh,1"].iter() {
+            let tuple_id = table.allocate_tuple(0).unwrap();
+            let tuple = Tuple::new(table.get_address(&tuple_id), data, &table.schema, ts).unwrap();
+            table.index_insert_by_tuple(&tuple_id, &tuple).unwrap();
+            tuple_ids.push(tuple_id);
+        }
+
+        let a = table.search_tuple_id(&IndexType::String(String::from("a"))).unwrap();
+        assert_eq!(a, tuple_ids[2]);
+        let ab = table.search_tuple_id(&IndexType::Bytes(b"ab".to_vec())).unwrap();
+        assert_eq!(ab, tuple_ids[1]);
+        // the padding is no part of the key
+        assert!(table.search_tuple_id(&IndexType::String(String::from("a\0"))).is_err());
+        let names: Vec<TupleId> = table
+            .range_cursor(Bound::Unbounded, Bound::Unbounded)
+            .unwrap()
+            .collect();
+        let order = [2, 1, 3, 4, 0];
+        assert_eq!(names, order.iter().map(|i| tuple_ids[*i].clone()).collect::<Vec<_>>());
+        // "ab" and the values it is a prefix of, up to the full column
+        let names: Vec<TupleId> = table
+            .range_cursor(
+                Bound::Included(IndexType::String(String::from("ab"))),
+                Bound::Excluded(IndexType::String(String::from("ac"))),
+            )
+            .unwrap()
+            .descending()
+            .collect();
+        assert_eq!(names, vec![tuple_ids[4].clone(), tuple_ids[3].clone(), tuple_ids[1].clone()]);
+        let names: Vec<TupleId> = table
+            .range_cursor(Bound::Excluded(IndexType::String(String::from("a"))), Bound::Unbounded)
+            .unwrap()
+            .collect();
+        assert_eq!(names.first(), Some(&tuple_ids[1]));
+        let b = table.search_tuple_id_on_index(&IndexType::Double(2.5), price_index).unwrap();
+        assert_eq!(b, tuple_ids[0]);
+
+        let prices: Vec<TupleId> = table
+            .range_cursor_on_index(
+                Bound::Unbounded,
+                Bound::Included(IndexType::Double(0.0)),
+                price_index,
+            )
+            .unwrap()
+            .descending()
+            .collect();
+        assert_eq!(prices, vec![tuple_ids[2].clone(), tuple_ids[1].clone(), tuple_ids[3].clone()]);
+        let prices: Vec<TupleId> = table
+            .range_cursor_on_index(Bound::Excluded(IndexType::Double(-1.0)), Bound::Unbounded, price_index)
+            .unwrap()
+            .collect();
+        assert_eq!(prices, vec![tuple_ids[2].clone(), tuple_ids[4].clone(), tuple_ids[0].clone()]);
+    }
+
+    #[test]
+    fn test_range_index_descending() {
+        let mut schema = TableSchema::new();
+        schema.push(ColumnType::Int64, "k");
+
+        NVMTableStorage::init_test_database();
+        let mut storage = NVMTableStorage::global_mut();
+        let page_id = storage.alloc_page().unwrap();
+        drop(storage);
+
+        let mut table = Table::new(schema, page_id.page_start, 0);
+        table.set_range_primary_key(&[0]).unwrap();
+
+        let ts = TimeStamp::default();
+        let mut tuple_ids = Vec::new();
+        let keys = (0..100u64).map(|k| k * 1000).chain(std::iter::once(u64::MAX));
+        for key in keys {
+            let tuple_id = table.allocate_tuple(0).unwrap();
+            let tuple = Tuple::new(table.get_address(&tuple_id), &key.to_string(), &table.schema, ts).unwrap();
+            table.index_insert_by_tuple(&tuple_id, &tuple).unwrap();
+            tuple_ids.push(tuple_id);
+        }
+
+        let all: Vec<TupleId> = table.range_cursor(Bound::Unbounded, Bound::Unbounded).unwrap().collect();
+        assert_eq!(all, tuple_ids);
+        let down: Vec<TupleId> = table
+            .range_cursor(Bound::Unbounded, Bound::Unbounded)
+            .unwrap()
+            .descending()
+            .collect();
+        assert_eq!(down, tuple_ids.iter().rev().cloned().collect::<Vec<_>>());
+
+        let top: Vec<TupleId> = table
+            .range_cursor(Bound::Excluded(IndexType::Int64(98_000)), Bound::Included(IndexType::Int64(u64::MAX)))
+            .unwrap()
+            .descending()
+            .collect();
+        assert_eq!(top, vec![tuple_ids[100].clone(), tuple_ids[99].clone()]);
+    }
+
+    #[test]
+    fn test_reload_layout() {
+        let mut schema = TableSchema::new();
+        schema.push(ColumnType::Int64, "id");
+        schema.push(ColumnType::String { len: 5 }, "name");
+
+        NVMTableStorage::init_test_database();
+        let mut storage = NVMTableStorage::global_mut();
+        let page_id = storage.alloc_page().unwrap();
+        drop(storage);
+
+        let table = Table::new(schema.clone(), page_id.page_start, 0);
+        let mut tuple_ids = Vec::new();
+        for i in 0..3 {
+            let tuple_id = table.allocate_tuple(0).unwrap();
+            let data = format!("{},n{}", i, i);
+            let tuple = Tuple::new(table.get_address(&tuple_id), &data, &table.schema, TimeStamp::default()).unwrap();
+            tuple.commit();
+            tuple_ids.push(tuple_id);
+        }
+
+        let reloaded = Table::reload(schema, page_id.page_start, 0);
+        assert_eq!(reloaded.tuple_size, table.tuple_size);
+        assert_eq!(reloaded.max_tuple, table.max_tuple);
+        assert_eq!(reloaded.tuple_start, table.tuple_start);
+        // the slots `new` allocated are where the reloaded table looks
+        let page = reloaded.meta_page.get(0).unwrap();
+        for (i, tuple_id) in tuple_ids.iter().enumerate() {
+            let slot = TupleId::new(page, i as u64, reloaded.tuple_size);
+            assert_eq!(&slot, tuple_id);
+            let tuple = reloaded.get_tuple(&slot);
+            let id = u64::from_le_bytes(tuple.get_data_by_column(reloaded.schema.get_column_offset(0)).try_into().unwrap());
+            assert_eq!(id, i as u64);
+        }
+    }
+
+    #[cfg(all(feature = "local_allocator", not(feature = "append")))]
+    #[test]
+    fn test_reload_lazy_pages() {
+        let mut schema = TableSchema::new();
+        schema.push(ColumnType::Int64, "id");
+
+        NVMTableStorage::init_test_database();
+        let mut storage = NVMTableStorage::global_mut();
+        let page_id = storage.alloc_page().unwrap();
+        drop(storage);
+
+        // a thread slot takes its first page at its first insert
+        let table = Table::new(schema.clone(), page_id.page_start, 0);
+        assert_eq!(table.meta_page.len(), 0);
+        let first = table.allocate_tuple(3).unwrap();
+        let tuple = Tuple::new(table.get_address(&first), "7", &table.schema, TimeStamp::default()).unwrap();
+        tuple.commit();
+        assert_eq!(table.meta_page.len(), 1);
+
+        // the reloaded slots have no page either, new inserts never reuse
+        // the page of another slot
+        let reloaded = Table::reload(schema, page_id.page_start, 0);
+        assert_eq!(reloaded.meta_page.len(), 1);
+        let second = reloaded.allocate_tuple(3).unwrap();
+        assert_ne!(second, first);
+        assert_eq!(reloaded.meta_page.len(), 2);
+        let tuple = reloaded.get_tuple(&first);
+        let id = u64::from_le_bytes(tuple.get_data_by_column(reloaded.schema.get_column_offset(0)).try_into().unwrap());
+        assert_eq!(id, 7);
+    }
+
+    #[test]
+    fn test_string_index_bytes() {
+        let mut schema = TableSchema::new();
+        schema.push(ColumnType::String { len: 8 }, "name");
+
+        NVMTableStorage::init_test_database();
+        let mut storage = NVMTableStorage::global_mut();
+        let page_id = storage.alloc_page().unwrap();
+        drop(storage);
+
+        let mut table = Table::new(schema, page_id.page_start, 0);
+        table.set_primary_key(&[0]).unwrap();
+
+        let ts = TimeStamp::default();
+        let tuple_id = table.allocate_tuple(0).unwrap();
+        let tuple = Tuple::new(table.get_address(&tuple_id), "ab", &table.schema, ts).unwrap();
+        table.index_insert_by_tuple(&tuple_id, &tuple).unwrap();
+        assert_eq!(table.search_tuple_id(&IndexType::Bytes(b"ab".to_vec())).unwrap(), tuple_id);
+        assert!(table.search_tuple_id(&IndexType::String(String::from("ab\0"))).is_err());
+
+        // a trailing zero byte is part of the key, not padding
+        let nul_id = table.allocate_tuple(0).unwrap();
+        let nul = Tuple::new(table.get_address(&nul_id), "ab\0", &table.schema, ts).unwrap();
+        table.index_insert_by_tuple(&nul_id, &nul).unwrap();
+        assert_eq!(table.search_tuple_id(&IndexType::String(String::from("ab\0"))).unwrap(), nul_id);
+        assert_eq!(table.search_tuple_id(&IndexType::String(String::from("ab"))).unwrap(), tuple_id);
+
+        // keys with zero and non-UTF-8 bytes inside
+        let raw = IndexType::Bytes(vec![b'a', 0, 0xFF]);
+        let raw_id = table.allocate_tuple(0).unwrap();
+        table.index_insert_on_index(raw.clone(), &raw_id, 0).unwrap();
+        assert_eq!(table.search_tuple_id(&raw).unwrap(), raw_id);
+        assert!(table.search_tuple_id(&IndexType::Bytes(b"a".to_vec())).is_err());
+
+        table.index_remove_by_tuple(&tuple_id, &tuple).unwrap();
+        assert!(table.search_tuple_id(&IndexType::String(String::from("ab"))).is_err());
+        assert_eq!(table.search_tuple_id(&raw).unwrap(), raw_id);
+        assert_eq!(table.search_tuple_id(&IndexType::String(String::from("ab\0"))).unwrap(), nul_id);
+    }
+
+    #[test]
+    fn test_unique_index() {
+        let mut schema = TableSchema::new();
+        schema.push(ColumnType::Int64, "a");
+        schema.push(ColumnType::Int64, "b");
+
+        NVMTableStorage::init_test_database();
+        let mut storage = NVMTableStorage::global_mut();
+        let page_id = storage.alloc_page().unwrap();
+        drop(storage);
+
+        let mut table = Table::new(schema, page_id.page_start, 0);
+        table.set_primary_key(&[0]).unwrap();
+        let pair_index = table.add_unique_index(&[1, 0]).unwrap();
+        table.add_index(&[1]).unwrap();
+
+        let ts = TimeStamp::default();
+        let mut tuples = Vec::new();
+        for data in ["1,7", "1,8", "2,7"].iter() {
+            let tuple_id = table.allocate_tuple(0).unwrap();
+            let tuple = Tuple::new(table.get_address(&tuple_id), data, &table.schema, ts).unwrap();
+            tuples.push((tuple_id, tuple));
+        }
+        table.index_insert_by_tuple(&tuples[0].0, &tuples[0].1).unwrap();
+        assert!(matches!(
+            table.index_insert_by_tuple(&tuples[1].0, &tuples[1].1),
+            Err(Error::Exist)
+        ));
+        // a non-unique key may repeat
+        table.index_insert_by_tuple(&tuples[2].0, &tuples[2].1).unwrap();
+        assert!(matches!(
+            table.index_insert(IndexType::Int64(2), &tuples[1].0),
+            Err(Error::Exist)
+        ));
+
+        // the failed insert undid its unique keys and can not remove the owner's
+        let key = IndexType::composite(&[IndexType::Int64(8), IndexType::Int64(1)]);
+        assert!(table.search_tuple_id_on_index(&key, pair_index).is_err());
+        table.index_remove_by_tuple(&tuples[1].0, &tuples[1].1).unwrap();
+        assert_eq!(table.search_tuple_id(&IndexType::Int64(1)).unwrap(), tuples[0].0);
+        assert!(table.owns_unique_keys(&tuples[0].0, &tuples[0].1));
+        assert!(!table.owns_unique_keys(&tuples[1].0, &tuples[1].1));
+
+        table.index_remove_by_tuple(&tuples[0].0, &tuples[0].1).unwrap();
+        table.index_insert_by_tuple(&tuples[1].0, &tuples[1].1).unwrap();
+        assert_eq!(table.search_tuple_id(&IndexType::Int64(1)).unwrap(), tuples[1].0);
+    }
+
+    #[test]
+    fn test_create_index() {
+        let mut schema = TableSchema::new();
+        schema.push(ColumnType::Int64, "id");
+        schema.push(ColumnType::Int64, "group");
+
+        NVMTableStorage::init_test_database();
+        let mut storage = NVMTableStorage::global_mut();
+        let page_id = storage.alloc_page().unwrap();
+        drop(storage);
+
+        let mut table = Table::new(schema, page_id.page_start, 0);
+        table.set_primary_key(&[0]).unwrap();
+
+        let insert = |table: &Table, i: u64, thread_id: usize| {
+            let tuple_id = table.allocate_tuple(thread_id).unwrap();
+            let data = format!("{},{}", i, i % 7);
+            let tuple =
+                Tuple::new(table.get_address(&tuple_id), &data, &table.schema, TimeStamp::default())
+                    .unwrap();
+            table.index_insert_by_tuple(&tuple_id, &tuple).unwrap();
+            (tuple_id, tuple)
+        };
+        for i in 0..100 {
+            let (tuple_id, tuple) = insert(&table, i, 0);
+            if i == 3 {
+                table.index_remove_by_tuple(&tuple_id, &tuple).unwrap();
+                table.free_tuple(&tuple_id, 0);
+            }
+        }
+
+        // rows keep coming in while the index is built
+        let table = std::sync::Arc::new(table);
+        let writer = {
+            let table = table.clone();
+            std::thread::spawn(move || {
+                for i in 100..300 {
+                    insert(&table, i, 1);
+                }
+            })
+        };
+        let group_index = table.create_index(&[1, 0]).unwrap();
+        writer.join().unwrap();
+        assert_eq!(table.create_index(&[1, 0]).unwrap(), group_index);
+
+        for group in 0..7 {
+            let prefix = IndexType::composite(&[IndexType::Int64(group)]);
+            let count = table.prefix_cursor_on_index(&prefix, group_index).unwrap().count();
+            let expected = (0..300).filter(|i| i % 7 == group && *i != 3).count();
+            assert_eq!(count, expected);
+        }
+    }
+
+    #[test]
+    fn test_rebuild_index() {
+        let mut schema = TableSchema::new();
+        schema.push(ColumnType::Int64, "id");
+        schema.push(ColumnType::String { len: 8 }, "name");
+
+        NVMTableStorage::init_test_database();
+        let mut storage = NVMTableStorage::global_mut();
+        let page_id = storage.alloc_page().unwrap();
+        drop(storage);
+
+        let table = Table::new(schema.clone(), page_id.page_start, 0);
+        let mut tuple_ids = Vec::new();
+        for (i, data) in ["1,a", "2,b", "3,c", "4,d"].iter().enumerate() {
+            let tuple_id = table.allocate_tuple(i % 2).unwrap();
+            let tuple =
+                Tuple::new(table.get_address(&tuple_id), data, &table.schema, TimeStamp::default())
+                    .unwrap();
+            // 3 is never committed
+            if i != 2 {
+                tuple.commit();
+            }
+            tuple_ids.push(tuple_id);
+        }
+        // 4 is deleted
+        table.free_tuple(&tuple_ids[3], 1);
+
+        let mut table = Table::reload(schema, page_id.page_start, 0);
+        table.set_primary_key(&[0]).unwrap();
+        let name_index = table.add_index(&[1]).unwrap();
+        assert_eq!(table.rebuild_index(4), 2);
+        assert_eq!(table.search_tuple_id(&IndexType::Int64(2)).unwrap(), tuple_ids[1]);
+        let a = table.search_tuple_id_on_index(&IndexType::String(String::from("a")), name_index);
+        assert_eq!(a.unwrap(), tuple_ids[0]);
+        assert!(table.search_tuple_id(&IndexType::Int64(3)).is_err());
+        assert!(table.search_tuple_id(&IndexType::Int64(4)).is_err());
+    }
 }
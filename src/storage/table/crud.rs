@@ -1,6 +1,7 @@
 use crate::{
     mvcc_config::delta::DELTA_DATA_OFFSET,
     storage::row::TUPLE_HEADER,
+    transaction::cc::ConcurrencyControl,
     transaction::transaction_buffer::TransactionBuffer,
     utils::file::{self, sfence},
 };
@@ -30,9 +31,19 @@ impl Table {
     #[cfg(feature = "append")]
     pub fn allocate_append_tuple(&self, tid: usize) -> Result<TupleId> {
         let mut allocator = self.allocator.get(tid).unwrap().write();
-        allocator.allocate_append_tuple(self.tuple_size, tid)
+        allocator.allocate_append_tuple(self.tuple_size)
     }
-    pub fn remove_tuple(&self, tuple_id: &TupleId, thread_id: usize) -> Result {
+    /// Recycle the slot of `tuple_id` once no snapshot older than `tid`, the
+    /// transaction that replaced it with a newer version, runs.
+    #[cfg(feature = "append")]
+    pub fn retire_version(&self, tuple_id: &TupleId, thread_id: usize, tid: u64) {
+        let mut allocator = self.allocator.get(thread_id).unwrap().write();
+        allocator.retire_version(tuple_id.get_address(), tid);
+    }
+    /// Give the slot of a tuple back to the allocator, leaving the index untouched.
+    pub fn free_tuple(&self, tuple_id: &TupleId, thread_id: usize) {
+        #[cfg(not(feature = "buffer_pool"))]
+        self.get_tuple(tuple_id).free();
         #[cfg(feature = "center_allocator")]
         self.allocator.free_tuple(tuple_id);
         #[cfg(feature = "local_allocator")]
@@ -41,6 +52,9 @@ impl Table {
             .unwrap()
             .read()
             .free_tuple(tuple_id.get_address());
+    }
+    pub fn remove_tuple(&self, tuple_id: &TupleId, thread_id: usize) -> Result {
+        self.free_tuple(tuple_id, thread_id);
         // println!("remove {:?}", tuple_id);
         return self.index_remove_by_tuple(tuple_id, &self.get_tuple(tuple_id));
         // return Ok(());
@@ -73,6 +87,7 @@ impl Table {
         new_data: &[u8],
         #[cfg(feature = "update_direct")] snapshot: &SnapShotEntity,
         ts: TimeStamp,
+        cc: ConcurrencyControl,
         thread_id: usize,
         cur_min_txn: u64,
         #[cfg(feature = "buffer_direct")] pool_id: usize,
@@ -125,6 +140,7 @@ impl Table {
             #[cfg(feature = "update_direct")]
             snapshot,
             ts,
+            cc,
             thread_id,
             cur_min_txn,
             #[cfg(feature = "buffer_direct")]
@@ -147,7 +163,8 @@ impl Table {
         #[cfg(feature = "ilog")] start: usize,
         #[cfg(feature = "ilog")] new_data: &[u8],
         #[cfg(feature = "ilog")] buffer: &mut TransactionBuffer,
-        #[cfg(feature = "local_cc_cfg_2pl")] counter: u64,
+        cc: ConcurrencyControl,
+        counter: u64,
     ) -> Result<TimeStamp> {
         #[cfg(not(feature = "buffer_pool"))]
         let tuple = self.get_tuple(tuple_id);
@@ -183,80 +200,18 @@ impl Table {
             // file::sfence();
             // assert_eq!(delta.len(), 140);
         }
-       
-        let lock_tid = tuple.lock_tid().clone();
 
-        #[cfg(feature = "cc_cfg_occ")]
-        {
-            if lock_tid != 0 && lock_tid != ts.tid {
-                return Err(TupleError::TupleChanged {
-                    conflict_tid: lock_tid,
-                }
-                .into());
-            }
-            if !snapshot.access(TimeStamp { tid: lock_tid }, ts.tid, cur_min_txn) {
-                return Err(TupleError::TupleChanged {
-                    conflict_tid: lock_tid,
-                }
-                .into());
-            }
-        }
-
-        #[cfg(feature = "cc_cfg_to")]
-        {
-            if lock_tid != 0 && lock_tid != ts.tid {
-                // println!("lock {:x} {} {}", tuple_id.get_address(), lock_tid, ts.tid);
-                return Err(TupleError::TupleChanged {
-                    conflict_tid: lock_tid,
-                }
-                .into());
-            }
-            let tuple_ts = tuple.ts();
-
-            if tuple_ts.read_ts > ts.tid || tuple_ts.tid > ts.tid {
-                // println!("read_ts {} {} {}", tuple_ts.read_ts, tuple_ts.tid, ts.tid);
-                return Err(TupleError::TupleChanged {
-                    conflict_tid: tuple_ts.read_ts,
-                }
-                .into());
-            }
-        }
-        #[cfg(feature = "cc_cfg_2pl")]
-        {
-            if tuple.lock_write(ts.tid, counter).is_ok() {
-                return Ok(ts);
-            } else {
-                return Err(TupleError::AcquireWriteLockFalse.into());
-            }
-        }
-        #[cfg(not(feature = "cc_cfg_2pl"))]
-        {
-            let cas_result = tuple.cas_lock_tid(0, ts.tid);
-            #[cfg(feature = "clock")]
-            timer.end(READING, READING);
-
-            if cas_result != 0 && cas_result != ts.tid {
-                // println!("lock {:x} {} {}", self.id, lock_tid, ts.tid);
-
-                return Err(TupleError::TupleChanged {
-                    conflict_tid: cas_result,
-                }
-                .into());
-            }
-            if tuple.ts().tid > ts.tid {
-                // tuple.set_lock_tid(0);
-                return Err(TupleError::TupleChanged {
-                    conflict_tid: cas_result,
-                }
-                .into());
-            }
-            #[cfg(feature = "ilog")]
-            {
-                delta.save(new_data);
-                buffer.save_redo(delta.len() + U64_OFFSET);
-            }
-            Ok(ts)
+        let locked = cc.lock(&tuple, snapshot, ts, cur_min_txn, counter);
+        #[cfg(feature = "clock")]
+        timer.end(READING, READING);
+        locked?;
+        // 2PL logs nothing at commit
+        #[cfg(feature = "ilog")]
+        if !cc.is_locking() {
+            delta.save(new_data);
+            buffer.save_redo(delta.len() + U64_OFFSET);
         }
+        Ok(ts)
         // tuple.set_ts(ts);
     }
     
@@ -271,6 +226,7 @@ impl Table {
         start: u64,
         #[cfg(feature = "update_direct")] snapshot: &SnapShotEntity,
         ts: TimeStamp,
+        cc: ConcurrencyControl,
         thread_id: usize,
         cur_min_txn: u64,
         #[cfg(feature = "buffer_direct")] new_pointer: usize,
@@ -281,6 +237,13 @@ impl Table {
         let old_next_address = tuple.next_address().clone();
         #[cfg(not(feature = "buffer_pool"))]
         let tuple_ts = tuple.ts().clone();
+        // every snapshot sees the version going to the delta, unlink the older
+        #[cfg(not(feature = "buffer_pool"))]
+        let old_next_address = if tuple_ts.tid < cur_min_txn {
+            0
+        } else {
+            old_next_address
+        };
         #[cfg(feature = "buffer_pool")]
         let (old_vec, pointer) = self.get_tuple_buffer(
             tuple_id,
@@ -292,8 +255,7 @@ impl Table {
         );
         #[cfg(feature = "update_direct")]
         {
-            #[cfg(feature = "cc_cfg_to")]
-            {
+            if cc == ConcurrencyControl::To {
                 let tuple_ts = tuple.ts();
                 if tuple_ts.tid > ts.tid && tuple_ts.tid != POW_2_63 | ts.tid {
                     return Err(TupleError::TupleChanged {
@@ -315,18 +277,14 @@ impl Table {
                     .into());
                 }
             }
-            #[cfg(feature = "cc_cfg_occ")]
-            if !snapshot.access(tuple_ts, ts.tid, cur_min_txn) {
+            if cc == ConcurrencyControl::Occ && !snapshot.access(tuple_ts, ts.tid, cur_min_txn) {
                 return Err(TupleError::TupleChanged {
                     conflict_tid: tuple_ts.tid,
                 }
                 .into());
             }
-            #[cfg(feature = "cc_cfg_2pl")]
-            {
-                if tuple.lock_write(ts.tid).is_err() {
-                    return Err(TupleError::AcquireWriteLockFalse.into());
-                }
+            if cc.is_locking() && tuple.lock_write(ts.tid, 0).is_err() {
+                return Err(TupleError::AcquireWriteLockFalse.into());
             }
         }
 
@@ -349,8 +307,9 @@ impl Table {
                 // if ts.tid < tuple_ts.tid {
                 //     println!("{}, {}, {}", tuple_id.get_address(), ts.tid, tuple_ts.tid);
                 // }
-                #[cfg(feature = "local_cc_cfg_to")]
-                assert!(ts.tid >= tuple_ts.tid);
+                if cc == ConcurrencyControl::To {
+                    assert!(ts.tid >= tuple_ts.tid);
+                }
                 delta.set_meta_data(delta::TID, tuple_ts.tid);
 
                 delta.save(old_data);
@@ -384,8 +343,9 @@ impl Table {
             //     }
             // }
             //T file::sfence();
-            #[cfg(feature = "cc_cfg_to")]
-            {
+            // under timestamp ordering the version stays marked pending
+            // until the writer commits
+            if cc == ConcurrencyControl::To {
                 let mut ts1 = ts.clone();
                 ts1.tid |= POW_2_63;
                 // if tuple.lock_tid() != ts.tid
@@ -396,9 +356,7 @@ impl Table {
                 tuple.set_ts_tid(ts1.tid);
                 #[cfg(feature = "buffer_pool")]
                 old_vec.data.read().set_ts_tid(ts1.tid);
-            }
-            #[cfg(not(feature = "cc_cfg_to"))]
-            {
+            } else {
                 tuple.set_ts_tid(ts.tid);
             }
             tuple.update_data_by_column(start as u64, new_data);
@@ -487,10 +445,7 @@ impl Table {
         }
         #[cfg(all(not(feature = "buffer_pool"), feature = "append"))]
         {
-            // #[cfg(not(feature = "tpcc"))]
-            // let new_tuple_id = self.allocate_append_tuple(thread_id).unwrap();
-            // #[cfg(feature = "tpcc")]
-            let new_tuple_id = self.allocate_tuple(thread_id).unwrap();
+            let new_tuple_id = self.allocate_append_tuple(thread_id).unwrap();
 
             let tuple_address = self.get_address(&new_tuple_id);
             let new_tuple = Tuple::reload(tuple_address);
@@ -520,6 +475,7 @@ impl Table {
             #[cfg(feature = "clock")]
             timer.end(BUFFER, BUFFER);
             new_tuple.clwb_len(self.tuple_size);
+            self.retire_version(tuple_id, thread_id, ts.tid);
             // assert_eq!(tuple_address, new_tuple._address());
             return Ok(tuple_address);
         }
@@ -537,6 +493,7 @@ impl Table {
         #[cfg(all(feature = "mvcc", feature = "ilog"))] d_delta_address: Address,
         #[cfg(feature = "update_direct")] snapshot: &SnapShotEntity,
         ts: TimeStamp,
+        cc: ConcurrencyControl,
         thread_id: usize,
         cur_min_txn: u64,
         #[cfg(feature = "buffer_direct")] pool_id: usize,
@@ -584,6 +541,7 @@ impl Table {
             #[cfg(feature = "update_direct")]
             snapshot,
             ts,
+            cc,
             thread_id,
             cur_min_txn,
             #[cfg(feature = "buffer_direct")]
@@ -603,6 +561,7 @@ impl Table {
         column_id: usize,
         new_data: &[u8],
         ts: TimeStamp,
+        cc: ConcurrencyControl,
         thread_id: usize,
         cur_min_txn: u64,
         #[cfg(feature = "clock")] timer: &mut Timer,
@@ -622,15 +581,17 @@ impl Table {
 
         let lock_tid = tuple.lock_tid().clone();
 
-        #[cfg(feature = "cc_cfg_occ")]
-        if !snapshot.access(TimeStamp { tid: lock_tid }, ts.tid, cur_min_txn) {
+        let locked = TimeStamp {
+            tid: lock_tid,
+            ..TimeStamp::default()
+        };
+        if cc == ConcurrencyControl::Occ && !snapshot.access(locked, ts.tid, cur_min_txn) {
             return Err(TupleError::TupleChanged {
                 conflict_tid: lock_tid,
             }
             .into());
         }
-        #[cfg(feature = "cc_cfg_to")]
-        {
+        if cc == ConcurrencyControl::To {
             if lock_tid != 0 && lock_tid != ts.tid {
                 // println!("lock {} {}", lock_tid, ts.tid);
                 return Err(TupleError::TupleChanged {
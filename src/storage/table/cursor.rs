@@ -0,0 +1,306 @@
+//! Streaming scans over ordered indexes.
+use super::*;
+use std::collections::VecDeque;
+use std::ops::Bound;
+
+/// Rows fetched from the index at a time.
+const CURSOR_BATCH: usize = 32;
+
+/// Iterator over the tuples of an ordered index with keys within a pair of
+/// bounds. Rows are fetched a batch at a time, the bounds narrowing past the
+/// last fetched key, so a scan never materialises the whole range.
+///
+/// ```ignore
+/// let mut cursor = table.range_cursor(lower.clone(), Bound::Unbounded)?.limit(20);
+/// for tuple_id in &mut cursor {}
+/// if let Some(key) = cursor.continuation() {
+///     // the next 20 rows
+///     let cursor = table.range_cursor(lower, Bound::Unbounded)?.limit(20).resume_after(key);
+/// }
+/// ```
+pub struct RangeCursor<'a> {
+    table: &'a Table,
+    index_id: usize,
+    lower: Bound<IndexType>,
+    upper: Bound<IndexType>,
+    descending: bool,
+    remaining: Option<usize>,
+    resume: Option<IndexType>,
+    /// Fetched rows, the key is None for backends that only return addresses.
+    buffer: VecDeque<(Option<IndexType>, TupleId)>,
+    last: Option<(Option<IndexType>, TupleId)>,
+    exhausted: bool,
+}
+
+impl<'a> RangeCursor<'a> {
+    /// Scan from the high end of the range.
+    pub fn descending(mut self) -> Self {
+        self.descending = true;
+        self
+    }
+    /// Stop after `count` rows.
+    pub fn limit(mut self, count: usize) -> Self {
+        self.remaining = Some(count);
+        self
+    }
+    /// Start after `key`, a continuation of an earlier scan in the same direction.
+    pub fn resume_after(mut self, key: IndexType) -> Self {
+        self.resume = Some(key);
+        self
+    }
+    /// Key of the last returned row to resume the scan from, None once the
+    /// range is exhausted.
+    pub fn continuation(&self) -> Option<IndexType> {
+        if self.exhausted && self.buffer.is_empty() {
+            return None;
+        }
+        self.last.as_ref().map(|entry| self.entry_key(entry))
+    }
+
+    fn entry_key(&self, entry: &(Option<IndexType>, TupleId)) -> IndexType {
+        match &entry.0 {
+            Some(key) => key.clone(),
+            None => {
+                let tuple = self.table.get_tuple(&entry.1);
+                self.table
+                    .index_key(self.index_id, |r| tuple.get_data_by_column(r))
+            }
+        }
+    }
+
+    fn fill(&mut self) {
+        if let Some(key) = self.resume.take() {
+            if self.descending {
+                self.upper = Bound::Excluded(key);
+            } else {
+                self.lower = Bound::Excluded(key);
+            }
+        }
+        let count = match self.remaining {
+            Some(n) => n.min(CURSOR_BATCH),
+            None => CURSOR_BATCH,
+        };
+        let rows = self.table.scan_on_index(
+            self.index_id,
+            &self.lower,
+            &self.upper,
+            self.descending,
+            count,
+        );
+        if rows.len() < count {
+            self.exhausted = true;
+        }
+        if let Some(entry) = rows.last() {
+            let key = self.entry_key(entry);
+            if self.descending {
+                self.upper = Bound::Excluded(key);
+            } else {
+                self.lower = Bound::Excluded(key);
+            }
+        }
+        self.buffer.extend(rows);
+    }
+}
+
+impl<'a> Iterator for RangeCursor<'a> {
+    type Item = TupleId;
+
+    fn next(&mut self) -> Option<TupleId> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        if self.buffer.is_empty() && !self.exhausted {
+            self.fill();
+        }
+        let entry = self.buffer.pop_front()?;
+        let tuple_id = entry.1.clone();
+        if let Some(n) = self.remaining.as_mut() {
+            *n -= 1;
+        }
+        self.last = Some(entry);
+        Some(tuple_id)
+    }
+}
+
+fn bound_key(bound: &Bound<IndexType>) -> Option<&IndexType> {
+    match bound {
+        Bound::Included(key) | Bound::Excluded(key) => Some(key),
+        Bound::Unbounded => None,
+    }
+}
+
+/// [start, last] of the u64 keys within the bounds, None if it is empty.
+/// Unbounded ends include 0 and u64::MAX.
+#[cfg(feature = "nbtree")]
+fn u64_range(lower: &Bound<IndexType>, upper: &Bound<IndexType>) -> Option<(u64, u64)> {
+    let start = match lower {
+        Bound::Included(IndexType::Int64(u)) => *u,
+        Bound::Excluded(IndexType::Int64(u)) => u.checked_add(1)?,
+        _ => 0,
+    };
+    let last = match upper {
+        Bound::Included(IndexType::Int64(u)) => *u,
+        Bound::Excluded(IndexType::Int64(u)) => u.checked_sub(1)?,
+        _ => u64::MAX,
+    };
+    if start <= last {
+        Some((start, last))
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "rust_map")]
+fn u64_bound(bound: &Bound<IndexType>) -> Bound<u64> {
+    match bound {
+        Bound::Included(IndexType::Int64(u)) => Bound::Included(*u),
+        Bound::Excluded(IndexType::Int64(u)) => Bound::Excluded(*u),
+        _ => Bound::Unbounded,
+    }
+}
+
+fn composite_bound(bound: &Bound<IndexType>) -> Bound<Vec<u8>> {
+    match bound {
+        Bound::Included(IndexType::Composite(k)) => Bound::Included(k.clone()),
+        Bound::Excluded(IndexType::Composite(k)) => Bound::Excluded(k.clone()),
+        _ => Bound::Unbounded,
+    }
+}
+
+impl Table {
+    /// Cursor over the primary key, see `range_cursor_on_index`.
+    pub fn range_cursor(&self, lower: Bound<IndexType>, upper: Bound<IndexType>) -> Result<RangeCursor> {
+        let k = self.primary_key.load(std::sync::atomic::Ordering::SeqCst);
+        self.range_cursor_on_index(lower, upper, k)
+    }
+    /// Cursor over the tuples of ordered index `columns` with keys within
+    /// (lower, upper), in ascending key order unless made `descending`.
+    pub fn range_cursor_on_index(
+        &self,
+        lower: Bound<IndexType>,
+        upper: Bound<IndexType>,
+        columns: usize,
+    ) -> Result<RangeCursor> {
+        let table_index = match self.index.get(&columns) {
+            Some(table_index) => table_index,
+            None => return Err(Error::Tuple(TupleError::IndexNotBuilt)),
+        };
+        match table_index {
+            TableIndex::Int64R(_) | TableIndex::Composite(_) => {}
+            TableIndex::None => return Err(Error::Tuple(TupleError::IndexNotBuilt)),
+            _ => return Err(Error::Tuple(TupleError::KeyNotMatched)),
+        }
+        if !self.index.is_ready(columns) {
+            return Err(Error::Tuple(TupleError::IndexNotBuilt));
+        }
+        let lower = self.stored_bound(lower, columns);
+        let upper = self.stored_bound(upper, columns);
+        for key in [&lower, &upper].iter().filter_map(|b| bound_key(b)) {
+            match (table_index, key) {
+                (TableIndex::Int64R(_), IndexType::Int64(_)) => {}
+                (TableIndex::Composite(_), IndexType::Composite(_)) => {}
+                _ => return Err(Error::Tuple(TupleError::KeyNotMatched)),
+            }
+        }
+        Ok(RangeCursor {
+            table: self,
+            index_id: columns,
+            lower,
+            upper,
+            descending: false,
+            remaining: None,
+            resume: None,
+            buffer: VecDeque::new(),
+            last: None,
+            exhausted: false,
+        })
+    }
+    /// Cursor over the tuples whose primary key starts with the composite key
+    /// `prefix`, see `IndexType::composite`.
+    pub fn prefix_cursor(&self, prefix: &IndexType) -> Result<RangeCursor> {
+        let k = self.primary_key.load(std::sync::atomic::Ordering::SeqCst);
+        self.prefix_cursor_on_index(prefix, k)
+    }
+    pub fn prefix_cursor_on_index(&self, prefix: &IndexType, columns: usize) -> Result<RangeCursor> {
+        let (lower, upper) = self.prefix_bounds(prefix, columns)?;
+        self.range_cursor_on_index(lower, upper, columns)
+    }
+    /// Bounds of the keys of index `columns` starting with `prefix`.
+    pub fn prefix_bounds(
+        &self,
+        prefix: &IndexType,
+        columns: usize,
+    ) -> Result<(Bound<IndexType>, Bound<IndexType>)> {
+        let prefix = self.stored_key(prefix, columns);
+        let upper = match prefix.as_ref() {
+            IndexType::Composite(u) => match key::prefix_successor(u) {
+                Some(v) => Bound::Excluded(IndexType::Composite(v)),
+                None => Bound::Unbounded,
+            },
+            _ => return Err(Error::Tuple(TupleError::KeyNotMatched)),
+        };
+        Ok((Bound::Included(prefix.into_owned()), upper))
+    }
+
+    /// Up to `count` entries of ordered index `columns` with keys within the
+    /// bounds, from the low end, or from the high end if `descending`.
+    fn scan_on_index(
+        &self,
+        columns: usize,
+        lower: &Bound<IndexType>,
+        upper: &Bound<IndexType>,
+        descending: bool,
+        count: usize,
+    ) -> Vec<(Option<IndexType>, TupleId)> {
+        let table_index = self.index.get(&columns).unwrap();
+        match table_index {
+            #[cfg(feature = "nbtree")]
+            TableIndex::Int64R(index) => {
+                let (start, last) = match u64_range(lower, upper) {
+                    Some(range) => range,
+                    None => return Vec::new(),
+                };
+                // the tree takes an exclusive end, so u64::MAX is looked up
+                // on its own
+                let end = last.saturating_add(1);
+                let max = if last == u64::MAX {
+                    Some(index.get(&u64::MAX).filter(|tid| tid.get_address() != 0))
+                } else {
+                    None
+                };
+                let mut rows = Vec::new();
+                if descending {
+                    rows.extend(max.into_iter().flatten());
+                    let wanted = count.saturating_sub(rows.len());
+                    rows.extend(index.scan_rev(&start, &end, wanted));
+                } else {
+                    if start < end {
+                        rows = index.scan(&start, &end, count);
+                    }
+                    if rows.len() < count {
+                        rows.extend(max.into_iter().flatten());
+                    }
+                }
+                rows.into_iter().map(|tid| (None, tid)).collect()
+            }
+            #[cfg(feature = "rust_map")]
+            TableIndex::Int64R(index) => {
+                let guard = crossbeam_epoch::pin();
+                let range = index
+                    .range((u64_bound(lower), u64_bound(upper)), &guard)
+                    .map(|(k, v)| (Some(IndexType::Int64(k.clone())), v.clone()));
+                if descending {
+                    range.rev().take(count).collect()
+                } else {
+                    range.take(count).collect()
+                }
+            }
+            TableIndex::Composite(index) => index
+                .scan(composite_bound(lower), composite_bound(upper), descending, count)
+                .into_iter()
+                .map(|(k, v)| (Some(IndexType::Composite(k)), v))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
@@ -1,6 +1,108 @@
 use super::*;
+use parking_lot::MutexGuard;
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::{Bound, Range};
 
 impl Table {
+    /// Encoded key of ordered index `index_id` built from the column data of
+    /// a tuple, None for the other indexes.
+    fn composite_key<'a, F>(&self, index_id: usize, data: F) -> Option<Vec<u8>>
+    where
+        F: Fn(Range<usize>) -> &'a [u8],
+    {
+        match self.index.get(&index_id) {
+            Some(TableIndex::Composite(_)) => {}
+            _ => return None,
+        }
+        let mut key = Vec::new();
+        for column in self.get_index_columns(index_id) {
+            key::encode_column(
+                &mut key,
+                self.schema.get_column_type(column),
+                self.schema.get_column_value(column, &data),
+            )
+            .unwrap();
+        }
+        Some(key)
+    }
+    /// `key` as stored on index `columns`, composite indexes take encoded
+    /// keys and range indexes over a Double column `key::order_f64`.
+    pub(super) fn stored_key<'k>(&self, key: &'k IndexType, columns: usize) -> Cow<'k, IndexType> {
+        match (self.index.get(&columns), key) {
+            (Some(TableIndex::Composite(_)), IndexType::Composite(_)) => Cow::Borrowed(key),
+            (Some(TableIndex::Composite(_)), _) => {
+                Cow::Owned(IndexType::composite(std::slice::from_ref(key)))
+            }
+            (Some(TableIndex::Int64R(_)), IndexType::Double(f)) => {
+                Cow::Owned(IndexType::Int64(key::order_f64(*f)))
+            }
+            _ => Cow::Borrowed(key),
+        }
+    }
+    pub(super) fn stored_bound(&self, bound: Bound<IndexType>, columns: usize) -> Bound<IndexType> {
+        match bound {
+            Bound::Included(key) => Bound::Included(self.stored_key(&key, columns).into_owned()),
+            Bound::Excluded(key) => Bound::Excluded(self.stored_key(&key, columns).into_owned()),
+            Bound::Unbounded => Bound::Unbounded,
+        }
+    }
+    /// Key of index `index_id` built from the column data of a tuple.
+    pub(super) fn index_key<'a, F>(&self, index_id: usize, data: F) -> IndexType
+    where
+        F: Fn(Range<usize>) -> &'a [u8],
+    {
+        if let Some(key) = self.composite_key(index_id, &data) {
+            return IndexType::Composite(key);
+        }
+        let key = data(self.schema.get_column_offset(index_id));
+        match self.schema.get_column_type(index_id) {
+            ColumnType::Int64 | ColumnType::Double => IndexType::Int64(self.range_key(index_id, key)),
+            _ => IndexType::Bytes(self.schema.get_column_value(index_id, &data).to_vec()),
+        }
+    }
+    /// u64 key of Int64 or Double index `index_id` from its column data,
+    /// floats mapped to keep their order, see `key::order_f64`.
+    fn range_key(&self, index_id: usize, data: &[u8]) -> u64 {
+        match self.schema.get_column_type(index_id) {
+            ColumnType::Double => key::order_f64(f64::from_le_bytes(data.try_into().unwrap())),
+            _ => u64::from_le_bytes(data.try_into().unwrap()),
+        }
+    }
+    pub(super) fn lock_unique_key(&self, index_id: usize, key: &IndexType) -> MutexGuard<()> {
+        let mut hasher = DefaultHasher::new();
+        index_id.hash(&mut hasher);
+        match key {
+            IndexType::Int64(u) => u.hash(&mut hasher),
+            IndexType::String(_) | IndexType::Bytes(_) => key.string_key().hash(&mut hasher),
+            IndexType::Double(f) => f.to_bits().hash(&mut hasher),
+            IndexType::Composite(k) => k.hash(&mut hasher),
+        }
+        self.unique_lock[hasher.finish() as usize % UNIQUE_LOCK_COUNT].lock()
+    }
+    /// Address `key` maps to on index `columns`, None if it is not indexed.
+    pub(super) fn owner_on_index(&self, key: &IndexType, columns: usize) -> Option<u64> {
+        match self.lookup_on_index(key, columns) {
+            Ok(tid) if tid.get_address() != 0 => Some(tid.get_address()),
+            _ => None,
+        }
+    }
+    /// Whether every unique key of an inserted tuple still maps to it,
+    /// checked before the insert commits.
+    pub fn owns_unique_keys(&self, tuple_id: &TupleId, tuple: &Tuple) -> bool {
+        for column_id in self.index.keys() {
+            if !self.is_unique_index(*column_id) {
+                continue;
+            }
+            let key = self.index_key(*column_id, |r| tuple.get_data_by_column(r));
+            match self.owner_on_index(&key, *column_id) {
+                Some(address) if address != tuple_id.get_address() => return false,
+                _ => {}
+            }
+        }
+        true
+    }
     #[cfg(feature = "lock_index")]
     pub fn get_index_read_guard(&self, columns: usize) -> RwLockReadGuard<TableIndex> {
         let index = self.index.get(&columns).unwrap();
@@ -27,6 +129,13 @@ impl Table {
     }
 
     pub fn search_tuple_id_on_index(&self, key: &IndexType, columns: usize) -> Result<TupleId> {
+        if !self.index.is_ready(columns) {
+            return Err(Error::Tuple(TupleError::IndexNotBuilt));
+        }
+        self.lookup_on_index(key, columns)
+    }
+    /// `search_tuple_id_on_index` on an index that may still be being built.
+    fn lookup_on_index(&self, key: &IndexType, columns: usize) -> Result<TupleId> {
         // let index = self.index.read().unwrap();
         // println!("key:{:?}, columns:{:?}, index:{:?}",key,columns,self.index);
 
@@ -35,7 +144,8 @@ impl Table {
         #[cfg(not(feature = "lock_index"))]
         {
             let table_index = self.index.get(&columns).unwrap();
-            match (table_index, key) {
+            let key = self.stored_key(key, columns);
+            match (table_index, key.as_ref()) {
                 (TableIndex::Int64(index), IndexType::Int64(u)) => match index.get(u) {
                     Some(v) => {
                         result = Ok(v.clone());
@@ -44,20 +154,7 @@ impl Table {
                         return Err(Error::Tuple(TupleError::KeyNotMatched));
                     }
                 },
-                #[cfg(feature = "dash")]
-                (TableIndex::String(index), IndexType::String(u)) => {
-                    let u = u.trim_end_matches(char::from(0));
-                    match index.get(u, u.len()) {
-                        Some(v) => {
-                            result = Ok(v.clone());
-                        }
-                        None => {
-                            return Err(Error::Tuple(TupleError::KeyNotMatched));
-                        }
-                    }
-                }
-                #[cfg(not(feature = "dash"))]
-                (TableIndex::String(index), IndexType::String(u)) => match index.get(u) {
+                (TableIndex::String(index), _) => match key.string_key().and_then(|u| index.get(u)) {
                     Some(v) => {
                         result = Ok(v.clone());
                     }
@@ -83,6 +180,14 @@ impl Table {
                         return Err(Error::Tuple(TupleError::KeyNotMatched));
                     }
                 },
+                (TableIndex::Composite(index), IndexType::Composite(u)) => match index.get(u) {
+                    Some(v) => {
+                        result = Ok(v);
+                    }
+                    None => {
+                        return Err(Error::Tuple(TupleError::KeyNotMatched));
+                    }
+                },
                 (TableIndex::None, _) => return Err(Error::Tuple(TupleError::IndexNotBuilt)),
                 _ => return Err(Error::Tuple(TupleError::KeyNotMatched)),
             };
@@ -100,7 +205,7 @@ impl Table {
                         return Err(Error::Tuple(TupleError::KeyNotMatched));
                     }
                 },
-                (TableIndex::String(index), IndexType::String(u)) => match index.get(u) {
+                (TableIndex::String(index), _) => match key.string_key().and_then(|u| index.get(u)) {
                     Some(v) => {
                         result = Ok(*v);
                     }
@@ -169,7 +274,33 @@ impl Table {
 
     pub fn update_tuple_id_on_index(&self, new_address: u64, tuple: &Tuple) -> Result {
         for (column_id, table_index) in self.index.iter() {
+            if !self.index.is_ready(*column_id) {
+                // the backfill may not have reached the tuple yet
+                let key = self.index_key(*column_id, |r| tuple.get_data_by_column(r));
+                let _guard = self.lock_unique_key(*column_id, &key);
+                self.insert_on_index(key, &TupleId::from_address(new_address), *column_id)?;
+                continue;
+            }
             // println!("{} {}\n", self.id, column_id);
+            if let TableIndex::Composite(index) = table_index {
+                let key = self
+                    .composite_key(*column_id, |r| tuple.get_data_by_column(r))
+                    .unwrap();
+                match index.cas(&key, tuple._address(), new_address) {
+                    Ok(()) => {}
+                    Err(Some(result)) => {
+                        if *column_id == self.primary_key.load(Ordering::Relaxed) {
+                            return Err(Error::Tuple(TupleError::TupleChanged {
+                                conflict_tid: result,
+                            }));
+                        }
+                    }
+                    Err(None) => {
+                        return Err(Error::Tuple(TupleError::KeyNotMatched));
+                    }
+                }
+                continue;
+            }
 
             let range = self.schema.get_column_offset(*column_id);
             // let pair = self.index.get(key).unwrap();
@@ -210,7 +341,7 @@ impl Table {
                 #[cfg(feature = "rust_map")]
                 TableIndex::Int64R(index) => {
                     match index.get(
-                        &u64::from_le_bytes(key.try_into().unwrap()),
+                        &self.range_key(*column_id, key),
                         &crossbeam_epoch::pin()
                     ) {
                         Some(v) => {
@@ -229,7 +360,7 @@ impl Table {
                 #[cfg(feature = "nbtree")]
                 TableIndex::Int64R(index) => {
                     match index.get(
-                        &u64::from_le_bytes(key.try_into().unwrap()),
+                        &self.range_key(*column_id, key),
                     ) {
                         Some(v) => {
                             let result = v.cas(tuple._address(), new_address);
@@ -246,7 +377,8 @@ impl Table {
                 }
                 #[cfg(not(feature = "dash"))]
                 TableIndex::String(index) => {
-                    match index.get(&String::from(str::from_utf8(key).unwrap())) {
+                    let value = self.schema.get_column_value(*column_id, |r| tuple.get_data_by_column(r));
+                    match index.get(value) {
                         Some(v) => {
                             v.update(new_address);
                         }
@@ -257,10 +389,8 @@ impl Table {
                 }
                 #[cfg(feature = "dash")]
                 TableIndex::String(index) => {
-                    let key = String::from(str::from_utf8(key).unwrap());
-                    let key = key.trim_end_matches(char::from(0));
-
-                    match index.update(key.clone(), key.len(), TupleId::from_address(new_address)) {
+                    let value = self.schema.get_column_value(*column_id, |r| tuple.get_data_by_column(r));
+                    match index.update(value, TupleId::from_address(new_address)) {
                         Some(v) => {
                             // v.update(new_address);
                             // let v2 = index.get(key.clone(), key.len()).unwrap();
@@ -295,6 +425,15 @@ impl Table {
         pool_address: u64,
     ) -> Result {
         for (column_id, table_index) in self.index.iter() {
+            if let TableIndex::Composite(index) = table_index {
+                let key = self
+                    .composite_key(*column_id, |r| tuple.get_data_by_column(r))
+                    .unwrap();
+                if let Err(None) = index.cas(&key, pool_address, new_address) {
+                    return Err(Error::Tuple(TupleError::KeyNotMatched));
+                }
+                continue;
+            }
             let range = self.schema.get_column_offset(*column_id);
             // println!("{} {}\n", range.start, range.end);
             // let pair = self.index.get(key).unwrap();
@@ -332,10 +471,8 @@ impl Table {
                 }
                 #[cfg(feature = "dash")]
                 TableIndex::String(index) => {
-                    let key = String::from(str::from_utf8(key).unwrap());
-                    let key = key.trim_end_matches(char::from(0));
-
-                    match index.get(key.clone(), key.len()) {
+                    let value = self.schema.get_column_value(*column_id, |r| tuple.get_data_by_column(r));
+                    match index.get(value) {
                         Some(v) => {
                             v.update(new_address);
                         }
@@ -346,7 +483,7 @@ impl Table {
                 }
                 #[cfg(feature = "rust_map")]
                 TableIndex::Int64R(index) => {
-                    match index.get(&u64::from_le_bytes(key.try_into().unwrap()), &crossbeam_epoch::pin()) {
+                    match index.get(&self.range_key(*column_id, key), &crossbeam_epoch::pin()) {
                         Some(v) => {
                             if v.get_address() == pool_address {
                                 v.update(new_address);
@@ -362,7 +499,7 @@ impl Table {
                 }
                 #[cfg(feature = "nbtree")]
                 TableIndex::Int64R(index) => {
-                    match index.get(&u64::from_le_bytes(key.try_into().unwrap())) {
+                    match index.get(&self.range_key(*column_id, key)) {
                         Some(v) => {
                             if v.get_address() == pool_address {
                                 v.update(new_address);
@@ -378,7 +515,8 @@ impl Table {
                 }
                 #[cfg(not(feature = "dash"))]
                 TableIndex::String(index) => {
-                    match index.get(&String::from(str::from_utf8(key).unwrap())) {
+                    let value = self.schema.get_column_value(*column_id, |r| tuple.get_data_by_column(r));
+                    match index.get(value) {
                         Some(v) => {
                             v.update(new_address);
                         }
@@ -403,7 +541,23 @@ impl Table {
             self.primary_key.load(std::sync::atomic::Ordering::SeqCst),
         )
     }
+    /// Insert `key` into index `columns`. A unique index returns `Error::Exist`
+    /// if the key is already taken, by a committed tuple or by the uncommitted
+    /// insert of another transaction, as both stay indexed until removed.
     pub fn index_insert_on_index(&self, key: IndexType, value: &TupleId, columns: usize) -> Result {
+        if let Cow::Owned(key) = self.stored_key(&key, columns) {
+            return self.index_insert_on_index(key, value, columns);
+        }
+        if self.is_unique_index(columns) {
+            let _guard = self.lock_unique_key(columns, &key);
+            if self.owner_on_index(&key, columns).is_some() {
+                return Err(Error::Exist);
+            }
+            return self.insert_on_index(key, value, columns);
+        }
+        self.insert_on_index(key, value, columns)
+    }
+    pub(super) fn insert_on_index(&self, key: IndexType, value: &TupleId, columns: usize) -> Result {
         #[cfg(not(feature = "lock_index"))]
         {
             let table_index = self.index.get(&columns).unwrap();
@@ -411,15 +565,17 @@ impl Table {
                 (TableIndex::Int64(index), IndexType::Int64(u)) => {
                     index.insert(u, value.clone());
                 }
-                #[cfg(not(feature = "dash"))]
-                (TableIndex::String(index), IndexType::String(u)) => {
-                    index.insert(u, value.clone()).unwrap();
-                }
-                #[cfg(feature = "dash")]
-                (TableIndex::String(index), IndexType::String(u)) => {
-                    let u = u.trim_end_matches(char::from(0));
-                    index.insert(u.clone(), u.len(), value.clone()).unwrap();
-                }
+                (TableIndex::String(index), key) => match key.string_key() {
+                    #[cfg(feature = "dash")]
+                    Some(u) => {
+                        index.insert(u, value.clone());
+                    }
+                    #[cfg(not(feature = "dash"))]
+                    Some(u) => {
+                        index.insert(u.to_vec(), value.clone());
+                    }
+                    None => return Err(Error::Tuple(TupleError::KeyNotMatched)),
+                },
                 #[cfg(feature = "rust_map")]
                 (TableIndex::Int64R(index), IndexType::Int64(u)) => {
                     // println!("{}, {:?}", u, value);
@@ -430,6 +586,9 @@ impl Table {
                     // println!("{}, {:?}", u, value);
                     index.insert(u, value.clone());
                 }
+                (TableIndex::Composite(index), IndexType::Composite(u)) => {
+                    index.insert(u, value.clone());
+                }
                 (TableIndex::None, _) => return Err(Error::Tuple(TupleError::IndexNotBuilt)),
                 _ => return Err(Error::Tuple(TupleError::KeyNotMatched)),
             }
@@ -441,9 +600,12 @@ impl Table {
                 (TableIndex::Int64(index), IndexType::Int64(u)) => {
                     index.insert(u, value).unwrap();
                 }
-                (TableIndex::String(index), IndexType::String(u)) => {
-                    index.insert(u, value).unwrap();
-                }
+                (TableIndex::String(index), key) => match key.string_key() {
+                    Some(u) => {
+                        index.insert(u.to_vec(), value).unwrap();
+                    }
+                    None => return Err(Error::Tuple(TupleError::KeyNotMatched)),
+                },
                 (TableIndex::None, _) => return Err(Error::Tuple(TupleError::IndexNotBuilt)),
                 _ => return Err(Error::Tuple(TupleError::KeyNotMatched)),
             }
@@ -459,7 +621,39 @@ impl Table {
         // #[cfg(feature = "zen")]
         // self.tid2address.insert(tuple_id.clone(), tuple_id.get_address());
 
+        let mut unique = Vec::new();
+        for column_id in self.index.keys() {
+            if !self.is_unique_index(*column_id) {
+                continue;
+            }
+            let key = self.index_key(*column_id, |r| tuple.get_data_by_column(r));
+            if let Err(e) = self.index_insert_on_index(key, tuple_id, *column_id) {
+                for id in unique {
+                    let key = self.index_key(id, |r| tuple.get_data_by_column(r));
+                    self.index_remove_if_owner(&key, tuple_id, id)?;
+                }
+                return Err(e);
+            }
+            unique.push(*column_id);
+        }
+
         for (column_id, table_index) in self.index.iter() {
+            if self.is_unique_index(*column_id) {
+                continue;
+            }
+            if !self.index.is_ready(*column_id) {
+                let key = self.index_key(*column_id, |r| tuple.get_data_by_column(r));
+                let _guard = self.lock_unique_key(*column_id, &key);
+                self.insert_on_index(key, tuple_id, *column_id)?;
+                continue;
+            }
+            if let TableIndex::Composite(index) = table_index {
+                let key = self
+                    .composite_key(*column_id, |r| tuple.get_data_by_column(r))
+                    .unwrap();
+                index.insert(key, tuple_id.clone());
+                continue;
+            }
             let range = self.schema.get_column_offset(*column_id);
             // let pair = self.index.get(key).unwrap();
             let key = tuple.get_data_by_column(range);
@@ -476,21 +670,18 @@ impl Table {
                     }
                     #[cfg(feature = "dash")]
                     TableIndex::String(index) => {
-                        let key = str::from_utf8(key).unwrap();
-                        let key = key.to_string();
-                        let key = key.trim_end_matches(char::from(0));
-                        // println!("{}, {}", key, key.len());
-
-                        index.insert(key.clone(), key.len(), tuple_id.clone());
+                        let value = self.schema.get_column_value(*column_id, |r| tuple.get_data_by_column(r));
+                        index.insert(value, tuple_id.clone());
                     }
                     #[cfg(not(feature = "dash"))]
                     TableIndex::String(index) => {
-                        index.insert(String::from(str::from_utf8(key).unwrap()), tuple_id.clone());
+                        let value = self.schema.get_column_value(*column_id, |r| tuple.get_data_by_column(r));
+                        index.insert(value.to_vec(), tuple_id.clone());
                     }
                     #[cfg(feature = "rust_map")]
                     TableIndex::Int64R(index) => {
                         index.insert(
-                            u64::from_le_bytes(key.try_into().unwrap()),
+                            self.range_key(*column_id, key),
                             tuple_id.clone(), &crossbeam_epoch::pin()
                             
                         );
@@ -498,7 +689,7 @@ impl Table {
                     #[cfg(feature = "nbtree")]
                     TableIndex::Int64R(index) => {
                         index.insert(
-                            u64::from_le_bytes(key.try_into().unwrap()),
+                            self.range_key(*column_id, key),
                             tuple_id.clone()
                         );
                     }
@@ -516,7 +707,8 @@ impl Table {
                         index.insert(u64::from_le_bytes(key.try_into().unwrap()), tuple_id);
                     }
                     TableIndex::String(index) => {
-                        index.insert(String::from(str::from_utf8(key).unwrap()), tuple_id);
+                        let value = self.schema.get_column_value(*column_id, |r| tuple.get_data_by_column(r));
+                        index.insert(value.to_vec(), tuple_id);
                     }
                     _ => {
                         // return Err(Error::Tuple(TupleError::IndexNotBuilt)),
@@ -527,10 +719,26 @@ impl Table {
 
         Ok(())
     }
+    /// Remove the index entries of a tuple. Unique keys, and keys of an index
+    /// being built, are only removed while they still map to `tuple_id`, so
+    /// aborting a failed insert never drops the entry of the tuple that owns
+    /// the key.
     pub fn index_remove_by_tuple(&self, tuple_id: &TupleId, tuple: &Tuple) -> Result {
         // let index = self.index.read().unwrap();
         // let index_key = self.index_key.read().unwrap();
         for (column_id, table_index) in self.index.iter() {
+            if self.is_unique_index(*column_id) || !self.index.is_ready(*column_id) {
+                let key = self.index_key(*column_id, |r| tuple.get_data_by_column(r));
+                self.index_remove_if_owner(&key, tuple_id, *column_id)?;
+                continue;
+            }
+            if let TableIndex::Composite(index) = table_index {
+                let key = self
+                    .composite_key(*column_id, |r| tuple.get_data_by_column(r))
+                    .unwrap();
+                index.remove(&key);
+                continue;
+            }
             let range = self.schema.get_column_offset(*column_id);
             // println!("{} {}\n", range.start, range.end);
             // let pair = self.index.get(key).unwrap();
@@ -541,20 +749,14 @@ impl Table {
                     TableIndex::Int64(index) => {
                         index.remove(&u64::from_le_bytes(key.try_into().unwrap()));
                     }
-                    #[cfg(not(feature = "dash"))]
-                    TableIndex::String(index) => {
-                        index.remove(&String::from(str::from_utf8(key).unwrap()));
-                    }
-                    #[cfg(feature = "dash")]
                     TableIndex::String(index) => {
-                        let key = String::from(str::from_utf8(key).unwrap());
-                        let key = key.trim_end_matches(char::from(0));
-                        index.remove(key.clone(), key.len());
+                        let value = self.schema.get_column_value(*column_id, |r| tuple.get_data_by_column(r));
+                        index.remove(value);
                     }
                     #[cfg(feature = "rust_map")]
                     TableIndex::Int64R(index) => {
                         index.delete(
-                            &u64::from_le_bytes(key.try_into().unwrap()),
+                            &self.range_key(*column_id, key),
                             &crossbeam_epoch::pin(),
                         );
 
@@ -562,7 +764,7 @@ impl Table {
                     #[cfg(feature = "nbtree")]
                     TableIndex::Int64R(index) => {
                         index.remove(
-                            &u64::from_le_bytes(key.try_into().unwrap()),
+                            &self.range_key(*column_id, key),
                         );
 
                     }
@@ -579,7 +781,8 @@ impl Table {
                         index.remove(&u64::from_le_bytes(key.try_into().unwrap()));
                     }
                     TableIndex::String(index) => {
-                        index.remove(&String::from(str::from_utf8(key).unwrap()));
+                        let value = self.schema.get_column_value(*column_id, |r| tuple.get_data_by_column(r));
+                        index.remove(value);
                     }
                     _ => {
                         // return Err(Error::Tuple(TupleError::IndexNotBuilt)),
@@ -591,10 +794,53 @@ impl Table {
         Ok(())
     }
     
+    fn index_remove_if_owner(&self, key: &IndexType, tuple_id: &TupleId, columns: usize) -> Result {
+        let _guard = self.lock_unique_key(columns, key);
+        if self.owner_on_index(key, columns) == Some(tuple_id.get_address()) {
+            return self.remove_on_index(key, columns);
+        }
+        Ok(())
+    }
+    fn remove_on_index(&self, key: &IndexType, columns: usize) -> Result {
+        let table_index = self.index.get(&columns).unwrap();
+        match (table_index, key) {
+            (TableIndex::Int64(index), IndexType::Int64(u)) => {
+                index.remove(u);
+            }
+            (TableIndex::String(index), key) => match key.string_key() {
+                Some(u) => {
+                    index.remove(u);
+                }
+                None => return Err(Error::Tuple(TupleError::KeyNotMatched)),
+            },
+            #[cfg(feature = "rust_map")]
+            (TableIndex::Int64R(index), IndexType::Int64(u)) => {
+                index.delete(u, &crossbeam_epoch::pin());
+            }
+            #[cfg(feature = "nbtree")]
+            (TableIndex::Int64R(index), IndexType::Int64(u)) => {
+                index.remove(u);
+            }
+            (TableIndex::Composite(index), IndexType::Composite(u)) => {
+                index.remove(u);
+            }
+            (TableIndex::None, _) => return Err(Error::Tuple(TupleError::IndexNotBuilt)),
+            _ => return Err(Error::Tuple(TupleError::KeyNotMatched)),
+        }
+        Ok(())
+    }
+
     pub fn index_remove_by_tuple_buffer(&self, tuple: &RwLockReadGuard<BufferDataVec>,) -> Result {
         // let index = self.index.read().unwrap();
         // let index_key = self.index_key.read().unwrap();
         for (column_id, table_index) in self.index.iter() {
+            if let TableIndex::Composite(index) = table_index {
+                let key = self
+                    .composite_key(*column_id, |r| tuple.get_data_by_column(r))
+                    .unwrap();
+                index.remove(&key);
+                continue;
+            }
             let range = self.schema.get_column_offset(*column_id);
             // println!("{} {}\n", range.start, range.end);
             // let pair = self.index.get(key).unwrap();
@@ -605,27 +851,21 @@ impl Table {
                     TableIndex::Int64(index) => {
                         index.remove(&u64::from_le_bytes(key.try_into().unwrap()));
                     }
-                    #[cfg(not(feature = "dash"))]
                     TableIndex::String(index) => {
-                        index.remove(&String::from(str::from_utf8(key).unwrap()));
-                    }
-                    #[cfg(feature = "dash")]
-                    TableIndex::String(index) => {
-                        let key = String::from(str::from_utf8(key).unwrap());
-                        let key = key.trim_end_matches(char::from(0));
-                        index.remove(key.clone(), key.len());
+                        let value = self.schema.get_column_value(*column_id, |r| tuple.get_data_by_column(r));
+                        index.remove(value);
                     }
                     #[cfg(feature = "rust_map")]
                     TableIndex::Int64R(index) => {
                         index.delete(
-                            &u64::from_le_bytes(key.try_into().unwrap()),
+                            &self.range_key(*column_id, key),
                             &crossbeam_epoch::pin(),
                         );
                     }
                     #[cfg(feature = "nbtree")]
                     TableIndex::Int64R(index) => {
                         index.remove(
-                            &u64::from_le_bytes(key.try_into().unwrap()),
+                            &self.range_key(*column_id, key),
                         );
                     }
                     _ => {
@@ -649,85 +889,11 @@ impl Table {
     }
     /// [key_lower, key_upper)
     pub fn range_tuple_id_on_index(&self, key_lower: &IndexType, key_upper: &IndexType, columns: usize) -> Result<Vec<TupleId>> {
-        // let index = self.index.read().unwrap();
-        // println!("key:{:?}, columns:{:?}, index:{:?}",key,columns,self.index);
-        let mut collect = Vec::<TupleId>::new();
-        let result: Result<Vec<TupleId>>;
-
-        
-        let table_index = self.index.get(&columns).unwrap();
-        match (table_index, key_lower, key_upper) {
-            #[cfg(feature = "rust_map")]
-            (TableIndex::Int64R(index), IndexType::Int64(u), IndexType::Int64(v)) => {
-                let guard = crossbeam_epoch::pin();
-                let r:Vec<_> = index.range(u..v, &guard).collect();
-                for (_, tuple_id) in r {
-                    collect.push(tuple_id.clone());
-                }
-                result = Ok(collect);
-                
-            },
-            #[cfg(feature = "nbtree")]
-            (TableIndex::Int64R(index), IndexType::Int64(u), IndexType::Int64(v)) => {
-                result = Ok(index.range(u, v));
-                
-            },
-            (TableIndex::None, _, _) => return Err(Error::Tuple(TupleError::IndexNotBuilt)),
-            _ => return Err(Error::Tuple(TupleError::KeyNotMatched)),
-        };
-
-        match result {
-            Ok(tuples) => {
-                return Ok(tuples);
-            }
-            Err(e) => {
-                return Err(e);
-            }
-        }
-    }
-
-    pub fn last_range_tuple_id(&self, key_lower: &IndexType, key_upper: &IndexType) -> Result<TupleId> {
-        let k = self.primary_key.load(std::sync::atomic::Ordering::SeqCst); //read().unwrap();
-        self.last_tuple_id_on_index(key_lower, key_upper, k)
-    }
-    pub fn last_tuple_id_on_index(&self, key_lower: &IndexType, key_upper: &IndexType, columns: usize) -> Result<TupleId> {
-        // let index = self.index.read().unwrap();
-        // println!("key:{:?}, columns:{:?}, index:{:?}",key,columns,self.index);
-        let result: Result<TupleId>;
-        let table_index = self.index.get(&columns).unwrap();
-        match (table_index, key_lower, key_upper) {
-            #[cfg(feature = "rust_map")]
-            (TableIndex::Int64R(index), IndexType::Int64(u), IndexType::Int64(v)) => {
-                let guard = crossbeam_epoch::pin();
-                // println!("{} {}", u, v);
-                let (_, tid) = index.range(u..v, &guard).next_back().unwrap();
-                result = Ok(TupleId::from_address(tid.get_address()));
-            },
-            #[cfg(feature = "nbtree")]
-            (TableIndex::Int64R(index), IndexType::Int64(u), IndexType::Int64(v)) => {
-                match index.last(u, v) {
-                    Some(tid) => {
-                        result = Ok(TupleId::from_address(tid.get_address()));
-                    }
-                    None => {
-                        return Err(Error::Tuple(TupleError::KeyNotMatched));
-                    }
-                }
-
-            },
-            (TableIndex::None, _, _) => return Err(Error::Tuple(TupleError::IndexNotBuilt)),
-            _ => {
-                return Err(Error::Tuple(TupleError::KeyNotMatched));
-            }
-        };
-
-        match result {
-            Ok(tuple) => {
-                return Ok(tuple);
-            }
-            Err(e) => {
-                return Err(e);
-            }
-        }
+        let cursor = self.range_cursor_on_index(
+            Bound::Included(key_lower.clone()),
+            Bound::Excluded(key_upper.clone()),
+            columns,
+        )?;
+        Ok(cursor.collect())
     }
 }
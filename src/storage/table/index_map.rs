@@ -0,0 +1,109 @@
+//! Index slots of a table.
+use once_cell::sync::OnceCell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+const VACANT: usize = usize::MAX;
+
+#[derive(Debug)]
+struct IndexEntry<T> {
+    id: usize,
+    columns: Vec<usize>,
+    index: T,
+}
+
+/// Indexes of a table by index id. Indexes are only ever added, so one can
+/// be added through a shared reference while the table is in use. Such an
+/// index is visited by `iter` at once, for writers to maintain it, but is not
+/// `is_ready` for lookups until it is published.
+#[derive(Debug)]
+pub struct IndexMap<T> {
+    /// Entries in the order they were added.
+    entries: Vec<OnceCell<IndexEntry<T>>>,
+    len: AtomicUsize,
+    /// Position in `entries` by index id.
+    position: Vec<AtomicUsize>,
+    ready: Vec<AtomicBool>,
+}
+
+impl<T> IndexMap<T> {
+    /// Map for index ids below `capacity`.
+    pub fn new(capacity: usize) -> Self {
+        IndexMap {
+            entries: std::iter::repeat_with(OnceCell::new).take(capacity).collect(),
+            len: AtomicUsize::new(0),
+            position: std::iter::repeat_with(|| AtomicUsize::new(VACANT))
+                .take(capacity)
+                .collect(),
+            ready: std::iter::repeat_with(|| AtomicBool::new(false))
+                .take(capacity)
+                .collect(),
+        }
+    }
+    /// Add a ready index.
+    pub fn insert(&mut self, id: usize, columns: Vec<usize>, index: T) {
+        assert!(self.add(id, columns, index));
+        self.publish(id);
+    }
+    /// Add an index that is not ready yet, false if `id` is taken or out of
+    /// range. Adds through a shared reference must not run concurrently.
+    pub fn add(&self, id: usize, columns: Vec<usize>, index: T) -> bool {
+        if self.contains_key(&id) || id >= self.position.len() {
+            return false;
+        }
+        let n = self.len.load(Ordering::SeqCst);
+        if self.entries[n].set(IndexEntry { id, columns, index }).is_err() {
+            return false;
+        }
+        self.position[id].store(n, Ordering::SeqCst);
+        self.len.store(n + 1, Ordering::SeqCst);
+        true
+    }
+    /// Make index `id` visible to lookups.
+    pub fn publish(&self, id: usize) {
+        self.ready[id].store(true, Ordering::SeqCst);
+    }
+    pub fn is_ready(&self, id: usize) -> bool {
+        match self.ready.get(id) {
+            Some(ready) => ready.load(Ordering::Acquire),
+            None => false,
+        }
+    }
+    fn entry(&self, id: usize) -> Option<&IndexEntry<T>> {
+        let n = self.position.get(id)?.load(Ordering::Acquire);
+        if n == VACANT {
+            return None;
+        }
+        self.entries[n].get()
+    }
+    pub fn contains_key(&self, id: &usize) -> bool {
+        self.entry(*id).is_some()
+    }
+    /// Index `id`, ready or not.
+    pub fn get(&self, id: &usize) -> Option<&T> {
+        self.entry(*id).map(|e| &e.index)
+    }
+    pub fn columns(&self, id: usize) -> Option<&[usize]> {
+        self.entry(id).map(|e| e.columns.as_slice())
+    }
+    /// Id of the index on `columns`.
+    pub fn id_of(&self, columns: &[usize]) -> Option<usize> {
+        self.entries().find(|e| e.columns == columns).map(|e| e.id)
+    }
+    /// Smallest vacant id from `start` on.
+    pub fn vacant_from(&self, start: usize) -> Option<usize> {
+        (start..self.position.len()).find(|id| !self.contains_key(id))
+    }
+    fn entries(&self) -> impl Iterator<Item = &IndexEntry<T>> {
+        let len = self.len.load(Ordering::SeqCst);
+        self.entries[..len].iter().filter_map(|e| e.get())
+    }
+    /// Ids of every index in the order they were added.
+    pub fn keys(&self) -> impl Iterator<Item = &usize> {
+        self.entries().map(|e| &e.id)
+    }
+    /// Every index in the order they were added, so an index added while
+    /// the table is in use comes after the primary key.
+    pub fn iter(&self) -> impl Iterator<Item = (&usize, &T)> {
+        self.entries().map(|e| (&e.id, &e.index))
+    }
+}
@@ -0,0 +1,143 @@
+//! Order-preserving encoding of composite (multi-column) index keys.
+//!
+//! Every column is encoded so that comparing encoded bytes gives the same
+//! order as comparing the values, and a key is the concatenation of its
+//! columns. The key of a column prefix, e.g. (w, d, o) of an order line, is
+//! then a byte prefix of every full key starting with it.
+//!
+//! | type   | encoding                                                      |
+//! | ------ | ------------------------------------------------------------- |
+//! | Int64  | 8 bytes, big endian                                           |
+//! | Double | 8 bytes, big endian, sign bit flipped, negatives all flipped  |
+//! | String | value bytes, 0x00 escaped as 0x00 0xFF, ends 0x00 0x01      |
+//!
+//! A string column is keyed by its value up to the length `Tuple::push`
+//! stores, not by the zero padded column, so a value and the same value with
+//! trailing zero bytes are two keys. -0.0 and 0.0 are one key.
+use super::*;
+
+const STRING_ESCAPE: u8 = 0xFF;
+const STRING_END: u8 = 0x01;
+
+pub fn encode_u64(key: &mut Vec<u8>, u: u64) {
+    key.extend_from_slice(&u.to_be_bytes());
+}
+
+/// `f` as a u64 of the same order, the key of a Double range index.
+pub fn order_f64(f: f64) -> u64 {
+    let f = if f == 0.0 { 0.0 } else { f };
+    let bits = f.to_bits();
+    if bits >> 63 == 0 {
+        bits | 1 << 63
+    } else {
+        !bits
+    }
+}
+
+pub fn encode_f64(key: &mut Vec<u8>, f: f64) {
+    encode_u64(key, order_f64(f));
+}
+
+pub fn encode_string(key: &mut Vec<u8>, s: &[u8]) {
+    for &b in s {
+        key.push(b);
+        if b == 0 {
+            key.push(STRING_ESCAPE);
+        }
+    }
+    key.push(0);
+    key.push(STRING_END);
+}
+
+/// Encode the value of one column, see `TableSchema::get_column_value`.
+pub fn encode_column(key: &mut Vec<u8>, column_type: ColumnType, data: &[u8]) -> Result {
+    match column_type {
+        ColumnType::Int64 => encode_u64(key, u64::from_le_bytes(data.try_into().unwrap())),
+        ColumnType::Double => encode_f64(key, f64::from_le_bytes(data.try_into().unwrap())),
+        ColumnType::String { len: _ } => encode_string(key, data),
+    }
+    Ok(())
+}
+
+pub fn encode_index_type(key: &mut Vec<u8>, part: &IndexType) {
+    match part {
+        IndexType::Int64(u) => encode_u64(key, *u),
+        IndexType::String(s) => encode_string(key, s.as_bytes()),
+        IndexType::Bytes(b) => encode_string(key, b),
+        IndexType::Double(f) => encode_f64(key, *f),
+        IndexType::Composite(k) => key.extend_from_slice(k),
+    }
+}
+
+/// The smallest key greater than every key starting with `prefix`,
+/// None if there is no such key.
+pub fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut key = prefix.to_vec();
+    while let Some(b) = key.pop() {
+        if b != 0xFF {
+            key.push(b + 1);
+            return Some(key);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn composite(parts: &[IndexType]) -> Vec<u8> {
+        match IndexType::composite(parts) {
+            IndexType::Composite(k) => k,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_order() {
+        let a = composite(&[IndexType::Int64(1), IndexType::Int64(0x100)]);
+        let b = composite(&[IndexType::Int64(2), IndexType::Int64(0)]);
+        assert!(a < b);
+
+        let s1 = composite(&[IndexType::String(String::from("AB\0\0"))]);
+        let s2 = composite(&[IndexType::String(String::from("AB"))]);
+        let s3 = composite(&[IndexType::String(String::from("ABA"))]);
+        let s4 = composite(&[IndexType::String(String::from("AB\0A"))]);
+        let s5 = composite(&[IndexType::String(String::from("AB\0"))]);
+        assert!(s2 < s5);
+        assert!(s5 < s1);
+        assert!(s1 < s4);
+        assert!(s4 < s3);
+
+        let mut c1 = Vec::new();
+        encode_column(&mut c1, ColumnType::Int64, &7u64.to_le_bytes()).unwrap();
+        encode_column(&mut c1, ColumnType::String { len: 4 }, b"AB").unwrap();
+        assert_eq!(c1, composite(&[IndexType::Int64(7), IndexType::String(String::from("AB"))]));
+    }
+
+    #[test]
+    fn test_double() {
+        let values = [f64::NEG_INFINITY, -2.5, -1.0, -0.0, 0.0, 1e-300, 1.0, 2.5, f64::INFINITY];
+        let keys: Vec<Vec<u8>> = values
+            .iter()
+            .map(|f| composite(&[IndexType::Double(*f)]))
+            .collect();
+        for i in 1..keys.len() {
+            assert!(keys[i - 1] <= keys[i]);
+        }
+        assert_eq!(keys[3], keys[4]);
+    }
+
+    #[test]
+    fn test_prefix() {
+        let prefix = composite(&[IndexType::Int64(3), IndexType::Int64(0xFF)]);
+        let end = prefix_successor(&prefix).unwrap();
+        for i in [0, 1, u64::MAX].iter() {
+            let key = composite(&[IndexType::Composite(prefix.clone()), IndexType::Int64(*i)]);
+            assert!(prefix <= key && key < end);
+        }
+        let next = composite(&[IndexType::Int64(3), IndexType::Int64(0x100)]);
+        assert!(next >= end);
+        assert_eq!(prefix_successor(&[0xFF, 0xFF]), None);
+    }
+}
@@ -0,0 +1,71 @@
+//! Index creation on a table in use.
+//!
+//! The new index is added unpublished, from then on every writer maintains
+//! it while lookups on it fail with `IndexNotBuilt`. A scan of the table
+//! pages then backfills the rows written before, and the index is published
+//! once the scan is done. Pages and allocators are left as they are.
+use super::*;
+use std::sync::atomic::fence;
+
+impl Table {
+    /// `add_index` on a table in use.
+    pub fn create_index(&self, columns: &[usize]) -> Result<usize> {
+        self.build_index(columns, false)
+    }
+    pub fn create_index_by_name(&self, keys: &[&str]) -> Result<usize> {
+        let columns = self.search_columns_by_name(keys)?;
+        self.create_index(&columns)
+    }
+    /// `add_range_index` on a table in use.
+    pub fn create_range_index(&self, columns: &[usize]) -> Result<usize> {
+        self.build_index(columns, true)
+    }
+    pub fn create_range_index_by_name(&self, keys: &[&str]) -> Result<usize> {
+        let columns = self.search_columns_by_name(keys)?;
+        self.create_range_index(&columns)
+    }
+
+    fn build_index(&self, columns: &[usize], ordered: bool) -> Result<usize> {
+        let _build = self.build_lock.lock();
+        if let Some(id) = self.get_index_id(columns) {
+            return Ok(id);
+        }
+        // rows are told apart from free slots through the primary key
+        if !self.index.is_ready(self.get_primary_key()) {
+            return Err(Error::Tuple(TupleError::IndexNotBuilt));
+        }
+        let (id, index) = self.new_index(columns, ordered)?;
+        #[cfg(feature = "lock_index")]
+        let index = RwLock::new(index);
+        if !self.index.add(id, columns.to_vec(), index) {
+            return Err(Error::NoSpace);
+        }
+        // a writer that missed the index has indexed its row on the primary
+        // key before, and the scan below finds it
+        fence(Ordering::SeqCst);
+        self.backfill(id)?;
+        self.index.publish(id);
+        Ok(id)
+    }
+
+    /// Index every row reachable through the primary key on index `index_id`.
+    fn backfill(&self, index_id: usize) -> Result {
+        let primary_key = self.get_primary_key();
+        for i in 0..self.meta_page.len() {
+            let page_start = self.meta_page.get(i).unwrap();
+            for offset in 0..self.max_tuple {
+                let tuple_id = TupleId::new(page_start, offset, self.tuple_size);
+                let tuple = self.get_tuple(&tuple_id);
+                let primary = self.index_key(primary_key, |r| tuple.get_data_by_column(r));
+                let key = self.index_key(index_id, |r| tuple.get_data_by_column(r));
+                // writers of the key wait, a row removed before has left the
+                // primary key, one removed after drops the entry again
+                let _guard = self.lock_unique_key(index_id, &key);
+                if self.owner_on_index(&primary, primary_key) == Some(tuple_id.get_address()) {
+                    self.insert_on_index(key, &tuple_id, index_id)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
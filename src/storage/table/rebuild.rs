@@ -0,0 +1,46 @@
+//! Index rebuild from the table pages at recovery.
+use super::*;
+
+impl Table {
+    /// Fill every index of a reloaded table from its pages, scanned by
+    /// `threads` threads, and return the number of rows indexed. Free slots,
+    /// deleted rows and rows of uncommitted inserts lack the commit mark and
+    /// are skipped. Nothing else may use the table meanwhile.
+    pub fn rebuild_index(&self, threads: usize) -> u64 {
+        let threads = threads.max(1);
+        let pages = self.meta_page.len();
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads as u64)
+                .map(|first| {
+                    scope.spawn(move || {
+                        let mut count = 0;
+                        let mut page = first;
+                        while page < pages {
+                            count += self.rebuild_page(self.meta_page.get(page).unwrap());
+                            page += threads as u64;
+                        }
+                        count
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        })
+    }
+
+    fn rebuild_page(&self, page_start: Address) -> u64 {
+        let mut count = 0;
+        for offset in 0..self.max_tuple {
+            let tuple_id = TupleId::new(page_start, offset, self.tuple_size);
+            let tuple = self.get_tuple(&tuple_id);
+            if tuple.delete_flag() & COMMIT_MASK == 0 {
+                continue;
+            }
+            for column_id in self.index.keys() {
+                let key = self.index_key(*column_id, |r| tuple.get_data_by_column(r));
+                self.insert_on_index(key, &tuple_id, *column_id).unwrap();
+            }
+            count += 1;
+        }
+        count
+    }
+}
@@ -2,7 +2,12 @@ use crate::{config::U64_OFFSET, range};
 use std::{fmt, ops::Range};
 
 pub const TS_TID: Range<u64> = range!(0, U64_OFFSET);
-#[cfg(feature = "read_ts")]
+/// Read timestamp under timestamp ordering, the lock word under 2PL.
+///
+/// Always part of the tuple header since the protocol is picked at runtime.
+/// Builds without the old `read_ts` feature (the OCC default) kept only
+/// `TS_TID`, so their tuples are 8 bytes shorter. Their catalogs lack
+/// `FORMAT_VERSION`, `Catalog::open` refuses them before reading a tuple.
 pub const TS_READ_TS: Range<u64> = range!(TS_TID.end, U64_OFFSET);
 // #[cfg(any(feature = "read_ts"))]
 // pub const TS_BEGIN_TS: Range<u64> = range!(TS_READ_TS.end, U64_OFFSET);
@@ -12,7 +17,6 @@ pub const TS_READ_TS: Range<u64> = range!(TS_TID.end, U64_OFFSET);
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct TimeStamp {
     pub tid: u64,
-    #[cfg(feature = "read_ts")]
     pub read_ts: u64,
 }
 
@@ -26,7 +30,6 @@ impl Default for TimeStamp {
     fn default() -> Self {
         TimeStamp {
             tid: 0,
-            #[cfg(feature = "read_ts")]
             read_ts: 0,
         }
     }
@@ -6,7 +6,8 @@ pub mod tpcc_query;
 pub mod tpcc_test;
 pub mod tpcc_txn_asycn;
 pub mod tpcc_txn_sycn;
-use crate::{mvcc_config::TRANSACTION_COUNT, tpcc::tpcc::IS_FULL_SCHEMA};
+use crate::storage::table::IndexType;
+use crate::tpcc::tpcc::IS_FULL_SCHEMA;
 use once_cell::sync::OnceCell;
 use rand::{prelude::ThreadRng, *};
 
@@ -145,7 +146,6 @@ pub const STOCKS_PER_WAREHOUSE: u64 = ITEMS;
 pub const DISTRICTS_PER_WAREHOUSE: u64 = 10;
 pub const CUSTOMERS_PER_DISTRICT: u64 = 3000;
 pub const HISTORIES_PER_CUSTOMER: u64 = 1;
-pub const ORDERS_PER_DISTRICT: u64 = 0x100_000_000; // 2 ^ 32
 pub const MAX_LINES_PER_ORDER: u64 = 15;
 pub fn item_key(iid: u64) -> u64 {
     iid
@@ -153,42 +153,47 @@ pub fn item_key(iid: u64) -> u64 {
 pub fn warehouse_key(wid: u64) -> u64 {
     wid
 }
-pub fn district_key(wid: u64, did: u64) -> u64 {
-    wid * DISTRICTS_PER_WAREHOUSE + did
-}
-pub fn customer_key(wid: u64, did: u64, cid: u64) -> u64 {
-    district_key(wid, did) * CUSTOMERS_PER_DISTRICT + cid
-}
-pub fn cid_from_key(c_key: u64) -> u64 {
-    c_key % CUSTOMERS_PER_DISTRICT
-}
-pub fn customer_last_key(c_last: &String, wid: u64, did: u64) -> String {
-    let mut C_LAST_LEN: usize = 32;
-    // if IS_FULL_SCHEMA {C_LAST_LEN = 16};
-
-    let mut key = format!("{}{}{}", c_last, wid, did);
-    if key.len() < C_LAST_LEN {
-        key.reserve(C_LAST_LEN - key.len());
-    }
-    // key.push(' ');
-    // key.push_str(&wid.to_string());
-    // key.push(' ');
-    // key.push_str(&did.to_string());
-    while key.len() < C_LAST_LEN {
-        key.push('\0');
-    }
-
-    key
-}
-pub fn order_key(wid: u64, did: u64, oid: u64) -> u64 {
-    district_key(wid, did) * ORDERS_PER_DISTRICT + oid
-}
-pub fn new_order_key(wid: u64, did: u64, oid: u64) -> u64 {
+/// (D_W_ID, D_ID)
+pub fn district_key(wid: u64, did: u64) -> IndexType {
+    IndexType::composite(&[IndexType::Int64(wid), IndexType::Int64(did)])
+}
+/// (C_W_ID, C_D_ID, C_ID)
+pub fn customer_key(wid: u64, did: u64, cid: u64) -> IndexType {
+    IndexType::composite(&[
+        IndexType::Int64(wid),
+        IndexType::Int64(did),
+        IndexType::Int64(cid),
+    ])
+}
+/// (C_W_ID, C_D_ID, C_LAST)
+pub fn customer_last_key(c_last: &String, wid: u64, did: u64) -> IndexType {
+    IndexType::composite(&[
+        IndexType::Int64(wid),
+        IndexType::Int64(did),
+        IndexType::String(c_last.clone()),
+    ])
+}
+/// (O_W_ID, O_D_ID, O_ID), also the prefix of the order lines of an order.
+pub fn order_key(wid: u64, did: u64, oid: u64) -> IndexType {
+    IndexType::composite(&[
+        IndexType::Int64(wid),
+        IndexType::Int64(did),
+        IndexType::Int64(oid),
+    ])
+}
+/// (O_W_ID, O_D_ID, O_C_ID)
+pub fn order_customer_key(wid: u64, did: u64, cid: u64) -> IndexType {
+    customer_key(wid, did, cid)
+}
+/// (NO_W_ID, NO_D_ID, NO_O_ID)
+pub fn new_order_key(wid: u64, did: u64, oid: u64) -> IndexType {
     order_key(wid, did, oid)
 }
-pub fn order_line_key(wid: u64, did: u64, oid: u64, olid: u64) -> u64 {
-    order_key(wid, did, oid) * MAX_LINES_PER_ORDER + olid
+/// (OL_W_ID, OL_D_ID, OL_O_ID, OL_NUMBER)
+pub fn order_line_key(wid: u64, did: u64, oid: u64, olid: u64) -> IndexType {
+    IndexType::composite(&[order_key(wid, did, oid), IndexType::Int64(olid)])
 }
-pub fn stock_key(wid: u64, iid: u64) -> u64 {
-    wid * STOCKS_PER_WAREHOUSE + iid
+/// (S_W_ID, S_I_ID)
+pub fn stock_key(wid: u64, iid: u64) -> IndexType {
+    IndexType::composite(&[IndexType::Int64(wid), IndexType::Int64(iid)])
 }
@@ -1,4 +1,4 @@
-use crate::mvcc_config::{THREAD_COUNT, TRANSACTION_COUNT};
+use crate::mvcc_config::{thread_count, TRANSACTION_COUNT};
 use crate::storage::catalog::Catalog;
 use crate::tpcc::tpcc_index::TpccIndex;
 use crate::tpcc::*;
@@ -27,12 +27,12 @@ pub fn init_table_item(buffer: &mut TransactionBuffer) {
         append_string(&mut s, string_rand(rng, 14, 24));
         append_f64(&mut s, f64_rand(rng, 1.00, 100.00, 0.01));
         append_string(&mut s, data_rand(rng));
-        txn.insert(table, &s);
+        txn.insert(table, &s).unwrap();
         // index.add(item_key(iid), txn.insert(table, &s));
         s = String::new();
         s.reserve(40);
     }
-    txn.commit();
+    txn.commit().unwrap();
 }
 pub fn init_table_warehouse(buffer: &mut TransactionBuffer, wid: u64) {
     let mut rng_mut = rand::thread_rng();
@@ -53,9 +53,9 @@ pub fn init_table_warehouse(buffer: &mut TransactionBuffer, wid: u64) {
     append_string(&mut s, zip_rand(rng));
     append_f64(&mut s, f64_rand(rng, 0.0000, 0.2000, 0.0001));
     append_f64(&mut s, 300000.00);
-    txn.insert(table, &s);
+    txn.insert(table, &s).unwrap();
     // index.add(warehouse_key(wid), txn.insert(table, &s));
-    txn.commit();
+    txn.commit().unwrap();
 }
 pub fn init_table_stock(buffer: &mut TransactionBuffer, wid: u64) {
     let mut rng_mut = rand::thread_rng();
@@ -70,7 +70,7 @@ pub fn init_table_stock(buffer: &mut TransactionBuffer, wid: u64) {
     if IS_FULL_SCHEMA {
         for sid in 0..STOCKS_PER_WAREHOUSE {
             // println!("Insert stock {},{}", wid, sid);
-            append_u64(&mut s, stock_key(wid, sid));
+            append_u64(&mut s, sid);
             append_u64(&mut s, wid);
             append_u64(&mut s, u64_rand(rng, 10, 100));
             for _ in 0..10 {
@@ -81,7 +81,7 @@ pub fn init_table_stock(buffer: &mut TransactionBuffer, wid: u64) {
             append_u64(&mut s, 0);
             append_string(&mut s, string_rand(rng, 50, 50));
 
-            txn.insert(table, &s);
+            txn.insert(table, &s).unwrap();
             // index.add(stock_key(wid, sid), txn.insert(table, &s));
             s = String::new();
             s.reserve(40);
@@ -89,17 +89,17 @@ pub fn init_table_stock(buffer: &mut TransactionBuffer, wid: u64) {
     } else {
         for sid in 0..STOCKS_PER_WAREHOUSE {
             // println!("Insert stock {},{}", wid, sid);
-            append_u64(&mut s, stock_key(wid, sid));
+            append_u64(&mut s, sid);
             append_u64(&mut s, wid);
             append_u64(&mut s, u64_rand(rng, 10, 100));
             append_u64(&mut s, 0);
-            txn.insert(table, &s);
+            txn.insert(table, &s).unwrap();
             // index.add(stock_key(wid, sid), txn.insert(table, &s));
             s = String::new();
             s.reserve(40);
         }
     }
-    txn.commit();
+    txn.commit().unwrap();
 }
 pub fn init_table_district(buffer: &mut TransactionBuffer, wid: u64) {
     let mut rng_mut = rand::thread_rng();
@@ -114,7 +114,7 @@ pub fn init_table_district(buffer: &mut TransactionBuffer, wid: u64) {
     txn.begin();
     for did in 0..DISTRICTS_PER_WAREHOUSE {
         // println!("Insert district {},{}", wid, did);
-        append_u64(&mut s, district_key(wid, did));
+        append_u64(&mut s, did);
         append_u64(&mut s, wid);
         append_string(&mut s, string_rand(rng, 6, 10));
         append_string(&mut s, string_rand(rng, 10, 20));
@@ -125,12 +125,12 @@ pub fn init_table_district(buffer: &mut TransactionBuffer, wid: u64) {
         append_f64(&mut s, f64_rand(rng, 0.0000, 0.2000, 0.0001));
         append_f64(&mut s, 30000.00);
         append_u64(&mut s, CUSTOMERS_PER_DISTRICT);
-        txn.insert(table, &s);
+        txn.insert(table, &s).unwrap();
         // index.add(district_key(wid, did), txn.insert(table, &s));
         s = String::new();
         s.reserve(100);
     }
-    txn.commit();
+    txn.commit().unwrap();
 }
 pub fn init_table_customer(buffer: &mut TransactionBuffer, wid: u64, did: u64) {
     let mut rng_mut = rand::thread_rng();
@@ -145,7 +145,7 @@ pub fn init_table_customer(buffer: &mut TransactionBuffer, wid: u64, did: u64) {
     txn.begin();
     if IS_FULL_SCHEMA {
         for cid in 0..CUSTOMERS_PER_DISTRICT {
-            append_u64(&mut s, customer_key(wid, did, cid));
+            append_u64(&mut s, cid);
             append_u64(&mut s, did);
             append_u64(&mut s, wid);
             // println!("Insert customer {},{},{}", wid, did, cid);
@@ -153,12 +153,9 @@ pub fn init_table_customer(buffer: &mut TransactionBuffer, wid: u64, did: u64) {
             append_string(&mut s, string_rand(rng, 8, 16));
             append_string(&mut s, String::from("OE"));
             if cid < 1000 {
-                append_string(&mut s, customer_last_key(&lastname(cid), wid, did));
+                append_string(&mut s, lastname(cid));
             } else {
-                append_string(
-                    &mut s,
-                    customer_last_key(&lastname(nurand(rng, 255, 0, 999)), wid, did),
-                );
+                append_string(&mut s, lastname(nurand(rng, 255, 0, 999)));
             }
             // C_STREET_1
             append_string(&mut s, string_rand(rng, 20, 20));
@@ -182,25 +179,22 @@ pub fn init_table_customer(buffer: &mut TransactionBuffer, wid: u64, did: u64) {
             append_u64(&mut s, 1);
             append_string(&mut s, string_rand(rng, 500, 500));
 
-            txn.insert(table, &s);
+            txn.insert(table, &s).unwrap();
             // index.add(customer_key(wid, did, cid), txn.insert(table, &s));
             s = String::new();
             s.reserve(100);
         }
     } else {
         for cid in 0..CUSTOMERS_PER_DISTRICT {
-            append_u64(&mut s, customer_key(wid, did, cid));
+            append_u64(&mut s, cid);
             append_u64(&mut s, did);
             append_u64(&mut s, wid);
             // println!("Insert customer {},{},{}", wid, did, cid);
             append_string(&mut s, String::from("OE"));
             if cid < 1000 {
-                append_string(&mut s, customer_last_key(&lastname(cid), wid, did));
+                append_string(&mut s, lastname(cid));
             } else {
-                append_string(
-                    &mut s,
-                    customer_last_key(&lastname(nurand(rng, 255, 0, 999)), wid, did),
-                );
+                append_string(&mut s, lastname(nurand(rng, 255, 0, 999)));
             }
             append_string(&mut s, string_rand(rng, 2, 2));
             append_string(&mut s, credit_rand(rng));
@@ -208,13 +202,13 @@ pub fn init_table_customer(buffer: &mut TransactionBuffer, wid: u64, did: u64) {
             append_f64(&mut s, -10.00);
             append_f64(&mut s, 10.00);
             append_u64(&mut s, 1);
-            txn.insert(table, &s);
+            txn.insert(table, &s).unwrap();
             // index.add(customer_key(wid, did, cid), txn.insert(table, &s));
             s = String::new();
             s.reserve(100);
         }
     }
-    txn.commit();
+    txn.commit().unwrap();
 }
 pub fn init_table_history(buffer: &mut TransactionBuffer, wid: u64, did: u64, cid: u64) {
     let mut txn = Transaction::new(buffer, false);
@@ -227,7 +221,7 @@ pub fn init_table_history(buffer: &mut TransactionBuffer, wid: u64, did: u64, ci
     if IS_FULL_SCHEMA {
         for _ in 0..HISTORIES_PER_CUSTOMER {
             // println!("Insert history {},{},{},{}", wid, did, cid, i);
-            append_u64(&mut s, customer_key(wid, did, cid));
+            append_u64(&mut s, cid);
             append_u64(&mut s, did);
             append_u64(&mut s, wid);
             append_u64(&mut s, did);
@@ -236,24 +230,24 @@ pub fn init_table_history(buffer: &mut TransactionBuffer, wid: u64, did: u64, ci
             append_f64(&mut s, 10.00);
             append_string(&mut s, string_rand(rng, 24, 24));
 
-            txn.insert(table, &s);
+            txn.insert(table, &s).unwrap();
             s = String::new();
             s.reserve(40);
         }
     } else {
         for _ in 0..HISTORIES_PER_CUSTOMER {
             // println!("Insert history {},{},{},{}", wid, did, cid, i);
-            append_u64(&mut s, customer_key(wid, did, cid));
+            append_u64(&mut s, cid);
             append_u64(&mut s, did);
             append_u64(&mut s, wid);
             append_u64(&mut s, 0);
             append_f64(&mut s, 10.00);
-            txn.insert(table, &s);
+            txn.insert(table, &s).unwrap();
             s = String::new();
             s.reserve(40);
         }
     }
-    txn.commit();
+    txn.commit().unwrap();
 }
 pub fn init_table_order(buffer: &mut TransactionBuffer, wid: u64, did: u64) {
     let mut rng_mut = rand::thread_rng();
@@ -266,7 +260,7 @@ pub fn init_table_order(buffer: &mut TransactionBuffer, wid: u64, did: u64) {
     // let index = TpccIndex::orders_index();
     // let table1 = TpccIndex::order_lines_index();
     // let index2 = TpccIndex::new_orders_index();
-    let cids = permutation_rand(rng, 1, CUSTOMERS_PER_DISTRICT);
+    let cids = permutation_rand(rng, 0, CUSTOMERS_PER_DISTRICT - 1);
     let mut s = String::new();
     s.reserve(60);
 
@@ -275,8 +269,8 @@ pub fn init_table_order(buffer: &mut TransactionBuffer, wid: u64, did: u64) {
         for oid in 0..CUSTOMERS_PER_DISTRICT {
             let cid = cids[oid as usize];
             // println!("Insert order {},{},{},{}", wid, did, cid, oid);
-            append_u64(&mut s, order_key(wid, did, oid));
-            append_u64(&mut s, customer_key(wid, did, cid));
+            append_u64(&mut s, oid);
+            append_u64(&mut s, cid);
             append_u64(&mut s, did);
             append_u64(&mut s, wid);
             append_u64(&mut s, 0);
@@ -288,14 +282,14 @@ pub fn init_table_order(buffer: &mut TransactionBuffer, wid: u64, did: u64) {
             let ol_cnt = u64_rand(rng, 5, 15);
             append_u64(&mut s, ol_cnt);
             append_u64(&mut s, 1);
-            txn.insert(table, &s);
+            txn.insert(table, &s).unwrap();
             // index.add(order_key(wid, did, oid), txn.insert(table, &s));
             s = String::new();
             for olid in 0..ol_cnt {
-                append_u64(&mut s, order_key(wid, did, oid));
+                append_u64(&mut s, oid);
                 append_u64(&mut s, did);
                 append_u64(&mut s, wid);
-                append_u64(&mut s, order_line_key(wid, did, oid, olid));
+                append_u64(&mut s, olid);
                 append_u64(&mut s, u64_rand(rng, 1, ITEMS) - 1);
                 // OL_SUPPLY_W_ID
                 append_u64(&mut s, wid);
@@ -304,16 +298,16 @@ pub fn init_table_order(buffer: &mut TransactionBuffer, wid: u64, did: u64) {
                 append_f64(&mut s, 0.0);
                 append_string(&mut s, string_rand(rng, 24, 24));
 
-                txn.insert(table1, &s);
+                txn.insert(table1, &s).unwrap();
                 // index1.add(order_line_key(wid, did, oid, olid), txn.insert(table1, &s));
                 s = String::new();
                 s.reserve(100);
             }
             if oid >= 2100 {
-                append_u64(&mut s, order_key(wid, did, oid));
+                append_u64(&mut s, oid);
                 append_u64(&mut s, did);
                 append_u64(&mut s, wid);
-                txn.insert(table2, &s);
+                txn.insert(table2, &s).unwrap();
                 // index2.add(new_order_key(wid, did, oid), txn.insert(table2, &s));
                 s = String::new();
                 s.reserve(60);
@@ -323,8 +317,8 @@ pub fn init_table_order(buffer: &mut TransactionBuffer, wid: u64, did: u64) {
         for oid in 0..CUSTOMERS_PER_DISTRICT {
             let cid = cids[oid as usize];
             // println!("Insert order {},{},{},{}", wid, did, cid, oid);
-            append_u64(&mut s, order_key(wid, did, oid));
-            append_u64(&mut s, customer_key(wid, did, cid));
+            append_u64(&mut s, oid);
+            append_u64(&mut s, cid);
             append_u64(&mut s, did);
             append_u64(&mut s, wid);
             append_u64(&mut s, 0);
@@ -336,33 +330,33 @@ pub fn init_table_order(buffer: &mut TransactionBuffer, wid: u64, did: u64) {
             let ol_cnt = u64_rand(rng, 5, 15);
             append_u64(&mut s, ol_cnt);
             append_u64(&mut s, 1);
-            txn.insert(table, &s);
+            txn.insert(table, &s).unwrap();
             // index.add(order_key(wid, did, oid), txn.insert(table, &s));
             s = String::new();
             for olid in 0..ol_cnt {
-                append_u64(&mut s, order_key(wid, did, oid));
+                append_u64(&mut s, oid);
                 append_u64(&mut s, did);
                 append_u64(&mut s, wid);
-                append_u64(&mut s, order_line_key(wid, did, oid, olid));
+                append_u64(&mut s, olid);
                 //println!("Insert order line {},{},{},{}", wid, did, oid, olid);
                 append_u64(&mut s, u64_rand(rng, 1, ITEMS) - 1);
-                txn.insert(table1, &s);
+                txn.insert(table1, &s).unwrap();
                 // table1.add_range_index(order_line_key(wid, did, oid, olid), txn.insert(table1, &s));
                 s = String::new();
                 s.reserve(60);
             }
             if oid >= 2100 {
-                append_u64(&mut s, order_key(wid, did, oid));
+                append_u64(&mut s, oid);
                 append_u64(&mut s, did);
                 append_u64(&mut s, wid);
-                txn.insert(table2, &s);
+                txn.insert(table2, &s).unwrap();
                 // index2.add(new_order_key(wid, did, oid), txn.insert(table2, &s));
                 s = String::new();
                 s.reserve(60);
             }
         }
     }
-    txn.commit();
+    txn.commit().unwrap();
 }
 pub fn init_warehouse(buffer: &mut TransactionBuffer, wid: u64) {
     init_table_warehouse(buffer, wid);
@@ -381,10 +375,12 @@ pub fn init_tables() {
     let catalog = Catalog::global();
     let mut buffer = TransactionBuffer::new(catalog, 0);
     init_table_item(&mut buffer);
+    // the loader of thread 0 takes the id over
+    drop(buffer);
 
     let (tx0, rx) = mpsc::channel();
 
-    for i in 0..THREAD_COUNT {
+    for i in 0..thread_count() {
         let tx = tx0.clone();
         thread::spawn(move || {
             let mut buffer = TransactionBuffer::new(catalog, i as u64);
@@ -392,15 +388,17 @@ pub fn init_tables() {
             #[cfg(feature = "nbtree")]
             crate::storage::index::nbtree::init_index(i as i32);
             for wid in 0..WAREHOUSES {
-                if (wid as usize) % THREAD_COUNT == i {
+                if (wid as usize) % thread_count() == i {
                     println!("{}", wid);
                     init_warehouse(&mut buffer, wid);
                 }
             }
+            // the id is free once the caller hears back
+            drop(buffer);
             tx.send(()).unwrap();
         });
     }
-    for _ in 0..THREAD_COUNT {
+    for _ in 0..thread_count() {
         let _ = rx.recv().unwrap();
     }
 }
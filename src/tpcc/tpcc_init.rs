@@ -3,6 +3,19 @@ use crate::storage::catalog::Catalog;
 use crate::storage::schema::{ColumnType, TableSchema};
 use std::path::Path;
 
+/// Index columns listed after the table size of an index line,
+/// `default` if the line lists none.
+fn index_columns(table_name: &str, names: &[&str], default: usize) -> Vec<usize> {
+    if names.is_empty() {
+        return vec![default];
+    }
+    let schema = &Catalog::global().get_table(table_name).schema;
+    names
+        .iter()
+        .map(|name| schema.search_by_name(name.trim()).unwrap())
+        .collect()
+}
+
 pub fn init_schema(file_name: impl AsRef<Path>) {
     let catalog = Catalog::global();
     let contents = std::fs::read_to_string(file_name).unwrap();
@@ -64,7 +77,7 @@ pub fn init_schema(file_name: impl AsRef<Path>) {
                 let s: Vec<&str> = line[0..].split(",").collect();
                 table_name = s[0];
                 println!("{}", table_name);
-                catalog.set_primary_key(table_name, 0);
+                catalog.set_primary_key(table_name, &index_columns(table_name, &s[2..], 0));
             }
             3 => {
                 if line.len() < 5 {
@@ -74,18 +87,21 @@ pub fn init_schema(file_name: impl AsRef<Path>) {
                 let s: Vec<&str> = line[0..].split(",").collect();
                 table_name = s[0];
                 println!("{}", table_name);
-                catalog.set_range_primary_key(table_name, 3);
+                catalog.set_range_primary_key(table_name, &index_columns(table_name, &s[2..], 3));
             }
             _ => {
                 assert!(false);
             }
         }
     }
-    catalog.add_index_by_name("CUSTOMER", "C_LAST");
-    catalog.set_primary_key("ORDER", 0);
-    catalog.add_index_by_name("ORDER", "O_C_ID");
+    catalog.add_index_by_name("CUSTOMER", &["C_W_ID", "C_D_ID", "C_LAST"]);
+    catalog.set_primary_key("ORDER", &index_columns("ORDER", &["O_W_ID", "O_D_ID", "O_ID"], 0));
+    catalog.add_index_by_name("ORDER", &["O_W_ID", "O_D_ID", "O_C_ID"]);
 
-    catalog.set_range_primary_key("NEW-ORDER", 0);
+    catalog.set_range_primary_key(
+        "NEW-ORDER",
+        &index_columns("NEW-ORDER", &["NO_W_ID", "NO_D_ID", "NO_O_ID"], 0),
+    );
     #[cfg(feature = "buffer_pool")]
     {
         use crate::tpcc::*;
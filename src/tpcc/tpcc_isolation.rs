@@ -3,12 +3,14 @@ mod tests {
     use crate::storage::catalog::Catalog;
     use crate::storage::nvm_file::NVMTableStorage;
     use crate::storage::row::TupleError;
-    use crate::storage::table::IndexType;
+    use crate::storage::table::{IndexType, Table, TupleId};
     use crate::tpcc::tpcc::*;
     // use crate::tpcc::tpcc_index::TpccIndex;
     use crate::tpcc::tpcc_init;
     use crate::tpcc::*;
+    use crate::transaction::cc::ConcurrencyControl;
     use crate::transaction::transaction::Transaction;
+    use crate::transaction::testing::thread_id;
     use crate::transaction::transaction_buffer::TransactionBuffer;
     use crate::transaction::*;
     use crate::Error;
@@ -29,20 +31,20 @@ mod tests {
 
         // test1
 
-        let mut buffer0 = TransactionBuffer::new(Catalog::global(), 1);
-        let mut buffer1 = TransactionBuffer::new(Catalog::global(), 2);
-        let mut buffer2 = TransactionBuffer::new(Catalog::global(), 3);
-        let mut buffer3 = TransactionBuffer::new(Catalog::global(), 4);
+        let mut buffer0 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer1 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer2 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer3 = TransactionBuffer::new(Catalog::global(), thread_id());
         let mut t0 = Transaction::new(&mut buffer0, false);
         t0.begin();
         let wid = 0;
         let did = 0;
         //let cid = 0;
         let tid = districts
-            .search_tuple_id(&IndexType::Int64(district_key(wid, did)))
+            .search_tuple_id(&district_key(wid, did))
             .unwrap();
         let d0 = t0.read(districts, &tid).unwrap();
-        t0.commit();
+        let _ = t0.commit();
         let next_oid0 = u64::from_le_bytes(
             d0.get_column_by_id(&districts.schema, 10)
                 .try_into()
@@ -55,12 +57,12 @@ mod tests {
         let mut t2 = Transaction::new(&mut buffer2, false);
         t2.begin();
         let d1 = t2.read(districts, &tid).unwrap();
-        t2.commit();
-        assert_eq!(t1.commit(), false);
+        let _ = t2.commit();
+        assert!(t1.commit().is_err());
         let mut t3 = Transaction::new(&mut buffer3, false);
         t3.begin();
         let d2 = t3.read(districts, &tid).unwrap();
-        t3.commit();
+        let _ = t3.commit();
         let next_oid1 = u64::from_le_bytes(
             d1.get_column_by_id(&districts.schema, 10)
                 .try_into()
@@ -83,21 +85,21 @@ mod tests {
         // t1: abort
         // t3: read
         // t3: commit, t3 = t0
-        let mut buffer0 = TransactionBuffer::new(Catalog::global(), 0);
-        let mut buffer1 = TransactionBuffer::new(Catalog::global(), 1);
-        let mut buffer2 = TransactionBuffer::new(Catalog::global(), 2);
-        let mut buffer3 = TransactionBuffer::new(Catalog::global(), 3);
+        let mut buffer0 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer1 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer2 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer3 = TransactionBuffer::new(Catalog::global(), thread_id());
         let mut t0 = Transaction::new(&mut buffer0, false);
         t0.begin();
         let wid = 0;
         let did = 0;
         // let cid = 0;
         let tid = districts
-            .search_tuple_id(&IndexType::Int64(district_key(wid, did)))
+            .search_tuple_id(&district_key(wid, did))
             .unwrap();
 
         let d0 = t0.read(districts, &tid).unwrap();
-        t0.commit();
+        let _ = t0.commit();
         let next_oid0 = u64::from_le_bytes(
             d0.get_column_by_id(&districts.schema, 10)
                 .try_into()
@@ -112,7 +114,7 @@ mod tests {
         let mut t2 = Transaction::new(&mut buffer2, false);
         t2.begin();
         let d1 = t2.read(districts, &tid).unwrap();
-        t2.commit();
+        let _ = t2.commit();
         // let next_oid1 = u64::from_le_bytes(
         //     d1.get_column_by_id(&districts.schema, 10)
         //         .try_into()
@@ -126,7 +128,7 @@ mod tests {
         let mut t3 = Transaction::new(&mut buffer3, false);
         t3.begin();
         let d2 = t3.read(districts, &tid).unwrap();
-        t3.commit();
+        let _ = t3.commit();
         let next_oid1 = u64::from_le_bytes(
             d1.get_column_by_id(&districts.schema, 10)
                 .try_into()
@@ -140,22 +142,22 @@ mod tests {
         assert_eq!(next_oid0, next_oid1);
         assert_eq!(next_oid0, next_oid2);
         // test3
-        let mut buffer0 = TransactionBuffer::new(Catalog::global(), 0);
-        let mut buffer1 = TransactionBuffer::new(Catalog::global(), 1);
-        let mut buffer2 = TransactionBuffer::new(Catalog::global(), 2);
-        let mut buffer3 = TransactionBuffer::new(Catalog::global(), 3);
-        let mut buffer4 = TransactionBuffer::new(Catalog::global(), 4);
-        let mut buffer5 = TransactionBuffer::new(Catalog::global(), 5);
+        let mut buffer0 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer1 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer2 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer3 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer4 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer5 = TransactionBuffer::new(Catalog::global(), thread_id());
         let mut t0 = Transaction::new(&mut buffer0, false);
         t0.begin();
         let wid = 0;
         let did = 0;
         // let cid = 0;
         let tid = districts
-            .search_tuple_id(&IndexType::Int64(district_key(wid, did)))
+            .search_tuple_id(&district_key(wid, did))
             .unwrap();
         let d0 = t0.read(districts, &tid).unwrap();
-        t0.commit();
+        let _ = t0.commit();
         let next_oid0 = u64::from_le_bytes(
             d0.get_column_by_id(&districts.schema, 10)
                 .try_into()
@@ -170,12 +172,12 @@ mod tests {
         t2.begin();
         t2.update(districts, &tid, 10, &(next_oid0 + 1).to_le_bytes())
             .unwrap();
-        t1.commit();
-        assert_eq!(t2.commit(), true);
+        let _ = t1.commit();
+        assert!(t2.commit().is_ok());
         let mut t3 = Transaction::new(&mut buffer3, false);
         t3.begin();
         let d1 = t3.read(districts, &tid).unwrap();
-        t3.commit();
+        let _ = t3.commit();
         let next_oid1 = u64::from_le_bytes(
             d1.get_column_by_id(&districts.schema, 10)
                 .try_into()
@@ -185,11 +187,11 @@ mod tests {
         t4.begin();
         t4.update(districts, &tid, 10, &(next_oid1 + 1).to_le_bytes())
             .unwrap();
-        t4.commit();
+        let _ = t4.commit();
         let mut t5 = Transaction::new(&mut buffer5, false);
         t5.begin();
         let d2 = t5.read(districts, &tid).unwrap();
-        t5.commit();
+        let _ = t5.commit();
         let next_oid2 = u64::from_le_bytes(
             d2.get_column_by_id(&districts.schema, 10)
                 .try_into()
@@ -206,12 +208,12 @@ mod tests {
         // t4: update -> commit
         // t5: read next_oid+1
 
-        let mut buffer0 = TransactionBuffer::new(Catalog::global(), 0);
-        let mut buffer1 = TransactionBuffer::new(Catalog::global(), 1);
-        let mut buffer2 = TransactionBuffer::new(Catalog::global(), 2);
-        let mut buffer3 = TransactionBuffer::new(Catalog::global(), 3);
-        let mut buffer4 = TransactionBuffer::new(Catalog::global(), 4);
-        let mut buffer5 = TransactionBuffer::new(Catalog::global(), 5);
+        let mut buffer0 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer1 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer2 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer3 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer4 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer5 = TransactionBuffer::new(Catalog::global(), thread_id());
         let mut t0 = Transaction::new(&mut buffer0, false);
         println!("22222");
 
@@ -220,11 +222,11 @@ mod tests {
         let did = 0;
         // let cid = 0;
         let tid = districts
-            .search_tuple_id(&IndexType::Int64(district_key(wid, did)))
+            .search_tuple_id(&district_key(wid, did))
             .unwrap();
 
         let d0 = t0.read(districts, &tid).unwrap();
-        t0.commit();
+        let _ = t0.commit();
         let next_oid0 = u64::from_le_bytes(
             d0.get_column_by_id(&districts.schema, 10)
                 .try_into()
@@ -243,7 +245,7 @@ mod tests {
         let mut t3 = Transaction::new(&mut buffer3, false);
         t3.begin();
         let d1 = t3.read(districts, &tid).unwrap();
-        t3.commit();
+        let _ = t3.commit();
         let next_oid1 = u64::from_le_bytes(
             d1.get_column_by_id(&districts.schema, 10)
                 .try_into()
@@ -253,11 +255,11 @@ mod tests {
         t4.begin();
         t4.update(districts, &tid, 10, &(next_oid1 + 1).to_le_bytes())
             .unwrap();
-        t4.commit();
+        let _ = t4.commit();
         let mut t5 = Transaction::new(&mut buffer5, false);
         t5.begin();
         let d2 = t5.read(districts, &tid).unwrap();
-        t5.commit();
+        let _ = t5.commit();
         let next_oid2 = u64::from_le_bytes(
             d2.get_column_by_id(&districts.schema, 10)
                 .try_into()
@@ -286,10 +288,10 @@ mod tests {
         // T3 read t1  assert t11
         // T3 commit
         println!("11111");
-        let mut buffer0 = TransactionBuffer::new(Catalog::global(), 0);
-        let mut buffer1 = TransactionBuffer::new(Catalog::global(), 1);
-        let mut buffer2 = TransactionBuffer::new(Catalog::global(), 2);
-        let mut buffer3 = TransactionBuffer::new(Catalog::global(), 2);
+        let mut buffer0 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer1 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer2 = TransactionBuffer::new(Catalog::global(), thread_id());
+        let mut buffer3 = TransactionBuffer::new(Catalog::global(), thread_id());
         let items = &Catalog::global().get_table("ITEM");
         let mut t0 = Transaction::new(&mut buffer0, false);
         t0.begin();
@@ -309,7 +311,7 @@ mod tests {
         let price1 = f64::from_le_bytes(i1.get_column_by_id(&items.schema, 3).try_into().unwrap());
         let new_price0 = price0 * 1.1;
         let new_price1 = price1 * 1.1;
-        t0.commit();
+        let _ = t0.commit();
         let mut t1 = Transaction::new(&mut buffer1, false);
         println!("44144");
 
@@ -324,12 +326,12 @@ mod tests {
             .unwrap();
         t2.update(items, &tid1, 3, &new_price1.to_le_bytes())
             .unwrap();
-        t2.commit();
+        let _ = t2.commit();
         println!("44244");
 
         let i01 = t1.read(items, &tid0).unwrap();
         let i10 = t1.read(items, &tid1).unwrap();
-        t1.commit();
+        let _ = t1.commit();
         println!("44344");
 
         let price01 =
@@ -352,4 +354,76 @@ mod tests {
         assert_eq!(price10, new_price1);
         //TODO:test8 and test9
     }
+
+    fn next_oid<'a>(t: &mut Transaction<'a>, districts: &'a Table, tid: &TupleId) -> u64 {
+        let d = t.read(districts, tid).unwrap();
+        u64::from_le_bytes(
+            d.get_column_by_id(&districts.schema, 10)
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_write_skew() {
+        NVMTableStorage::init_test_database();
+        Catalog::init_catalog();
+        if IS_FULL_SCHEMA {
+            tpcc_init::init_schema("config/schema_file/TPCC_full_schema.txt");
+        } else {
+            tpcc_init::init_schema("config/schema_file/TPCC_short_schema.txt");
+        }
+        init_tables();
+        // SSI runs on OCC without the buffer pool only
+        if Catalog::global().concurrency_control() != ConcurrencyControl::Occ
+            || cfg!(feature = "buffer_pool")
+        {
+            let mut buffer = TransactionBuffer::new(Catalog::global(), thread_id());
+            let mut txn = Transaction::new(&mut buffer, false);
+            assert!(matches!(
+                txn.begin_with(IsolationLevel::SerializableSnapshot),
+                Err(Error::IsolationNotSupported)
+            ));
+            return;
+        }
+        let districts = &Catalog::global().get_table("DISTRICT");
+        let tid0 = districts.search_tuple_id(&district_key(0, 1)).unwrap();
+        let tid1 = districts.search_tuple_id(&district_key(0, 2)).unwrap();
+        // t1: read d1, read d2
+        // t2: read d1, read d2
+        // t1: update d1, commit
+        // t2: update d2, commit
+        // snapshot isolation lets both commit, SSI aborts t2: it read d1
+        // over t1 and t1 read d2 over it
+        let levels = [
+            (IsolationLevel::SnapshotIsolation, true),
+            (IsolationLevel::SerializableSnapshot, false),
+        ];
+        for (isolation, commits) in levels.iter() {
+            let mut buffer1 = TransactionBuffer::new(Catalog::global(), thread_id());
+            let mut buffer2 = TransactionBuffer::new(Catalog::global(), thread_id());
+            let mut t1 = Transaction::new(&mut buffer1, false);
+            t1.begin_with(*isolation).unwrap();
+            let mut t2 = Transaction::new(&mut buffer2, false);
+            t2.begin_with(*isolation).unwrap();
+            let oid0 = next_oid(&mut t1, districts, &tid0);
+            let oid1 = next_oid(&mut t1, districts, &tid1);
+            next_oid(&mut t2, districts, &tid0);
+            next_oid(&mut t2, districts, &tid1);
+            t1.update(districts, &tid0, 10, &(oid0 + 1).to_le_bytes())
+                .unwrap();
+            assert!(t1.commit().is_ok());
+            t2.update(districts, &tid1, 10, &(oid1 + 1).to_le_bytes())
+                .unwrap();
+            assert_eq!(t2.commit().is_ok(), *commits);
+
+            let mut buffer3 = TransactionBuffer::new(Catalog::global(), thread_id());
+            let mut t3 = Transaction::new(&mut buffer3, false);
+            t3.begin();
+            assert_eq!(next_oid(&mut t3, districts, &tid0), oid0 + 1);
+            let expected = if *commits { oid1 + 1 } else { oid1 };
+            assert_eq!(next_oid(&mut t3, districts, &tid1), expected);
+            assert!(t3.commit().is_ok());
+        }
+    }
 }
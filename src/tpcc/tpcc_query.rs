@@ -1,5 +1,6 @@
 use chrono::Local;
 
+use crate::mvcc_config::thread_count;
 use crate::storage::catalog::Catalog;
 use crate::storage::table::Table;
 use crate::tpcc::*;
@@ -43,8 +44,8 @@ pub struct TpccQuery {
 }
 impl TpccQuery {
     fn wid_for_thread(rng: &mut ThreadRng, thread_id: u64) -> u64 {
-        let k1 = (WAREHOUSES - thread_id - 1) / (TRANSACTION_COUNT as u64);
-        let k2 = u64_rand(rng, 0, k1) * (TRANSACTION_COUNT as u64);
+        let k1 = (WAREHOUSES - thread_id - 1) / (thread_count() as u64);
+        let k2 = u64_rand(rng, 0, k1) * (thread_count() as u64);
         k2 + thread_id
     }
     pub fn gen_new_order(rng: &mut ThreadRng, thread_id: u64) -> Self {
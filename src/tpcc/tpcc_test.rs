@@ -6,12 +6,13 @@ mod tests {
     use crate::tpcc::tpcc::*;
     use crate::tpcc::*;
     // use crate::tpcc::tpcc_index::TpccIndex;
-    use crate::mvcc_config::{TEST_THREAD_COUNT, THREAD_COUNT, TRANSACTION_COUNT};
+    use crate::mvcc_config::{TEST_THREAD_COUNT, thread_count, TRANSACTION_COUNT};
     use crate::tpcc::tpcc_init;
     use crate::tpcc::tpcc_query::*;
     use crate::tpcc::{tpcc_txn_asycn, tpcc_txn_sycn};
     use crate::transaction::transaction::Transaction;
     use crate::transaction::transaction_buffer::TransactionBuffer;
+    use crate::transaction::cc::{ConcurrencyControl, LockPolicy};
     use crate::utils::executor::executor::Executor;
     use log4rs;
     use std::fs::OpenOptions;
@@ -27,7 +28,10 @@ mod tests {
         // debug!("INFO");
         // debug!("DEBUG");
         NVMTableStorage::init_test_database();
-        Catalog::init_catalog();
+        Catalog::init_catalog_with(ConcurrencyControl::from_env());
+        Catalog::global()
+            .lock_waits()
+            .set_policy(LockPolicy::from_env());
         if IS_FULL_SCHEMA {
             tpcc_init::init_schema("config/schema_file/TPCC_full_schema.txt");
         } else {
@@ -71,7 +75,7 @@ mod tests {
                 let tablelist = TableList::new(&catalog);
 
                 #[cfg(feature = "nbtree")]
-                crate::storage::index::nbtree::init_index((i+THREAD_COUNT) as i32);
+                crate::storage::index::nbtree::init_index((i+thread_count()) as i32);
                 let mut num = 0;
                 let mut total = 0;
                 println!("test start {} ", i);
@@ -253,8 +257,8 @@ mod tests {
             hit as f64 / TEST_THREAD_COUNT as f64,
             read as f64 / TEST_THREAD_COUNT as f64,
             update as f64 / TEST_THREAD_COUNT as f64,
-            vec.get(THREAD_COUNT * 1000).unwrap(),
-            vec.get(THREAD_COUNT * 9500).unwrap(),
+            vec.get(thread_count() * 1000).unwrap(),
+            vec.get(thread_count() * 9500).unwrap(),
         );
         #[cfg(feature = "txn_clock")]
         println!(
@@ -266,6 +270,13 @@ mod tests {
             vec.get(TEST_THREAD_COUNT * 9500).unwrap(),
             vec.get(TEST_THREAD_COUNT * 9900).unwrap(),
         );
+        if catalog.concurrency_control().is_locking() {
+            println!(
+                "{}: {:?}",
+                catalog.lock_waits().policy().name(),
+                catalog.lock_waits().total()
+            );
+        }
         // #[cfg(feature = "clock")]
         // for v in vec {
         //     f.write(v.to_string().as_bytes()).unwrap();
@@ -30,7 +30,7 @@ pub async fn run_new_order<'a>(
     let cid = new_order.cid;
     let entry_d = new_order.entry_d;
     let customer: TupleVec;
-    match customers.search_tuple_id(&IndexType::Int64(customer_key(wid, did, cid))) {
+    match customers.search_tuple_id(&customer_key(wid, did, cid)) {
         Ok(tid) => {
             txn.prefetch_read(customers, &tid).await;
             match txn.read(customers, &tid) {
@@ -76,7 +76,7 @@ pub async fn run_new_order<'a>(
             .unwrap(),
     );
     let tid = districts
-        .search_tuple_id(&IndexType::Int64(district_key(wid, did)))
+        .search_tuple_id(&district_key(wid, did))
         .unwrap();
     let district: TupleVec;
     txn.prefetch_read(districts, &tid).await;
@@ -121,7 +121,7 @@ pub async fn run_new_order<'a>(
         }
     }
     let mut tuple = txn.alloc(orders);
-    tuple.save_u64(0, order_key(wid, did, oid));
+    tuple.save_u64(0, oid);
     tuple.save_u64(8, cid);
     tuple.save_u64(16, did);
     tuple.save_u64(24, wid);
@@ -131,7 +131,7 @@ pub async fn run_new_order<'a>(
     tuple.save_u64(56, all_local);
 
     let mut tuple = txn.alloc(new_orders);
-    tuple.save_u64(0, order_key(wid, did, oid));
+    tuple.save_u64(0, oid);
     tuple.save_u64(8, did);
     tuple.save_u64(16, wid);
 
@@ -168,7 +168,7 @@ pub async fn run_new_order<'a>(
         );
         let stock: TupleVec;
         let s_tid: TupleId;
-        match stocks.search_tuple_id(&IndexType::Int64(stock_key(ol_wid, ol_iid))) {
+        match stocks.search_tuple_id(&stock_key(ol_wid, ol_iid)) {
             Ok(tid) => {
                 s_tid = tid.clone();
                 txn.prefetch_read(stocks, &tid).await;
@@ -206,15 +206,14 @@ pub async fn run_new_order<'a>(
         let _ol_amount = ol_quantity as f64 * i_price * (1.0 + w_tax + d_tax) * (1.0 - c_discount); //Full schema
 
         let mut tuple = txn.alloc(order_lines);
-        tuple.save_u64(0, order_key(wid, did, oid));
+        tuple.save_u64(0, oid);
         tuple.save_u64(8, did);
         tuple.save_u64(16, wid);
         tuple.save_u64(24, olid);
         tuple.save_u64(32, ol_iid);
     }
 
-    txn.commit();
-    true
+    txn.commit().is_ok()
 }
 pub async fn run_payment<'a>(
     txn: &mut Transaction<'a>,
@@ -268,7 +267,7 @@ pub async fn run_payment<'a>(
     }
     let district: TupleVec;
     let d_tid: TupleId;
-    match districts.search_tuple_id(&IndexType::Int64(district_key(wid, did))) {
+    match districts.search_tuple_id(&district_key(wid, did)) {
         Ok(tid) => {
             txn.prefetch_read(districts, &tid).await;
 
@@ -314,8 +313,10 @@ pub async fn run_payment<'a>(
     if by_last {
         let c_last = payment.c_last.clone();
         match customers.search_tuple_id_on_index(
-            &IndexType::String(customer_last_key(&c_last, c_wid, c_did)),
-            schema.search_by_name("C_LAST").unwrap(),
+            &customer_last_key(&c_last, c_wid, c_did),
+            customers
+                .get_index_id_by_name(&["C_W_ID", "C_D_ID", "C_LAST"])
+                .unwrap(),
         ) {
             Ok(tid) => {
                 txn.prefetch_read(customers, &tid).await;
@@ -336,15 +337,15 @@ pub async fn run_payment<'a>(
                 return false;
             }
         }
-        cid = cid_from_key(u64::from_le_bytes(
+        cid = u64::from_le_bytes(
             customer
                 .get_column_by_id(schema, schema.search_by_name("C_ID").unwrap())
                 .try_into()
                 .unwrap(),
-        ));
+        );
     } else {
         cid = payment.cid;
-        match customers.search_tuple_id(&IndexType::Int64(customer_key(c_wid, c_did, cid))) {
+        match customers.search_tuple_id(&customer_key(c_wid, c_did, cid)) {
             Ok(tid) => {
                 txn.prefetch_read(customers, &tid).await;
                 match txn.read(customers, &tid) {
@@ -385,6 +386,5 @@ pub async fn run_payment<'a>(
     tuple.save_u64(16, c_wid);
     tuple.save_u64(24, 0);
     tuple.save(32, &h_amount.to_le_bytes());
-    txn.commit();
-    true
+    txn.commit().is_ok()
 }
@@ -8,6 +8,7 @@ use crate::transaction::transaction::*;
 use crate::transaction::transaction_buffer::*;
 use chrono::prelude::*;
 use std::convert::TryInto;
+use std::ops::Bound;
 
 pub fn run_new_order<'a>(
     txn: &mut Transaction<'a>,
@@ -15,8 +16,8 @@ pub fn run_new_order<'a>(
     new_order: &TpccQuery,
 ) -> bool {
     // let mut txn = Transaction::new(buffer, false);
-    txn.begin();
     txn.read_only = false;
+    txn.begin();
     let warehouses = &table_list.warehouses;
     let districts = &table_list.districts;
     let customers = &table_list.customers;
@@ -30,7 +31,7 @@ pub fn run_new_order<'a>(
     let cid = new_order.cid;
     let entry_d = new_order.entry_d;
     let customer: TupleVec;
-    match customers.search_tuple_id(&IndexType::Int64(customer_key(wid, did, cid))) {
+    match customers.search_tuple_id(&customer_key(wid, did, cid)) {
         Ok(tid) => match txn.read(customers, &tid) {
             Ok(row) => {
                 customer = row;
@@ -73,7 +74,7 @@ pub fn run_new_order<'a>(
             .unwrap(),
     );
     let tid = districts
-        .search_tuple_id(&IndexType::Int64(district_key(wid, did)))
+        .search_tuple_id(&district_key(wid, did))
         .unwrap();
     let district: TupleVec;
     match txn.read(districts, &tid) {
@@ -120,21 +121,27 @@ pub fn run_new_order<'a>(
     }
     {
         let mut tuple = txn.alloc(orders);
-        tuple.save_u64(0, order_key(wid, did, oid));
-        tuple.save_u64(8, customer_key(wid, did, cid));
+        tuple.save_u64(0, oid);
+        tuple.save_u64(8, cid);
         tuple.save_u64(16, did);
         tuple.save_u64(24, wid);
         tuple.save_u64(32, entry_d);
         tuple.save_u64(40, 0);
         tuple.save_u64(48, ol_cnt);
         tuple.save_u64(56, all_local);
-        orders.index_insert_by_tuple(&TupleId::from_address(tuple._address()), &tuple).unwrap();
+        if orders.index_insert_by_tuple(&TupleId::from_address(tuple._address()), &tuple).is_err() {
+            txn.abort();
+            return false;
+        }
         let mut tuple = txn.alloc(new_orders);
-        tuple.save_u64(0, order_key(wid, did, oid));
+        tuple.save_u64(0, oid);
         tuple.save_u64(8, did);
         tuple.save_u64(16, wid);
-        // println!("insert w {}, d {}, {}", wid, did, order_key(wid, did, oid));
-        new_orders.index_insert(IndexType::Int64(order_key(wid, did, oid)), &TupleId::from_address(tuple._address())).unwrap();
+        // println!("insert w {}, d {}, {}", wid, did, oid);
+        if new_orders.index_insert(new_order_key(wid, did, oid), &TupleId::from_address(tuple._address())).is_err() {
+            txn.abort();
+            return false;
+        }
         // println!("insert end");
 
 
@@ -173,7 +180,7 @@ pub fn run_new_order<'a>(
         let i_price = f64::from_le_bytes(item.data.as_slice().try_into().unwrap());
         let stock: TupleVec;
         let s_tid: TupleId;
-        match stocks.search_tuple_id(&IndexType::Int64(stock_key(ol_wid, ol_iid))) {
+        match stocks.search_tuple_id(&stock_key(ol_wid, ol_iid)) {
             Ok(tid) => {
                 s_tid = tid.clone();
                 match txn.read(stocks, &tid) {
@@ -211,10 +218,10 @@ pub fn run_new_order<'a>(
         }
 
         let mut tuple = txn.alloc(order_lines);
-        tuple.save_u64(0, order_key(wid, did, oid));
+        tuple.save_u64(0, oid);
         tuple.save_u64(8, did);
         tuple.save_u64(16, wid);
-        tuple.save_u64(24, order_line_key(wid, did, oid, olid));
+        tuple.save_u64(24, olid);
         tuple.save_u64(32, ol_iid);
         if IS_FULL_SCHEMA {
             tuple.save_u64(40, 0); //OL_SUPPLY_WID
@@ -223,11 +230,13 @@ pub fn run_new_order<'a>(
             tuple.save_f64(64, _ol_amount);
             tuple.save_u64(72, 0);
         }
-        // println!("insert wid {}, {}", wid, order_line_key(wid, did, oid, olid));
-        order_lines.index_insert(IndexType::Int64(order_line_key(wid, did, oid, olid)), &TupleId::from_address(tuple._address())).unwrap();
+        // println!("insert wid {}, {}", wid, olid);
+        if order_lines.index_insert(order_line_key(wid, did, oid, olid), &TupleId::from_address(tuple._address())).is_err() {
+            txn.abort();
+            return false;
+        }
     }
-    // println!("add {} ol from {} to {}", next_oid, order_line_key(wid, did, oid, 0), order_line_key(wid, did, oid, ol_cnt));
-    txn.commit()
+    txn.commit().is_ok()
 }
 pub fn run_payment<'a>(
     txn: &mut Transaction<'a>,
@@ -235,8 +244,8 @@ pub fn run_payment<'a>(
     payment: &TpccQuery,
 ) -> bool {
     // let mut txn = Transaction::new(buffer, false);
-    txn.begin();
     txn.read_only = false;
+    txn.begin();
 
     let warehouses = &table_list.warehouses;
     let districts = &table_list.districts;
@@ -283,7 +292,7 @@ pub fn run_payment<'a>(
     }
     let district: TupleVec;
     let d_tid: TupleId;
-    match districts.search_tuple_id(&IndexType::Int64(district_key(wid, did))) {
+    match districts.search_tuple_id(&district_key(wid, did)) {
         Ok(tid) => match txn.read(districts, &tid) {
             Ok(row) => {
                 d_tid = tid;
@@ -327,8 +336,10 @@ pub fn run_payment<'a>(
     if by_last {
         let c_last = payment.c_last.clone();
         match customers.search_tuple_id_on_index(
-            &IndexType::String(customer_last_key(&c_last, c_wid, c_did)),
-            schema.search_by_name("C_LAST").unwrap(),
+            &customer_last_key(&c_last, c_wid, c_did),
+            customers
+                .get_index_id_by_name(&["C_W_ID", "C_D_ID", "C_LAST"])
+                .unwrap(),
         ) {
             Ok(tid) => match txn.read(customers, &tid) {
                 Ok(row) => {
@@ -346,15 +357,15 @@ pub fn run_payment<'a>(
                 return false;
             }
         }
-        cid = cid_from_key(u64::from_le_bytes(
+        cid = u64::from_le_bytes(
             customer
                 .get_column_by_id(schema, schema.search_by_name("C_ID").unwrap())
                 .try_into()
                 .unwrap(),
-        ));
+        );
     } else {
         cid = payment.cid;
-        match customers.search_tuple_id(&IndexType::Int64(customer_key(c_wid, c_did, cid))) {
+        match customers.search_tuple_id(&customer_key(c_wid, c_did, cid)) {
             Ok(tid) => match txn.read(customers, &tid) {
                 Ok(row) => {
                     c_tid = tid;
@@ -390,7 +401,7 @@ pub fn run_payment<'a>(
     }
     {
         let mut tuple = txn.alloc(histories);
-        tuple.save_u64(0, customer_key(wid, did, cid));
+        tuple.save_u64(0, cid);
         tuple.save_u64(8, c_did);
         tuple.save_u64(16, c_wid);
         if IS_FULL_SCHEMA {
@@ -404,7 +415,7 @@ pub fn run_payment<'a>(
             tuple.save(32, &h_amount.to_le_bytes());
         }
     }
-    if txn.commit() {
+    if txn.commit().is_ok() {
         return true;
     }
     return false;
@@ -414,8 +425,8 @@ pub fn run_stock_level<'a>(
     table_list: &'a TableList,
     stock_level: &TpccQuery,
 ) -> bool {
-    txn.begin();
     txn.read_only = true;
+    txn.begin();
     let districts = &table_list.districts;
     let order_lines = &table_list.order_lines;
     let stocks = &table_list.stocks;
@@ -425,7 +436,7 @@ pub fn run_stock_level<'a>(
 
     // 1.获取街区表 d_next_o_id;
     let d_tid: TupleId;
-    match districts.search_tuple_id(&IndexType::Int64(district_key(wid, did))) {
+    match districts.search_tuple_id(&district_key(wid, did)) {
         Ok(tid) => match txn.read(districts, &tid) {
             Ok(row) => {
                 d_tid = tid;
@@ -451,13 +462,13 @@ pub fn run_stock_level<'a>(
     );
 
     // 2.获取街区内20个订单库存不足的去重货物种类数量
-    let max_orderline_key = order_line_key(wid, did, d_next_o_id-1, MAX_LINES_PER_ORDER);
-    let min_orderline_key = order_line_key(wid, did, d_next_o_id-20, 0);
+    // order lines of orders [d_next_o_id - 20, d_next_o_id)
+    let max_orderline_key = order_key(wid, did, d_next_o_id);
+    let min_orderline_key = order_key(wid, did, d_next_o_id-20);
 
-    let lines:Vec<TupleId>;
-    // println!("read {} old from {} to {}", d_next_o_id, min_orderline_key, max_orderline_key);
+    let lines: Vec<TupleId>;
 
-    match order_lines.range_tuple_id(&IndexType::Int64(min_orderline_key), &IndexType::Int64(max_orderline_key)) {
+    match txn.range_tuple_id(order_lines, Bound::Included(min_orderline_key), Bound::Excluded(max_orderline_key)) {
         Ok(l) => {
             lines = l;
         },
@@ -511,7 +522,7 @@ pub fn run_stock_level<'a>(
         let stock: TupleColumnVec;
         let schema = &stocks.schema;
         let column = schema.search_by_name("S_QUANTITY").unwrap();
-        match stocks.search_tuple_id(&IndexType::Int64(stock_key(wid, *item_id))) {
+        match stocks.search_tuple_id(&stock_key(wid, *item_id)) {
             Ok(tid) => {
                 match txn.read_column(stocks, &tid, column) {
                     Ok(row) => {
@@ -535,7 +546,7 @@ pub fn run_stock_level<'a>(
         }
     }
     assert!(scnt >= 0);
-    if txn.commit() {
+    if txn.commit().is_ok() {
         return true;
     }
     return false;
@@ -546,8 +557,8 @@ pub fn run_order_status<'a>(
     table_list: &'a TableList,
     order_status: &TpccQuery,
 ) -> bool {
-    txn.begin();
     txn.read_only = true;
+    txn.begin();
     let orders = &table_list.orders;
     let order_lines = &table_list.order_lines;
     let customers = &table_list.customers;
@@ -562,8 +573,10 @@ pub fn run_order_status<'a>(
     // 1.获取顾客信息
     if by_last {
         match customers.search_tuple_id_on_index(
-            &IndexType::String(customer_last_key(c_last, wid, did)),
-            schema.search_by_name("C_LAST").unwrap(),
+            &customer_last_key(c_last, wid, did),
+            customers
+                .get_index_id_by_name(&["C_W_ID", "C_D_ID", "C_LAST"])
+                .unwrap(),
         ) {
             Ok(tid) => match txn.read(customers, &tid) {
                 Ok(row) => {                    customer = row;
@@ -578,18 +591,17 @@ pub fn run_order_status<'a>(
                 return false;
             }
         }
-        cid = cid_from_key(u64::from_le_bytes(
+        cid = u64::from_le_bytes(
             customer
                 .get_column_by_id(schema, schema.search_by_name("C_ID").unwrap())
                 .try_into()
                 .unwrap(),
-        ));
+        );
     } else {
-        match customers.search_tuple_id(&IndexType::Int64(customer_key(wid, did, cid))) {
+        match customers.search_tuple_id(&customer_key(wid, did, cid)) {
             Ok(tid) => match txn.read(customers, &tid) {
                 Ok(row) => {
                     customer = row;
-                    cid = customer_key(wid, did, cid);
                 }
                 _ => {
                     txn.abort();
@@ -607,7 +619,10 @@ pub fn run_order_status<'a>(
     let schema = &orders.schema;
     let mut oid: u64 = 0;
     // println!("1111");
-    match orders.search_tuple_id_on_index(&IndexType::Int64(cid), schema.search_by_name("O_C_ID").unwrap()) {
+    match orders.search_tuple_id_on_index(
+        &order_customer_key(wid, did, cid),
+        orders.get_index_id_by_name(&["O_W_ID", "O_D_ID", "O_C_ID"]).unwrap(),
+    ) {
         
         Ok(tid) => {
             // 
@@ -632,14 +647,8 @@ pub fn run_order_status<'a>(
     }
 
     // 3. 获取orderline信息
-    let oid = oid % ORDERS_PER_DISTRICT;
-
-    let max_orderline_key = order_line_key(wid, did, oid, MAX_LINES_PER_ORDER);
-    let min_orderline_key = order_line_key(wid, did, oid, 0);
-    // println!("read {} old from {} to {}", d_next_o_id, min_orderline_key, max_orderline_key);
-
-    let lines:Vec<TupleId>;
-    match order_lines.range_tuple_id(&IndexType::Int64(min_orderline_key), &IndexType::Int64(max_orderline_key)) {
+    let lines: Vec<TupleId>;
+    match txn.prefix_tuple_id(order_lines, &order_key(wid, did, oid)) {
         Ok(l) => {
             lines = l;
             // assert!(lines.len() == 0 || lines.len() >= 5);
@@ -673,7 +682,7 @@ pub fn run_order_status<'a>(
         }
     }
 
-    if txn.commit() {
+    if txn.commit().is_ok() {
         return true;
     }
     return false;
@@ -695,16 +704,22 @@ pub fn run_deliver<'a>(
     for did in 0..DISTRICTS_PER_WAREHOUSE { 
         loop 
         {
-            txn.begin();
             txn.read_only = false;
+            txn.begin();
             let oid:u64;
             let no_tid: TupleId;
             // 1.查找最早的未交付订单
-            let max_order_key = order_key(wid, did, ORDERS_PER_DISTRICT);
-            let min_order_key = order_key(wid, did, 0);
-            // println!("de w {} d {} {} {}", wid, did, min_order_key, max_order_key);
-            match new_orders.last_range_tuple_id(&IndexType::Int64(min_order_key), &IndexType::Int64(max_order_key)) {
-                Ok(tid) => {
+            let district_orders = district_key(wid, did);
+            let last_order = match txn.prefix_last_tuple_id(new_orders, &district_orders) {
+                Ok(last) => last,
+                Err(_) => {
+                    // not an empty district, the delivery did not run
+                    txn.abort();
+                    return false;
+                }
+            };
+            match last_order {
+                Some(tid) => {
                     no_tid = tid;
                     match txn.read(new_orders, &no_tid) {
                         Ok(row) => {
@@ -727,7 +742,7 @@ pub fn run_deliver<'a>(
             let o_tid: TupleId;
             let schema = &orders.schema;
 
-            match orders.search_tuple_id(&IndexType::Int64(oid)) {
+            match orders.search_tuple_id(&order_key(wid, did, oid)) {
                 Ok(tid) => match txn.read(orders, &tid) {
                     Ok(row) => {
                         o_tid = tid;
@@ -763,13 +778,9 @@ pub fn run_deliver<'a>(
                 }
             }
             // 3. 更新orderline数据表
-            let oid = oid % ORDERS_PER_DISTRICT;
-            let max_orderline_key = order_line_key(wid, did, oid, MAX_LINES_PER_ORDER);
-            let min_orderline_key = order_line_key(wid, did, oid, 0);
-    
-            let lines:Vec<TupleId>;
+            let lines: Vec<TupleId>;
 
-            match order_lines.range_tuple_id(&IndexType::Int64(min_orderline_key), &IndexType::Int64(max_orderline_key)) {
+            match txn.prefix_tuple_id(order_lines, &order_key(wid, did, oid)) {
                 Ok(l) => {
                     lines = l;
                 },
@@ -809,7 +820,7 @@ pub fn run_deliver<'a>(
             let column: usize = schema.search_by_name("C_BALANCE").unwrap();
             let c_tid: TupleId;
             let customer: TupleVec;
-            match customers.search_tuple_id(&IndexType::Int64(cid)) {
+            match customers.search_tuple_id(&customer_key(wid, did, cid)) {
                 Ok(tid) => match txn.read(customers, &tid) {
                     Ok(row) => {
                         c_tid = tid;
@@ -907,7 +918,7 @@ pub fn run_deliver<'a>(
             // }
             // println!("deilver {} {} end", wid, did);
 
-            if txn.commit() {
+            if txn.commit().is_ok() {
 
                 break;
 
@@ -4,12 +4,15 @@ use crate::config::Address;
 use crate::storage::catalog::Catalog;
 use crate::storage::global::Timer;
 use crate::storage::global::UPDATING;
-use crate::storage::table::{Table, TupleId};
+use crate::storage::table::{IndexType, Table, TupleId};
 use crate::storage::timestamp::TimeStamp;
+use crate::transaction::cc::ConcurrencyControl;
 use crate::utils::file;
 use crate::{Error, Result};
 use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
+use std::ops::Bound;
 
 #[derive(Debug)]
 pub struct AccessStruct<'a> {
@@ -32,21 +35,74 @@ impl<'a> AccessStruct<'a> {
         ts.tid == self.ts.tid
     }
 
-    pub fn finish(&self, thread_id: usize, cur_min_txn: u64) {
-        #[cfg(feature = "cc_cfg_2pl")]
-        {
-            #[cfg(not(feature = "buffer_pool"))]
-            let tuple = self.table.get_tuple(&self.tuple_id);
-            #[cfg(feature = "buffer_pool")]
-            let tuple = self
-                .table
-                .get_tuple_buffer(&self.tuple_id, thread_id, self.ts.tid, cur_min_txn)
-                .0
-                .data
-                .read();
-            // println!("{} release lock ", self.tuple_id.get_address());
-            tuple.release_read_lock();
+    pub fn finish(&self, cc: ConcurrencyControl, thread_id: usize, cur_min_txn: u64) {
+        if !cc.is_locking() {
+            return;
         }
+        #[cfg(not(feature = "buffer_pool"))]
+        let tuple = self.table.get_tuple(&self.tuple_id);
+        #[cfg(feature = "buffer_pool")]
+        let tuple = self
+            .table
+            .get_tuple_buffer(&self.tuple_id, thread_id, self.ts.tid, cur_min_txn)
+            .0
+            .data
+            .read();
+        // println!("{} release lock ", self.tuple_id.get_address());
+        cc.release_read(&tuple);
+    }
+}
+
+/// A range of an ordered index scanned by a serializable transaction, with
+/// the tuples found in it.
+#[derive(Debug)]
+pub struct ScanSetStruct<'a> {
+    pub table: &'a Table,
+    pub index_id: usize,
+    pub lower: Bound<IndexType>,
+    pub upper: Bound<IndexType>,
+    pub tuple_ids: Vec<TupleId>,
+}
+impl<'a> ScanSetStruct<'a> {
+    pub fn new(
+        table: &'a Table,
+        index_id: usize,
+        lower: Bound<IndexType>,
+        upper: Bound<IndexType>,
+        tuple_ids: Vec<TupleId>,
+    ) -> Self {
+        ScanSetStruct {
+            table,
+            index_id,
+            lower,
+            upper,
+            tuple_ids,
+        }
+    }
+
+    /// Tuples in the range now and not at the scan or the other way round,
+    /// leaving out those `inserted` by the transaction itself.
+    pub fn phantoms(&self, inserted: &HashSet<u64>) -> Result<Vec<TupleId>> {
+        let scanned: HashSet<u64> = self.tuple_ids.iter().map(|t| t.get_address()).collect();
+        let mut found = HashSet::new();
+        let mut phantoms = Vec::new();
+        for tuple_id in self.table.range_cursor_on_index(
+            self.lower.clone(),
+            self.upper.clone(),
+            self.index_id,
+        )? {
+            let address = tuple_id.get_address();
+            found.insert(address);
+            if !scanned.contains(&address) && !inserted.contains(&address) {
+                phantoms.push(tuple_id);
+            }
+        }
+        for tuple_id in &self.tuple_ids {
+            if !found.contains(&tuple_id.get_address()) {
+                phantoms.push(tuple_id.clone());
+            }
+        }
+        Ok(phantoms)
     }
 }
 
@@ -94,6 +150,7 @@ impl<'a> WriteSetStruct<'a> {
         address: Address,
         #[cfg(all(feature = "mvcc", feature = "ilog"))] d_delta_address: Address,
         column_id: usize,
+        cc: ConcurrencyControl,
         thread_id: usize,
         cur_min_txn: u64,
         #[cfg(feature = "clock")] timer: &mut Timer,
@@ -117,6 +174,7 @@ impl<'a> WriteSetStruct<'a> {
                     column_id,
                     self.data.as_slice(),
                     self.ts,
+                    cc,
                     thread_id,
                     cur_min_txn,
                     #[cfg(feature = "buffer_direct")]
@@ -135,8 +193,9 @@ impl<'a> WriteSetStruct<'a> {
                 }
 
                 let tuple = self.table.get_tuple(&self.tuple_id);
-                #[cfg(not(feature = "cc_cfg_2pl"))]
-                assert_eq!(tuple.lock_tid(), self.ts.tid);
+                if !cc.is_locking() {
+                    assert_eq!(tuple.lock_tid(), self.ts.tid);
+                }
                 tuple.set_ts_tid(self.ts.tid);
                 // T
                 // file::sfence();
@@ -190,6 +249,7 @@ impl<'a> WriteSetStruct<'a> {
                     #[cfg(all(feature = "mvcc", feature = "ilog"))]
                     d_delta_address,
                     self.ts,
+                    cc,
                     thread_id,
                     cur_min_txn,
                     #[cfg(feature = "buffer_direct")]
@@ -208,8 +268,9 @@ impl<'a> WriteSetStruct<'a> {
                 }
 
                 let tuple = self.table.get_tuple(&self.tuple_id);
-                #[cfg(not(feature = "cc_cfg_2pl"))]
-                assert_eq!(tuple.lock_tid(), self.ts.tid);
+                if !cc.is_locking() {
+                    assert_eq!(tuple.lock_tid(), self.ts.tid);
+                }
                 tuple.set_ts_tid(self.ts.tid);
                 self.table.remove_tuple(&self.tuple_id, thread_id).unwrap();
                 // T
@@ -258,7 +319,7 @@ impl<'a> WriteSetStruct<'a> {
         return self.table.get_address(&self.tuple_id);
     }
 
-    pub fn finish(&self, thread_id: usize, cur_min_txn: u64) {
+    pub fn finish(&self, cc: ConcurrencyControl, thread_id: usize, cur_min_txn: u64) {
         #[cfg(not(feature = "buffer_pool"))]
         let tuple: crate::storage::row::Tuple = self.table.get_tuple(&self.tuple_id);
         #[cfg(feature = "buffer_pool")]
@@ -275,19 +336,7 @@ impl<'a> WriteSetStruct<'a> {
             .0
             .data
             .read();
-        #[cfg(feature = "cc_cfg_2pl")]
-        {
-            tuple.release_write_lock();
-        }
-        #[cfg(not(feature = "cc_cfg_2pl"))]
-        {
-            // println!("{} {}", tuple.lock_tid(), self.ts.tid);
-            // assert!(tuple.lock_tid() == self.ts.tid);
-            if tuple.lock_tid() == self.ts.tid {
-                // println!("{:?}", tuple.get_data(self.table.tuple_size));
-                tuple.set_lock_tid(0);
-            }
-        }
+        cc.release_write(&tuple, self.ts.tid);
         #[cfg(all(feature = "clwb_tuple", not(feature = "buffer_pool")))]
         if self.is_insert {
             tuple.clwb_len(self.table.tuple_size);
@@ -309,7 +358,6 @@ impl<'a> WriteSetStruct<'a> {
             let tuple = self.table.get_tuple(&self.tuple_id);
             tuple.apply_next();
         }
-        #[cfg(not(feature = "cc_cfg_2pl"))]
         {
             #[cfg(not(feature = "buffer_pool"))]
             let tuple = self.table.get_tuple(&self.tuple_id);
@@ -0,0 +1,526 @@
+//! Concurrency control protocols.
+//!
+//! The protocol is picked when the database is opened and owns the hooks a
+//! transaction calls on the tuples it touches: `read` before a version is
+//! checked for visibility, `lock` on every tuple it updates at commit,
+//! `validates` before the writes are installed, and `release_read` /
+//! `release_write` when it finishes.
+//!
+//! Under 2PL a conflicting lock request is handled by the `LockPolicy` of
+//! the catalog: abort at once, or wait ordered by transaction timestamps
+//! so that no deadlock forms. A wait is bounded by `LOCK_WAIT_TIMEOUT`
+//! all the same, the holders of read locks are anonymous. Under wound-wait
+//! a wounded holder aborts at its next lock request or before it installs
+//! its writes, the older transaction waits up to `WOUNDED_WAIT_TIMEOUT` for
+//! it.
+use crate::config::POW_2_63;
+use crate::storage::catalog::Catalog;
+use crate::storage::row::{BufferDataVec, Tuple, TupleError};
+use crate::storage::timestamp::TimeStamp;
+use crate::mvcc_config::THREAD_ID_BITS;
+use crate::transaction::snapshot::{SnapShot, SnapShotEntity};
+use crate::Result;
+use parking_lot::{Mutex, RwLockReadGuard};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+/// Longest a lock request waits before it aborts.
+pub const LOCK_WAIT_TIMEOUT: Duration = Duration::from_millis(1);
+/// Longest a lock request waits for a holder it wounded.
+pub const WOUNDED_WAIT_TIMEOUT: Duration = Duration::from_millis(100);
+/// Spins of a waiting request before it yields the thread.
+const LOCK_SPINS: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConcurrencyControl {
+    /// Optimistic, the read set is validated at commit.
+    Occ,
+    /// Timestamp ordering, a write behind a later write or read aborts.
+    To,
+    /// Two-phase locking on the read-ts word of the tuple header.
+    TwoPl,
+}
+
+impl Default for ConcurrencyControl {
+    /// The protocol of the `local_cc_cfg_*` feature, OCC without one.
+    fn default() -> Self {
+        if cfg!(feature = "cc_cfg_to") {
+            ConcurrencyControl::To
+        } else if cfg!(feature = "cc_cfg_2pl") {
+            ConcurrencyControl::TwoPl
+        } else {
+            ConcurrencyControl::Occ
+        }
+    }
+}
+
+impl ConcurrencyControl {
+    pub const ALL: [ConcurrencyControl; 3] = [
+        ConcurrencyControl::Occ,
+        ConcurrencyControl::To,
+        ConcurrencyControl::TwoPl,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConcurrencyControl::Occ => "occ",
+            ConcurrencyControl::To => "to",
+            ConcurrencyControl::TwoPl => "2pl",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|cc| cc.name().eq_ignore_ascii_case(name))
+    }
+    /// The protocol named by the `N2DB_CC` environment variable, for the
+    /// benchmarks to run every protocol from one build.
+    pub fn from_env() -> Self {
+        std::env::var("N2DB_CC")
+            .ok()
+            .and_then(|name| Self::from_name(&name))
+            .unwrap_or_default()
+    }
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+    pub fn from_u8(value: u8) -> Self {
+        Self::ALL[value as usize]
+    }
+
+    /// Whether a transaction sees the versions before its own timestamp
+    /// rather than those before its snapshot was taken.
+    pub fn orders_by_tid(self) -> bool {
+        self != ConcurrencyControl::Occ
+    }
+    /// Whether writes hold a lock until the transaction finishes.
+    pub fn is_locking(self) -> bool {
+        self == ConcurrencyControl::TwoPl
+    }
+    /// Whether the read set is validated at commit.
+    pub fn validates(self) -> bool {
+        self == ConcurrencyControl::Occ
+    }
+
+    /// Called by transaction `txn` on the latest version of a tuple it reads,
+    /// true if the read is protected by a lock and needs no visibility check.
+    pub fn read<T: LockWord + ?Sized>(self, tuple: &T, txn: u64) -> Result<bool> {
+        match self {
+            ConcurrencyControl::TwoPl => {
+                acquire(tuple, txn, || tuple.lock_read(txn))?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Lock a tuple updated by the transaction of `ts`, which holds `counter`
+    /// read locks on it.
+    pub fn lock<T: LockWord + ?Sized>(
+        self,
+        tuple: &T,
+        snapshot: &SnapShotEntity,
+        ts: TimeStamp,
+        cur_min_txn: u64,
+        counter: u64,
+    ) -> Result {
+        let lock_tid = tuple.lock_tid();
+        match self {
+            ConcurrencyControl::Occ => {
+                if lock_tid != 0 && lock_tid != ts.tid {
+                    return Err(TupleError::TupleChanged {
+                        conflict_tid: lock_tid,
+                    }
+                    .into());
+                }
+                let locked = TimeStamp {
+                    tid: lock_tid,
+                    ..TimeStamp::default()
+                };
+                if !snapshot.access(locked, ts.tid, cur_min_txn) {
+                    return Err(TupleError::TupleChanged {
+                        conflict_tid: lock_tid,
+                    }
+                    .into());
+                }
+            }
+            ConcurrencyControl::To => {
+                if lock_tid != 0 && lock_tid != ts.tid {
+                    return Err(TupleError::TupleChanged {
+                        conflict_tid: lock_tid,
+                    }
+                    .into());
+                }
+                let tuple_ts = tuple.ts();
+                if tuple_ts.read_ts > ts.tid || tuple_ts.tid > ts.tid {
+                    return Err(TupleError::TupleChanged {
+                        conflict_tid: tuple_ts.read_ts,
+                    }
+                    .into());
+                }
+            }
+            ConcurrencyControl::TwoPl => {
+                return acquire(tuple, ts.tid, || tuple.lock_write(ts.tid, counter));
+            }
+        }
+        let cas_result = tuple.cas_lock_tid(0, ts.tid);
+        if cas_result != 0 && cas_result != ts.tid {
+            return Err(TupleError::TupleChanged {
+                conflict_tid: cas_result,
+            }
+            .into());
+        }
+        if tuple.ts().tid > ts.tid {
+            return Err(TupleError::TupleChanged {
+                conflict_tid: cas_result,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Release what `read` took on a tuple.
+    pub fn release_read<T: LockWord + ?Sized>(self, tuple: &T) {
+        if self.is_locking() {
+            tuple.release_read_lock();
+        }
+    }
+    /// Release what `lock` took on a tuple for transaction `txn`.
+    pub fn release_write<T: LockWord + ?Sized>(self, tuple: &T, txn: u64) {
+        if self.is_locking() {
+            // a transaction that failed to lock must not drop the readers
+            if tuple.ts().read_ts == txn | POW_2_63 {
+                tuple.release_write_lock();
+            }
+        } else if tuple.lock_tid() == txn {
+            tuple.set_lock_tid(0);
+        }
+    }
+}
+
+/// How a 2PL transaction treats a lock another one holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockPolicy {
+    /// Abort at once.
+    NoWait,
+    /// An older transaction waits for a younger holder, a younger one
+    /// aborts.
+    WaitDie,
+    /// An older transaction wounds a younger holder, which aborts at its
+    /// next lock request or at commit, and waits; a younger one waits.
+    WoundWait,
+}
+
+impl Default for LockPolicy {
+    fn default() -> Self {
+        LockPolicy::NoWait
+    }
+}
+
+impl LockPolicy {
+    pub const ALL: [LockPolicy; 3] = [
+        LockPolicy::NoWait,
+        LockPolicy::WaitDie,
+        LockPolicy::WoundWait,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LockPolicy::NoWait => "no-wait",
+            LockPolicy::WaitDie => "wait-die",
+            LockPolicy::WoundWait => "wound-wait",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|policy| policy.name().eq_ignore_ascii_case(name))
+    }
+    /// The policy named by the `N2DB_LOCK_POLICY` environment variable.
+    pub fn from_env() -> Self {
+        std::env::var("N2DB_LOCK_POLICY")
+            .ok()
+            .and_then(|name| Self::from_name(&name))
+            .unwrap_or_default()
+    }
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+    pub fn from_u8(value: u8) -> Self {
+        Self::ALL[value as usize]
+    }
+
+    /// Whether `txn` waits for `holder`, 0 for a lock held by readers.
+    fn waits(self, txn: u64, holder: u64, waits: &LockWaits) -> bool {
+        match self {
+            LockPolicy::NoWait => false,
+            LockPolicy::WaitDie => holder != 0 && txn < holder,
+            LockPolicy::WoundWait => {
+                if holder != 0 && txn < holder {
+                    waits.wound(holder);
+                }
+                true
+            }
+        }
+    }
+}
+
+/// Lock requests of a transaction that found the lock taken.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LockStats {
+    pub conflicts: u64,
+    /// Conflicts the policy let wait.
+    pub waits: u64,
+    pub wait_nanos: u64,
+    /// Waits that ran out of `LOCK_WAIT_TIMEOUT`.
+    pub timeouts: u64,
+    /// Requests refused since an older transaction wounded it.
+    pub wounded: u64,
+}
+
+impl LockStats {
+    fn add(&mut self, other: &LockStats) {
+        self.conflicts += other.conflicts;
+        self.waits += other.waits;
+        self.wait_nanos += other.wait_nanos;
+        self.timeouts += other.timeouts;
+        self.wounded += other.wounded;
+    }
+}
+
+#[derive(Debug, Default)]
+struct ThreadLockStats {
+    current: LockStats,
+    total: LockStats,
+}
+
+/// The lock policy with the wounds and lock-wait statistics of the
+/// transactions, per thread.
+#[derive(Debug)]
+pub struct LockWaits {
+    policy: AtomicU8,
+    /// The tid of the transaction of each thread an older one wounded.
+    wounded: Vec<AtomicU64>,
+    stats: Vec<Mutex<ThreadLockStats>>,
+}
+
+impl LockWaits {
+    pub fn new() -> Self {
+        let threads = 1 << THREAD_ID_BITS;
+        LockWaits {
+            policy: AtomicU8::new(LockPolicy::default().to_u8()),
+            wounded: std::iter::repeat_with(|| AtomicU64::new(0))
+                .take(threads)
+                .collect(),
+            stats: std::iter::repeat_with(Default::default)
+                .take(threads)
+                .collect(),
+        }
+    }
+    pub fn policy(&self) -> LockPolicy {
+        LockPolicy::from_u8(self.policy.load(Ordering::Relaxed))
+    }
+    /// Pick the policy while no transaction runs.
+    pub fn set_policy(&self, policy: LockPolicy) {
+        self.policy.store(policy.to_u8(), Ordering::Relaxed);
+    }
+    fn wound(&self, txn: u64) {
+        self.wounded[SnapShot::thread_of(txn)].store(txn, Ordering::SeqCst);
+    }
+    pub fn is_wounded(&self, txn: u64) -> bool {
+        self.wounded[SnapShot::thread_of(txn)].load(Ordering::SeqCst) == txn
+    }
+    /// `TupleError::Wounded` if an older transaction wounded `txn`, checked
+    /// at commit before the writes are installed.
+    pub fn check_wound(&self, txn: u64) -> Result {
+        if !self.is_wounded(txn) {
+            return Ok(());
+        }
+        let stats = LockStats {
+            wounded: 1,
+            ..LockStats::default()
+        };
+        self.record(txn, &stats);
+        Err(TupleError::Wounded.into())
+    }
+    fn record(&self, txn: u64, stats: &LockStats) {
+        let mut slot = self.stats[SnapShot::thread_of(txn)].lock();
+        slot.current.add(stats);
+        slot.total.add(stats);
+    }
+    /// Statistics of the transactions of `thread_id` since the last call.
+    pub fn take_stats(&self, thread_id: usize) -> LockStats {
+        std::mem::take(&mut self.stats[thread_id].lock().current)
+    }
+    /// Statistics of every transaction so far.
+    pub fn total(&self) -> LockStats {
+        let mut total = LockStats::default();
+        for slot in &self.stats {
+            total.add(&slot.lock().total);
+        }
+        total
+    }
+}
+
+/// Take a lock of `txn` on `tuple` with `try_lock`, waiting for the holder
+/// while the policy allows.
+fn acquire<T: LockWord + ?Sized>(
+    tuple: &T,
+    txn: u64,
+    try_lock: impl Fn() -> std::result::Result<(), TupleError>,
+) -> Result {
+    let waits = Catalog::global().lock_waits();
+    let mut stats = LockStats::default();
+    let mut started: Option<Instant> = None;
+    let mut spins = 0;
+    let result = loop {
+        if waits.is_wounded(txn) {
+            stats.wounded += 1;
+            break Err(TupleError::Wounded);
+        }
+        let err = match try_lock() {
+            Ok(()) => break Ok(()),
+            Err(err) => err,
+        };
+        let word = tuple.ts().read_ts;
+        let holder = if word & POW_2_63 != 0 {
+            word & !POW_2_63
+        } else {
+            0
+        };
+        let policy = waits.policy();
+        if !policy.waits(txn, holder, waits) {
+            if started.is_none() {
+                stats.conflicts += 1;
+            }
+            break Err(err);
+        }
+        let start = *started.get_or_insert_with(|| {
+            stats.conflicts += 1;
+            stats.waits += 1;
+            Instant::now()
+        });
+        let timeout = if policy == LockPolicy::WoundWait && holder != 0 && txn < holder {
+            WOUNDED_WAIT_TIMEOUT
+        } else {
+            LOCK_WAIT_TIMEOUT
+        };
+        if start.elapsed() > timeout {
+            stats.timeouts += 1;
+            break Err(err);
+        }
+        spins += 1;
+        if spins % LOCK_SPINS == 0 {
+            std::thread::yield_now();
+        } else {
+            std::hint::spin_loop();
+        }
+    };
+    if let Some(start) = started {
+        stats.wait_nanos = start.elapsed().as_nanos() as u64;
+    }
+    if stats != LockStats::default() {
+        waits.record(txn, &stats);
+    }
+    result.map_err(Into::into)
+}
+
+/// The tuple header words the protocols work on, of a tuple on NVM or in
+/// the buffer pool.
+pub trait LockWord {
+    fn ts(&self) -> TimeStamp;
+    fn lock_tid(&self) -> u64;
+    fn set_lock_tid(&self, tid: u64) -> bool;
+    fn cas_lock_tid(&self, old_ts: u64, new_ts: u64) -> u64;
+    fn lock_read(&self, reader: u64) -> std::result::Result<(), TupleError>;
+    fn release_read_lock(&self);
+    fn lock_write(&self, txn_id: u64, counter: u64) -> std::result::Result<(), TupleError>;
+    fn release_write_lock(&self);
+}
+
+macro_rules! impl_lock_word {
+    ($t:ty) => {
+        impl_lock_word!($t, $t);
+    };
+    ($t:ty, $inner:ty) => {
+        impl LockWord for $t {
+            fn ts(&self) -> TimeStamp {
+                <$inner>::ts(self)
+            }
+            fn lock_tid(&self) -> u64 {
+                <$inner>::lock_tid(self)
+            }
+            fn set_lock_tid(&self, tid: u64) -> bool {
+                <$inner>::set_lock_tid(self, tid)
+            }
+            fn cas_lock_tid(&self, old_ts: u64, new_ts: u64) -> u64 {
+                <$inner>::cas_lock_tid(self, old_ts, new_ts)
+            }
+            fn lock_read(&self, reader: u64) -> std::result::Result<(), TupleError> {
+                <$inner>::lock_read(self, reader)
+            }
+            fn release_read_lock(&self) {
+                <$inner>::release_read_lock(self)
+            }
+            fn lock_write(&self, txn_id: u64, counter: u64) -> std::result::Result<(), TupleError> {
+                <$inner>::lock_write(self, txn_id, counter)
+            }
+            fn release_write_lock(&self) {
+                <$inner>::release_write_lock(self)
+            }
+        }
+    };
+}
+impl_lock_word!(Tuple);
+impl_lock_word!(BufferDataVec);
+impl_lock_word!(RwLockReadGuard<'_, BufferDataVec>, BufferDataVec);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cc_names() {
+        for cc in ConcurrencyControl::ALL.iter() {
+            assert_eq!(ConcurrencyControl::from_name(cc.name()), Some(*cc));
+            assert_eq!(ConcurrencyControl::from_u8(cc.to_u8()), *cc);
+        }
+        assert_eq!(
+            ConcurrencyControl::from_name("2PL"),
+            Some(ConcurrencyControl::TwoPl)
+        );
+        assert_eq!(ConcurrencyControl::from_name("mvto"), None);
+        for policy in LockPolicy::ALL.iter() {
+            assert_eq!(LockPolicy::from_name(policy.name()), Some(*policy));
+            assert_eq!(LockPolicy::from_u8(policy.to_u8()), *policy);
+        }
+    }
+
+    #[test]
+    fn test_lock_policy() {
+        let waits = LockWaits::new();
+        // holders 20 and 30, the younger the larger
+        assert!(!LockPolicy::NoWait.waits(20, 30, &waits));
+        assert!(LockPolicy::WaitDie.waits(20, 30, &waits));
+        assert!(!LockPolicy::WaitDie.waits(30, 20, &waits));
+        assert!(!LockPolicy::WaitDie.waits(20, 0, &waits));
+        assert!(LockPolicy::WoundWait.waits(30, 20, &waits));
+        assert!(!waits.is_wounded(20));
+        assert!(LockPolicy::WoundWait.waits(20, 30, &waits));
+        assert!(waits.is_wounded(30));
+        assert!(!waits.is_wounded(30 + (1 << THREAD_ID_BITS)));
+
+        let stats = LockStats {
+            conflicts: 1,
+            waits: 1,
+            ..LockStats::default()
+        };
+        waits.record(30, &stats);
+        waits.record(30, &stats);
+        assert_eq!(waits.take_stats(SnapShot::thread_of(30)).waits, 2);
+        assert_eq!(waits.take_stats(SnapShot::thread_of(30)), LockStats::default());
+        assert_eq!(waits.total().conflicts, 2);
+    }
+}
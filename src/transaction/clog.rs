@@ -1,63 +1,186 @@
+//! The commit status of every transaction by tid, kept on NVM.
+//!
+//! A tid is looked up by its tick, `tid >> THREAD_ID_BITS`, which the clog
+//! snapshot hands out from a dense counter, so no two tids share one. Ticks
+//! are cut in segments of `SEGMENT_TXN_COUNT`, each on a page of its own.
+//!
+//! The area at the end of the catalog page (`CLOG_SPACE_SIZE`) holds the
+//! first segment kept, then a page address per slot: segment `s` lives in
+//! slot `s % CLOG_MAX_PAGES`, and the first word of its page is `s + 1`.
+//!
+//! A transaction below every running one and every pinned snapshot has
+//! finished, its writes are committed or rolled back, so the segments below it
+//! are truncated and read as `FROZEN`. Their pages are reused by the segments
+//! after them.
 use crate::config::Address;
 use crate::config::PAGE_SIZE;
 use crate::config::U64_OFFSET;
+use crate::mvcc_config::THREAD_ID_BITS;
+use crate::storage::catalog::{Catalog, CLOG_MAX_PAGES};
+use crate::storage::nvm_file::NVMTableStorage;
 use crate::storage::timestamp::TimeStamp;
-use crossbeam::queue::SegQueue;
+use crate::utils::{file, io};
+use crate::{Error, Result};
+use parking_lot::Mutex;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 
-const MAX_TXN_COUNT_PER_PAGE: u64 = PAGE_SIZE / U64_OFFSET;
-const MAX_CLOG_SIZE: u64 = 32;
+/// Ticks on a segment page, after the segment number.
+pub const SEGMENT_TXN_COUNT: u64 = PAGE_SIZE / U64_OFFSET - 1;
+/// The status of a tid in a truncated segment.
+pub const FROZEN: u64 = u64::MAX;
 
-#[derive(Debug)]
-pub struct ClogPage {
-    page: Vec<AtomicU64>,
+const BASE_OFFSET: u64 = 0;
+const SLOT_OFFSET: u64 = U64_OFFSET;
+
+fn word<'a>(address: Address) -> &'a AtomicU64 {
+    unsafe { &*(address as *const AtomicU64) }
 }
-impl ClogPage {
-    pub fn new() -> Self {
-        ClogPage {
-            page: std::iter::from_fn(|| Some(AtomicU64::new(0)))
-                .take(MAX_TXN_COUNT_PER_PAGE as usize)
-                .collect(),
-        }
-    }
-    fn set(&self, tid: u64, status: u64) {
-        self.page
-            .get(tid as usize)
-            .unwrap()
-            .store(status, Ordering::Relaxed);
-    }
-    fn get(&self, tid: u64) -> u64 {
-        self.page.get(tid as usize).unwrap().load(Ordering::Relaxed)
-    }
+fn persist(address: Address) {
+    unsafe { io::clwb(address as *const u8) };
+    file::sfence();
 }
 
 #[derive(Debug)]
 pub struct Clog {
-    page: ClogPage, //Vec<ClogPage>,
+    address: Address,
+    /// Held to open or truncate segments.
+    segments: Mutex<()>,
 }
 
 impl Clog {
-    pub fn new() -> Clog {
+    /// An empty log in the `CLOG_SPACE_SIZE` bytes at `address`.
+    pub fn new(address: Address) -> Clog {
+        for i in 0..CLOG_MAX_PAGES + 1 {
+            word(address + i * U64_OFFSET).store(0, Ordering::SeqCst);
+        }
+        let mut iter = address;
+        while iter < address + SLOT_OFFSET + CLOG_MAX_PAGES * U64_OFFSET {
+            unsafe { io::clwb(iter as *const u8) };
+            iter += 64;
+        }
+        file::sfence();
+        Clog::reload(address)
+    }
+    pub fn reload(address: Address) -> Clog {
         Clog {
-            // pages: std::iter::from_fn(|| Some(ClogPage::new()))
-            //     .take(MAX_CLOG_SIZE as usize)
-            //     .collect(),
-            page: ClogPage::new(),
+            address,
+            segments: Mutex::new(()),
         }
     }
+    /// The first segment kept.
+    pub fn base(&self) -> u64 {
+        word(self.address + BASE_OFFSET).load(Ordering::SeqCst)
+    }
+    fn slot(&self, segment: u64) -> Address {
+        self.address + SLOT_OFFSET + segment % CLOG_MAX_PAGES * U64_OFFSET
+    }
+    /// The page of `segment`, None before it is opened.
+    fn page(&self, segment: u64) -> Option<Address> {
+        match word(self.slot(segment)).load(Ordering::SeqCst) {
+            0 => None,
+            page if word(page).load(Ordering::SeqCst) == segment + 1 => Some(page),
+            _ => None,
+        }
+    }
+    fn segment(tid: u64) -> u64 {
+        (tid >> THREAD_ID_BITS) / SEGMENT_TXN_COUNT
+    }
+    fn status_address(page: Address, tid: u64) -> Address {
+        page + (1 + (tid >> THREAD_ID_BITS) % SEGMENT_TXN_COUNT) * U64_OFFSET
+    }
+
+    /// The status saved for `ts`, 0 if none is.
     pub fn get(&self, ts: TimeStamp) -> u64 {
-        // let page_id = 0;//(ts.tid / MAX_TXN_COUNT_PER_PAGE) % MAX_CLOG_SIZE;
-        let page_offset = ts.tid % MAX_TXN_COUNT_PER_PAGE;
-        // let page = self.pages.get(page_id as usize).unwrap();
-        self.page.get(page_offset)
-    }
-    pub fn save(&self, ts: TimeStamp, status: u64) {
-        // let page_id = 0;//(ts.tid / MAX_TXN_COUNT_PER_PAGE) % MAX_CLOG_SIZE;
-        let page_offset = ts.tid % MAX_TXN_COUNT_PER_PAGE;
-        // println!("{}", page_id);
-        // let page = self.pages.get(page_id as usize).unwrap();
-        self.page.set(page_offset, status);
+        let segment = Self::segment(ts.tid);
+        if segment < self.base() {
+            return FROZEN;
+        }
+        let status = match self.page(segment) {
+            Some(page) => word(Self::status_address(page, ts.tid)).load(Ordering::SeqCst),
+            None => 0,
+        };
+        // the page may have been taken over by a later segment meanwhile
+        if segment < self.base() {
+            return FROZEN;
+        }
+        status
+    }
+    /// Save the status of `ts` durably. Fails with `NoSpace` while a
+    /// transaction `CLOG_MAX_PAGES` segments back is running, and with
+    /// `NotFound` once the segment of `ts` is truncated.
+    pub fn save(&self, ts: TimeStamp, status: u64) -> Result {
+        let segment = Self::segment(ts.tid);
+        let page = match self.page(segment) {
+            Some(page) => page,
+            None => self.open(segment, ts.tid)?,
+        };
+        let iter = Self::status_address(page, ts.tid);
+        word(iter).store(status, Ordering::SeqCst);
+        persist(iter);
+        Ok(())
+    }
+    fn open(&self, segment: u64, tid: u64) -> Result<Address> {
+        let _guard = self.segments.lock();
+        if let Some(page) = self.page(segment) {
+            return Ok(page);
+        }
+        if segment < self.base() {
+            return Err(Error::NotFound);
+        }
+        if segment >= self.base() + CLOG_MAX_PAGES {
+            // with nothing running the tids before `tid` have all finished
+            self.truncate_locked(Catalog::get_min_active().unwrap_or(tid));
+        }
+        if segment >= self.base() + CLOG_MAX_PAGES {
+            return Err(Error::NoSpace);
+        }
+        // the slot is empty or holds a segment below the base
+        let slot = self.slot(segment);
+        let page = match word(slot).load(Ordering::SeqCst) {
+            0 => NVMTableStorage::global_mut().alloc_page()?.page_start,
+            page => page,
+        };
+        word(page).store(0, Ordering::SeqCst);
+        persist(page);
+        let mut iter = page;
+        while iter < page + PAGE_SIZE {
+            unsafe {
+                io::write(iter, [0u8; 64]);
+                io::clwb(iter as *const u8);
+            }
+            iter += 64;
+        }
+        word(page).store(segment + 1, Ordering::SeqCst);
+        persist(page);
+        word(slot).store(page, Ordering::SeqCst);
+        persist(slot);
+        Ok(page)
+    }
+    /// Drop the segments below the one of `min_txn`.
+    pub fn truncate(&self, min_txn: u64) {
+        let _guard = self.segments.lock();
+        self.truncate_locked(min_txn);
+    }
+    fn truncate_locked(&self, min_txn: u64) {
+        let base = Self::segment(min_txn);
+        if base > self.base() {
+            word(self.address + BASE_OFFSET).store(base, Ordering::SeqCst);
+            persist(self.address + BASE_OFFSET);
+        }
+    }
+    /// The largest status saved in the segments kept.
+    pub fn max_status(&self) -> u64 {
+        let base = self.base();
+        let mut max_status = 0;
+        for segment in base..base + CLOG_MAX_PAGES {
+            if let Some(page) = self.page(segment) {
+                let statuses =
+                    unsafe { io::read_slice::<u64>(page + U64_OFFSET, SEGMENT_TXN_COUNT as usize) };
+                max_status = statuses.iter().copied().fold(max_status, u64::max);
+            }
+        }
+        max_status
     }
 }
 
@@ -65,30 +188,35 @@ impl Clog {
 mod tests {
     use super::*;
     use crate::config::NVM_ADDR;
-    use crate::storage::catalog::Catalog;
-    use crate::storage::nvm_file::NVMTableStorage;
 
     #[test]
     fn test_clog_rw() {
-        let clog = Clog::new();
+        NVMTableStorage::init_test_database();
+        let address = NVMTableStorage::global_mut()
+            .alloc_page()
+            .unwrap()
+            .page_start;
+        let clog = Clog::new(address);
         let mut ts0 = TimeStamp::default();
         let mut ts1 = TimeStamp::default();
 
         ts0.tid = 0;
-        ts1.tid = 1;
+        ts1.tid = 1 << THREAD_ID_BITS | 1;
 
-        clog.save(ts0, 1);
-        clog.save(ts1, 0);
+        clog.save(ts0, 1).unwrap();
+        clog.save(ts1, 0).unwrap();
         assert_eq!(clog.get(ts0), 1);
         assert_eq!(clog.get(ts1), 0);
 
-        // let clog_reload = Clog::reload(page_id.page_start);
-        // assert_eq!(clog_reload.get(ts0), 1);
-        // assert_eq!(clog_reload.get(ts1), 0);
+        // consecutive ticks share the page of their segment
+        assert_eq!(Clog::segment(ts0.tid), Clog::segment(ts1.tid));
+
+        let clog_reload = Clog::reload(address);
+        assert_eq!(clog_reload.get(ts0), 1);
+        assert_eq!(clog_reload.get(ts1), 0);
     }
 
     #[test]
-    #[cfg(feature = "cc_cfg_occ")]
     fn test_clog_in_catalog() {
         NVMTableStorage::init_test_database();
         let catalog = Catalog::new(NVM_ADDR);
@@ -97,10 +225,45 @@ mod tests {
         let mut ts1 = TimeStamp::default();
 
         ts0.tid = 0;
-        ts1.tid = 1;
-        clog.save(ts0, 1);
-        clog.save(ts1, 0);
+        ts1.tid = 1 << THREAD_ID_BITS | 1;
+        clog.save(ts0, 1).unwrap();
+        clog.save(ts1, 0).unwrap();
         assert_eq!(clog.get(ts0), 1);
         assert_eq!(clog.get(ts1), 0);
     }
+
+    #[test]
+    fn test_clog_truncate() {
+        NVMTableStorage::init_test_database();
+        let address = NVMTableStorage::global_mut()
+            .alloc_page()
+            .unwrap()
+            .page_start;
+        let clog = Clog::new(address);
+        let ts = |tick: u64| TimeStamp {
+            tid: tick << THREAD_ID_BITS | 1,
+            ..TimeStamp::default()
+        };
+        let old = ts(SEGMENT_TXN_COUNT - 1);
+        let new = ts(SEGMENT_TXN_COUNT * 2);
+        clog.save(old, 7).unwrap();
+        clog.save(new, 9).unwrap();
+        // tids past the window do not alias the ones before
+        let far = ts(SEGMENT_TXN_COUNT * (CLOG_MAX_PAGES + 2));
+        assert_eq!(clog.get(far), 0);
+        assert_eq!(clog.max_status(), 9);
+
+        clog.truncate(ts(SEGMENT_TXN_COUNT * 2).tid);
+        let clog = Clog::reload(address);
+        assert_eq!(clog.base(), 2);
+        assert_eq!(clog.get(old), FROZEN);
+        assert_eq!(clog.get(new), 9);
+        // segment 2 gives its slot over and still reads as truncated
+        clog.truncate(ts(SEGMENT_TXN_COUNT * (CLOG_MAX_PAGES + 1)).tid);
+        clog.save(far, 11).unwrap();
+        assert_eq!(clog.get(far), 11);
+        assert_eq!(clog.get(old), FROZEN);
+        assert_eq!(clog.get(new), FROZEN);
+        assert!(matches!(clog.save(old, 13), Err(Error::NotFound)));
+    }
 }
@@ -0,0 +1,190 @@
+//! Garbage collection of old versions.
+//!
+//! A delta written by transaction `t` keeps the version `t` overwrote, which
+//! only snapshots before `t` read. Every thread writes its deltas into
+//! regions of its own, the pages of its `TransactionBuffer` or the DRAM pages
+//! under `ilog`, and retires a full region with the last tid that wrote to
+//! it, its epoch. Under `append` the regions are the tuple slots of replaced
+//! versions. The horizon follows `Catalog::get_min_txn`, the oldest running
+//! transaction, and is advanced by the thread asking for a region: once it
+//! passes an epoch no snapshot follows a pointer into the region, which is
+//! reused.
+//!
+//! Writers unlink what is behind a version every snapshot sees, and a reader
+//! whose snapshot fell behind the horizon aborts with `SnapshotTooOld`
+//! rather than read a reused region.
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::storage::catalog::Catalog;
+use crate::transaction::snapshot::SnapShotEntity;
+
+/// The horizon of a database, in `Catalog::get_gc`.
+#[derive(Debug, Default)]
+pub struct VersionGc {
+    horizon: AtomicU64,
+    reclaimed: AtomicU64,
+    /// In nanoseconds.
+    retention: AtomicU64,
+}
+
+impl VersionGc {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Versions written before it are no longer kept.
+    pub fn horizon(&self) -> u64 {
+        self.horizon.load(Ordering::SeqCst)
+    }
+    /// Move the horizon up to the oldest snapshot still running, kept the
+    /// retention window behind the clock.
+    pub fn advance(&self) -> u64 {
+        let mut min_txn = Catalog::get_min_txn();
+        let retention = self.retention();
+        if retention > Duration::ZERO {
+            let snapshots = Catalog::global().get_snapshot();
+            let mut snapshot = SnapShotEntity::new();
+            snapshots.get_snapshot(&mut snapshot);
+            min_txn = min_txn.min(snapshot.clock.saturating_sub(snapshots.span(retention)));
+        }
+        self.horizon
+            .fetch_max(min_txn, Ordering::SeqCst)
+            .max(min_txn)
+    }
+    /// Whether a snapshot at `clock` may miss versions it reads.
+    pub fn too_old(&self, clock: u64) -> bool {
+        clock < self.horizon()
+    }
+    /// Keep the versions of the last `retention` for
+    /// `Transaction::read_as_of`, as many ticks as the oracle counts in it.
+    /// It holds the horizon back from now on, zero keeps only what running
+    /// snapshots read.
+    pub fn set_retention(&self, retention: Duration) {
+        let nanos = retention.as_nanos().min(u64::MAX as u128) as u64;
+        self.retention.store(nanos, Ordering::SeqCst);
+    }
+    pub fn retention(&self) -> Duration {
+        Duration::from_nanos(self.retention.load(Ordering::SeqCst))
+    }
+    /// Regions reused so far.
+    pub fn reclaimed(&self) -> u64 {
+        self.reclaimed.load(Ordering::Relaxed)
+    }
+}
+
+/// The full regions of a thread, oldest first.
+pub struct RetiredRegions<T> {
+    regions: VecDeque<(T, u64)>,
+}
+
+impl<T> RetiredRegions<T> {
+    pub fn new() -> Self {
+        RetiredRegions {
+            regions: VecDeque::new(),
+        }
+    }
+    pub fn retire(&mut self, region: T, epoch: u64) {
+        self.regions.push_back((region, epoch));
+    }
+    /// The oldest region if no snapshot reads it any more.
+    pub fn reclaim(&mut self) -> Option<T> {
+        let epoch = self.regions.front()?.1;
+        let gc = Catalog::global().get_gc();
+        if epoch >= gc.horizon() && epoch >= gc.advance() {
+            return None;
+        }
+        gc.reclaimed.fetch_add(1, Ordering::Relaxed);
+        self.regions.pop_front().map(|(region, _)| region)
+    }
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+}
+
+impl<T> Default for RetiredRegions<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for RetiredRegions<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.regions.iter().map(|(_, epoch)| epoch))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::nvm_file::NVMTableStorage;
+    use crate::transaction::testing::thread_id;
+    use crate::transaction::transaction_buffer::TransactionBuffer;
+    use std::time::Instant;
+
+    #[test]
+    fn test_retired_regions() {
+        NVMTableStorage::init_test_database();
+        Catalog::init_catalog();
+        let mut buffer = TransactionBuffer::new(Catalog::global(), thread_id());
+        buffer.run(|_| Ok(())).unwrap();
+        let gc = Catalog::global().get_gc();
+        let horizon = gc.advance();
+        assert!(horizon > 0);
+        assert!(!gc.too_old(horizon));
+        assert!(gc.too_old(horizon - 1));
+
+        let mut regions = RetiredRegions::new();
+        regions.retire(1, horizon - 1);
+        // the transaction that filled it may still run
+        regions.retire(2, u64::MAX);
+        assert_eq!(regions.reclaim(), Some(1));
+        assert_eq!(regions.reclaim(), None);
+        assert_eq!(regions.len(), 1);
+        assert!(gc.reclaimed() >= 1);
+    }
+
+    #[test]
+    fn test_idle_thread() {
+        NVMTableStorage::init_test_database();
+        Catalog::init_catalog();
+        let mut idle = TransactionBuffer::new(Catalog::global(), thread_id());
+        let tid = idle.run(|txn| Ok(txn.ts.tid)).unwrap();
+        let mut regions = RetiredRegions::new();
+        regions.retire(1, tid);
+        // the idle buffer keeps its last tid, yet holds nothing back; the
+        // transactions of other tests may, for a while
+        let start = Instant::now();
+        while regions.reclaim().is_none() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(regions.len(), 0);
+        drop(idle);
+    }
+
+    #[test]
+    fn test_retention() {
+        NVMTableStorage::init_test_database();
+        Catalog::init_catalog();
+        // not the one of the catalog, its horizon would stop for every test
+        let gc = VersionGc::new();
+        let mut buffer = TransactionBuffer::new(Catalog::global(), thread_id());
+        gc.set_retention(Duration::MAX);
+        buffer.run(|_| Ok(())).unwrap();
+        // versions of the whole window are kept
+        assert_eq!(gc.advance(), 0);
+        gc.set_retention(Duration::from_secs(3600));
+        let horizon = gc.advance();
+        let snapshots = Catalog::global().get_snapshot();
+        let mut snapshot = SnapShotEntity::new();
+        snapshots.get_snapshot(&mut snapshot);
+        let hour = snapshots.span(Duration::from_secs(3600));
+        assert!(horizon <= snapshot.clock.saturating_sub(hour));
+        gc.set_retention(Duration::ZERO);
+        assert!(gc.advance() > 0);
+    }
+}
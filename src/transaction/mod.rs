@@ -1,4 +1,5 @@
 use numeric_enum_macro::numeric_enum;
+use std::time::Duration;
 
 numeric_enum! {
     #[repr(u8)]
@@ -14,13 +15,199 @@ numeric_enum! {
     }
 }
 
+/// Isolation of a transaction, picked at `Transaction::begin_with`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IsolationLevel {
+    /// Every read sees the versions committed before it.
+    ReadCommitted,
+    /// Reads see the snapshot taken at begin, only write-write conflicts
+    /// abort.
+    SnapshotIsolation,
+    /// Snapshot isolation plus read-set validation at commit.
+    Serializable,
+    /// Snapshot isolation aborting only the pivots of rw-antidependency
+    /// cycles, see `ssi`. Needs OCC.
+    SerializableSnapshot,
+}
+
+impl Default for IsolationLevel {
+    fn default() -> Self {
+        if cfg!(feature = "serializable") {
+            IsolationLevel::Serializable
+        } else {
+            IsolationLevel::SnapshotIsolation
+        }
+    }
+}
+
+/// How `TransactionBuffer::run` retries a transaction that conflicted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt, None to retry until it commits.
+    pub max_retries: Option<u32>,
+    /// Wait before the first retry, doubled on each one up to `max_backoff`.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: Some(16),
+            backoff: Duration::from_micros(1),
+            max_backoff: Duration::from_millis(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Retry at once and until it commits, as the benchmark loops do.
+    pub fn forever() -> Self {
+        RetryPolicy {
+            max_retries: None,
+            backoff: Duration::from_secs(0),
+            max_backoff: Duration::from_secs(0),
+        }
+    }
+    /// Wait before retry `retry`, counting from 0.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.backoff
+            .checked_mul(1 << retry.min(31))
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+}
+
+/// A committed transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitInfo {
+    /// Timestamp of the versions the transaction wrote.
+    pub commit_ts: u64,
+}
+
+/// A point of a running transaction that `Transaction::rollback_to` undoes
+/// its later writes back to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Savepoint {
+    tid: u64,
+    writes: usize,
+}
+
+/// What made a transaction abort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbortKind {
+    /// A tuple it read changed, a scanned range got a phantom, a unique key
+    /// it inserted was taken or, under SSI, it was a pivot.
+    Validation,
+    /// Another transaction wrote a tuple it writes, `conflict_tid` if known
+    /// and 0 otherwise.
+    WriteConflict { conflict_tid: u64 },
+    /// A lock was refused, not granted in time or taken by a wound.
+    LockTimeout,
+    /// A version it needs is no longer kept.
+    SnapshotTooOld,
+    /// Aborted by the caller.
+    User,
+    /// An error unrelated to other transactions, such as a missing tuple,
+    /// a full page or an IO error. Running it again fails the same way.
+    Internal,
+}
+
+impl AbortKind {
+    /// Kind of the abort that a failed write or read with `err` leads to.
+    pub fn from_error(err: &crate::Error) -> Self {
+        use crate::storage::row::TupleError;
+        match err {
+            crate::Error::Tuple(TupleError::TupleChanged { conflict_tid }) => AbortKind::WriteConflict {
+                conflict_tid: *conflict_tid,
+            },
+            crate::Error::Tuple(TupleError::AcquireReadLockFalse)
+            | crate::Error::Tuple(TupleError::AcquireWriteLockFalse)
+            | crate::Error::Tuple(TupleError::Wounded) => AbortKind::LockTimeout,
+            crate::Error::Tuple(TupleError::PreValidationFailed) | crate::Error::Exist => AbortKind::Validation,
+            crate::Error::Tuple(TupleError::SnapshotTooOld) => AbortKind::SnapshotTooOld,
+            crate::Error::TxConflict | crate::Error::TxNeedAbort => AbortKind::WriteConflict { conflict_tid: 0 },
+            crate::Error::Aborted(reason) => reason.kind,
+            crate::Error::NoSpace
+            | crate::Error::NotFound
+            | crate::Error::IsolationNotSupported
+            | crate::Error::PoolMismatch { .. }
+            | crate::Error::IO(_)
+            | crate::Error::Tuple(_) => AbortKind::Internal,
+        }
+    }
+    /// Whether running the transaction again may commit.
+    pub fn is_conflict(&self) -> bool {
+        match self {
+            AbortKind::Validation
+            | AbortKind::WriteConflict { .. }
+            | AbortKind::LockTimeout
+            | AbortKind::SnapshotTooOld => true,
+            AbortKind::User | AbortKind::Internal => false,
+        }
+    }
+}
+
+/// Why `Transaction::commit` aborted, with the table and the tuple address
+/// it failed on when there is one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbortReason {
+    pub kind: AbortKind,
+    pub table: Option<String>,
+    pub tuple: Option<u64>,
+}
+
+impl AbortReason {
+    pub fn new(kind: AbortKind) -> Self {
+        AbortReason {
+            kind,
+            table: None,
+            tuple: None,
+        }
+    }
+    /// An abort on table `table_id` as a whole.
+    pub fn on_table(kind: AbortKind, table_id: u32) -> Self {
+        AbortReason {
+            kind,
+            table: crate::storage::catalog::Catalog::global().table_name(table_id),
+            tuple: None,
+        }
+    }
+    /// An abort on the tuple at `tuple` of table `table_id`.
+    pub fn on(kind: AbortKind, table_id: u32, tuple: u64) -> Self {
+        AbortReason {
+            kind,
+            table: crate::storage::catalog::Catalog::global().table_name(table_id),
+            tuple: Some(tuple),
+        }
+    }
+}
+
+impl std::fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.kind)?;
+        if let Some(table) = &self.table {
+            write!(f, " on table {}", table)?;
+        }
+        if let Some(tuple) = self.tuple {
+            write!(f, " tuple {:#x}", tuple)?;
+        }
+        Ok(())
+    }
+}
+
 pub mod access;
+pub mod cc;
 pub mod clog;
+pub mod gc;
+pub mod session;
 pub mod snapshot;
+pub mod ssi;
+#[cfg(test)]
+pub(crate) mod testing;
 pub mod transaction;
 pub mod transaction_buffer;
 
-pub const THREAD_ID_BITS: u64 = 8;
+pub use crate::mvcc_config::THREAD_ID_BITS;
 pub const THREAD_ID_MASK: u64 = (1u64 << THREAD_ID_BITS) - 1;
 
 pub fn get_thread_id(tid: u64) -> u64 {
@@ -38,6 +225,45 @@ mod tests {
     use super::*;
     use std::convert::TryInto;
 
+    #[test]
+    fn test_retry_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_micros(1));
+        assert_eq!(policy.backoff(3), Duration::from_micros(8));
+        assert_eq!(policy.backoff(40), Duration::from_millis(1));
+        assert_eq!(RetryPolicy::forever().backoff(5), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_abort_kind() {
+        use crate::storage::row::TupleError;
+        use crate::Error;
+        let kind = AbortKind::from_error(&Error::Tuple(TupleError::TupleChanged { conflict_tid: 7 }));
+        assert_eq!(kind, AbortKind::WriteConflict { conflict_tid: 7 });
+        assert_eq!(AbortKind::from_error(&Error::Exist), AbortKind::Validation);
+        assert_eq!(AbortKind::from_error(&Error::Tuple(TupleError::Wounded)), AbortKind::LockTimeout);
+        assert_eq!(AbortKind::from_error(&Error::NoSpace), AbortKind::Internal);
+        assert_eq!(
+            AbortKind::from_error(&Error::Tuple(TupleError::TupleNotExists)),
+            AbortKind::Internal
+        );
+        assert!(AbortKind::SnapshotTooOld.is_conflict());
+        assert!(!AbortKind::Internal.is_conflict());
+        assert!(!AbortKind::User.is_conflict());
+    }
+
+    #[test]
+    fn test_tid_thread() {
+        use crate::mvcc_config::TRANSACTION_COUNT;
+        use snapshot::SnapShot;
+        assert!(TRANSACTION_COUNT <= 1 << THREAD_ID_BITS);
+        let snapshot = SnapShot::new();
+        let last = TRANSACTION_COUNT as u64 - 1;
+        let tid = snapshot.new_txn(last);
+        assert_eq!(SnapShot::thread_of(tid), last as usize);
+        assert!(snapshot.new_txn(0) >= tid & !THREAD_ID_MASK);
+    }
+
     #[test]
     fn enum_cast_example() {
         let s = TxStatus::Initial;
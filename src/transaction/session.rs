@@ -0,0 +1,237 @@
+//! Leasing of transaction slots.
+//!
+//! A thread id indexes the per-thread allocators, buffer-pool ranges and the
+//! timestamp slots of the catalog, so two threads must never use the same one
+//! at once. A `SessionPool` hands each caller a `TransactionBuffer` with an id
+//! of its own and takes it back when the `Session` is dropped, so workers of a
+//! thread pool can borrow one per task. The catalog keeps the ids held by a
+//! buffer in `ThreadIds`, so an id is never handed out twice.
+use std::ops::{Deref, DerefMut, Range};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::mvcc_config::TRANSACTION_COUNT;
+use crate::storage::catalog::Catalog;
+
+use super::transaction_buffer::TransactionBuffer;
+
+/// The thread ids a live `TransactionBuffer` holds.
+#[derive(Debug)]
+pub struct ThreadIds {
+    claimed: Vec<AtomicBool>,
+}
+
+impl ThreadIds {
+    pub fn new() -> Self {
+        ThreadIds {
+            claimed: std::iter::repeat_with(|| AtomicBool::new(false))
+                .take(TRANSACTION_COUNT)
+                .collect(),
+        }
+    }
+    /// Take `thread_id`, false if a buffer holds it already.
+    pub fn claim(&self, thread_id: u64) -> bool {
+        !self.claimed[thread_id as usize].swap(true, Ordering::SeqCst)
+    }
+    pub fn release(&self, thread_id: u64) {
+        self.claimed[thread_id as usize].store(false, Ordering::SeqCst);
+    }
+    pub fn is_claimed(&self, thread_id: u64) -> bool {
+        self.claimed[thread_id as usize].load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug)]
+struct Slots {
+    /// Buffers of returned sessions, reused before a new id is opened.
+    idle: Vec<TransactionBuffer>,
+    next: u64,
+}
+
+/// Thread ids in `ids` leased out as sessions, their buffers are made on
+/// first use and kept for the next lease. Ids another buffer holds then are
+/// skipped.
+#[derive(Debug)]
+pub struct SessionPool {
+    ids: Range<u64>,
+    slots: Mutex<Slots>,
+    returned: Condvar,
+}
+
+impl SessionPool {
+    /// A pool of every thread id not held elsewhere.
+    pub fn new() -> Self {
+        Self::with_ids(0..TRANSACTION_COUNT as u64)
+    }
+    pub fn with_ids(ids: Range<u64>) -> Self {
+        assert!(ids.end <= TRANSACTION_COUNT as u64);
+        SessionPool {
+            slots: Mutex::new(Slots {
+                idle: Vec::new(),
+                next: ids.start,
+            }),
+            ids,
+            returned: Condvar::new(),
+        }
+    }
+    /// Sessions that can be leased at most, fewer if ids were skipped.
+    pub fn capacity(&self) -> usize {
+        (self.ids.end - self.ids.start) as usize
+    }
+    fn take(&self, slots: &mut Slots) -> Option<TransactionBuffer> {
+        if let Some(buffer) = slots.idle.pop() {
+            return Some(buffer);
+        }
+        while slots.next < self.ids.end {
+            slots.next += 1;
+            if let Ok(buffer) = TransactionBuffer::try_new(Catalog::global(), slots.next - 1) {
+                return Some(buffer);
+            }
+        }
+        None
+    }
+    fn lease(&self, buffer: TransactionBuffer) -> Session {
+        Session {
+            pool: self,
+            buffer: Some(buffer),
+        }
+    }
+    /// A session, waiting for one to be returned while all are leased.
+    pub fn acquire(&self) -> Session {
+        let mut slots = self.slots.lock();
+        loop {
+            if let Some(buffer) = self.take(&mut slots) {
+                return self.lease(buffer);
+            }
+            self.returned.wait(&mut slots);
+        }
+    }
+    /// A session, None if all are leased.
+    pub fn try_acquire(&self) -> Option<Session> {
+        let mut slots = self.slots.lock();
+        self.take(&mut slots).map(|buffer| self.lease(buffer))
+    }
+    /// A session, None if none is returned within `timeout`.
+    pub fn acquire_timeout(&self, timeout: Duration) -> Option<Session> {
+        let deadline = Instant::now() + timeout;
+        let mut slots = self.slots.lock();
+        loop {
+            if let Some(buffer) = self.take(&mut slots) {
+                return Some(self.lease(buffer));
+            }
+            if self.returned.wait_until(&mut slots, deadline).timed_out() {
+                return self.take(&mut slots).map(|buffer| self.lease(buffer));
+            }
+        }
+    }
+    fn release(&self, buffer: TransactionBuffer) {
+        self.slots.lock().idle.push(buffer);
+        self.returned.notify_one();
+    }
+}
+
+impl Default for SessionPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A leased `TransactionBuffer`, back to its pool on drop.
+#[derive(Debug)]
+pub struct Session<'p> {
+    pool: &'p SessionPool,
+    buffer: Option<TransactionBuffer>,
+}
+
+impl<'p> Deref for Session<'p> {
+    type Target = TransactionBuffer;
+
+    fn deref(&self) -> &TransactionBuffer {
+        self.buffer.as_ref().unwrap()
+    }
+}
+
+impl<'p> DerefMut for Session<'p> {
+    fn deref_mut(&mut self) -> &mut TransactionBuffer {
+        self.buffer.as_mut().unwrap()
+    }
+}
+
+impl<'p> Drop for Session<'p> {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            self.pool.release(buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::nvm_file::NVMTableStorage;
+    use crate::transaction::testing::thread_ids;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_session_pool() {
+        NVMTableStorage::init_test_database();
+        Catalog::init_catalog();
+        let ids = thread_ids(2);
+        let pool = Arc::new(SessionPool::with_ids(ids.clone()));
+        assert_eq!(pool.capacity(), 2);
+
+        let s1 = pool.acquire();
+        let s2 = pool.try_acquire().unwrap();
+        assert_ne!(s1.get_thread_id(), s2.get_thread_id());
+        assert!(pool.try_acquire().is_none());
+        assert!(pool.acquire_timeout(Duration::from_millis(1)).is_none());
+        let id = s1.get_thread_id();
+        drop(s1);
+        assert_eq!(pool.try_acquire().unwrap().get_thread_id(), id);
+        drop(s2);
+
+        // more workers than ids, each holds its own id while it runs
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    let session = pool.acquire();
+                    std::thread::sleep(Duration::from_millis(1));
+                    session.get_thread_id()
+                })
+            })
+            .collect();
+        let workers: HashSet<u64> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert!(workers.is_subset(&ids.collect()));
+    }
+
+    #[test]
+    fn test_thread_id_reuse() {
+        NVMTableStorage::init_test_database();
+        Catalog::init_catalog();
+        let ids = thread_ids(3);
+        let direct = TransactionBuffer::new(Catalog::global(), ids.start);
+        assert!(matches!(
+            TransactionBuffer::try_new(Catalog::global(), ids.start),
+            Err(crate::Error::Exist)
+        ));
+
+        // the pools skip the ids held elsewhere, also by each other
+        let pool1 = SessionPool::with_ids(ids.clone());
+        let pool2 = SessionPool::with_ids(ids.clone());
+        let s1 = pool1.try_acquire().unwrap();
+        assert_eq!(s1.get_thread_id(), ids.start + 1);
+        let s2 = pool2.try_acquire().unwrap();
+        assert_eq!(s2.get_thread_id(), ids.start + 2);
+        assert!(pool1.try_acquire().is_none());
+        assert!(pool2.try_acquire().is_none());
+
+        // an id is free again once its buffer is dropped
+        drop(direct);
+        assert!(!Catalog::global().thread_ids().is_claimed(ids.start));
+        drop(TransactionBuffer::new(Catalog::global(), ids.start));
+    }
+}
@@ -1,8 +1,12 @@
+use crate::mvcc_config::THREAD_ID_BITS;
 use crate::storage::timestamp::TimeStamp;
-use crate::transaction::TxStatus;
+use crate::transaction::clog::FROZEN;
+use crate::Result;
 use crate::{storage::catalog::Catalog, transaction::clog::Clog};
 use std::sync::atomic::{AtomicU64, Ordering};
 const ABORT: u64 = 1;
+/// Tids and commit stamps are ticks of one counter over the thread id, the
+/// dense ticks `Clog` is indexed by.
 #[derive(Debug)]
 pub struct ClogMvccSnapShot {
     clock: AtomicU64,
@@ -13,19 +17,25 @@ impl ClogMvccSnapShot {
             clock: AtomicU64::new(1),
         }
     }
-    pub fn new_txn(&self, _: u64) -> u64 {
-        self.clock.fetch_add(1, Ordering::Relaxed)
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed) << THREAD_ID_BITS
+    }
+    pub fn new_txn(&self, thread_id: u64) -> u64 {
+        self.tick() | thread_id
         // 10
     }
+    /// Hand out tids from `new_clock` on only.
     pub fn reload_clock(&self, new_clock: u64) {
-        self.clock.store(new_clock, Ordering::Relaxed)
+        self.clock
+            .store((new_clock >> THREAD_ID_BITS) + 1, Ordering::Relaxed)
     }
-    pub fn finish_txn(&self, txn_id: TimeStamp, commited: bool) {
+    /// Save the commit stamp or the abort of `txn_id` to the clog.
+    pub fn finish_txn(&self, txn_id: TimeStamp, commited: bool) -> Result {
         let clog = Catalog::global().get_clog();
         if commited {
-            clog.save(txn_id, self.clock.fetch_add(1, Ordering::Relaxed));
+            clog.save(txn_id, self.tick())
         } else {
-            clog.save(txn_id, ABORT);
+            clog.save(txn_id, ABORT)
         }
     }
     pub fn is_finished(txn_id: TimeStamp, clog: &Clog) -> bool {
@@ -36,7 +46,7 @@ impl ClogMvccSnapShot {
         false
     }
     pub fn get_snapshot(&self, snapshot: &mut ClogMvccSnapShotEntity) {
-        snapshot.clock = self.clock.load(Ordering::SeqCst);
+        snapshot.clock = self.clock.load(Ordering::SeqCst) << THREAD_ID_BITS;
     }
 }
 
@@ -61,7 +71,7 @@ impl ClogMvccSnapShotEntity {
         if ts == 0 {
             return false;
         }
-        if ts == ABORT {
+        if ts == ABORT || ts == FROZEN {
             return true;
         }
         ts <= self.clock
@@ -1,10 +1,10 @@
-// #[cfg(feature = "cc_cfg_occ")]
-// pub mod clog_snapshot;
+pub mod clog_snapshot;
 // #[cfg(feature = "cc_cfg_occ")]
 // pub type SnapShot = clog_snapshot::ClogMvccSnapShot;
 // #[cfg(feature = "cc_cfg_occ")]
 // pub type SnapShotEntity = clog_snapshot::ClogMvccSnapShotEntity;
 
+pub mod oracle;
 // #[cfg(feature = "cc_cfg_to")]
 pub mod to_snapshot;
 // #[cfg(feature = "cc_cfg_to")]
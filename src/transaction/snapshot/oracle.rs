@@ -0,0 +1,282 @@
+//! Sources of the timestamps in tids.
+//!
+//! A tid is a tick of the oracle shifted over the thread id, see `ToSnapShot`.
+//! Ticks never go back, not even across a restart: the catalog keeps a
+//! high-water tick on NVM above every tick handed out, seeds the oracle with
+//! it when reloaded and later ticks are above it, whatever the wall clock or
+//! the TSC say.
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use chrono::prelude::*;
+
+use crate::mvcc_config::THREAD_ID_BITS;
+
+/// Ticks of a tid below `POW_2_63`.
+pub const MAX_TICK: u64 = (1 << (63 - THREAD_ID_BITS)) - 1;
+/// Nanoseconds or cycles per tick as a shift, 2^60 of them fill the ticks.
+const TICK_SHIFT: u64 = THREAD_ID_BITS.saturating_sub(3);
+/// 2020-01-01, physical ticks count from it.
+const EPOCH_NANOS: i64 = 1_577_836_800_000_000_000;
+
+pub trait TimestampOracle: std::fmt::Debug + Send + Sync {
+    /// A tick above the seed and above any returned before to the calling
+    /// thread, to other threads as well unless the oracle says otherwise.
+    fn now(&self) -> u64;
+    /// Return only ticks above `tick` from now on.
+    fn seed(&self, tick: u64);
+    /// Ticks `duration` spans, rounded up.
+    fn ticks(&self, duration: Duration) -> u64;
+}
+
+/// Physical ticks of `duration`.
+fn nanos_ticks(duration: Duration) -> u64 {
+    (duration.as_nanos() >> TICK_SHIFT).min(MAX_TICK as u128) as u64
+}
+
+/// The wall clock read by each thread on its own, no word is shared per
+/// tick. Ticks are only in order per thread and oracle: a thread may get a
+/// tick below one another thread got before if the wall clock steps back,
+/// so it is no default, only for benchmarks on a steady clock.
+#[derive(Debug, Default)]
+pub struct ClockOracle {
+    offset: AtomicU64,
+}
+
+thread_local! {
+    /// The `ClockOracle` the thread took a tick from last, and the tick.
+    static LAST_TICK: Cell<(usize, u64)> = Cell::new((0, 0));
+}
+
+impl TimestampOracle for ClockOracle {
+    fn now(&self) -> u64 {
+        let oracle = self as *const Self as usize;
+        let tick = HybridClock::physical() + self.offset.load(Ordering::Relaxed);
+        LAST_TICK.with(|last| {
+            let tick = match last.get() {
+                (owner, last) if owner == oracle => tick.max(last + 1),
+                _ => tick,
+            };
+            last.set((oracle, tick));
+            tick
+        })
+    }
+    fn seed(&self, tick: u64) {
+        let physical = HybridClock::physical();
+        if tick >= physical {
+            self.offset.fetch_max(tick + 1 - physical, Ordering::SeqCst);
+        }
+    }
+    fn ticks(&self, duration: Duration) -> u64 {
+        nanos_ticks(duration)
+    }
+}
+
+/// A shared counter, one tick per call.
+#[derive(Debug, Default)]
+pub struct AtomicOracle {
+    counter: AtomicU64,
+}
+
+impl TimestampOracle for AtomicOracle {
+    fn now(&self) -> u64 {
+        self.counter.fetch_add(1, Ordering::SeqCst) + 1
+    }
+    fn seed(&self, tick: u64) {
+        self.counter.fetch_max(tick, Ordering::SeqCst);
+    }
+    /// As many as nanoseconds, no call takes less than one.
+    fn ticks(&self, duration: Duration) -> u64 {
+        duration.as_nanos().min(MAX_TICK as u128) as u64
+    }
+}
+
+/// The invariant TSC, in step on every core, plus an offset keeping it past
+/// the seed after a reboot reset it.
+#[derive(Debug, Default)]
+pub struct TscOracle {
+    offset: AtomicU64,
+}
+
+impl TscOracle {
+    fn cycles() -> u64 {
+        unsafe { core::arch::x86_64::_rdtsc() >> TICK_SHIFT }
+    }
+    /// Ticks per millisecond, measured once.
+    fn ticks_per_milli() -> u64 {
+        static TICKS_PER_MILLI: AtomicU64 = AtomicU64::new(0);
+        match TICKS_PER_MILLI.load(Ordering::Relaxed) {
+            0 => {
+                let start = (Instant::now(), Self::cycles());
+                std::thread::sleep(Duration::from_millis(10));
+                let ticks = (Self::cycles() - start.1) as u128 * 1_000_000
+                    / start.0.elapsed().as_nanos().max(1);
+                let ticks = (ticks as u64).max(1);
+                TICKS_PER_MILLI.store(ticks, Ordering::Relaxed);
+                ticks
+            }
+            ticks => ticks,
+        }
+    }
+}
+
+impl TimestampOracle for TscOracle {
+    fn now(&self) -> u64 {
+        Self::cycles() + self.offset.load(Ordering::SeqCst)
+    }
+    fn seed(&self, tick: u64) {
+        let cycles = Self::cycles();
+        if tick >= cycles {
+            self.offset.fetch_max(tick + 1 - cycles, Ordering::SeqCst);
+        }
+    }
+    fn ticks(&self, duration: Duration) -> u64 {
+        let ticks = duration.as_micros() * Self::ticks_per_milli() as u128 / 1000 + 1;
+        ticks.min(MAX_TICK as u128) as u64
+    }
+}
+
+/// A hybrid logical clock: the wall clock while it moves forward, counting on
+/// from the last tick while it stands still or steps back. Ticks are in order
+/// across threads, at the price of one atomic update of a shared word per
+/// tick, never a retried CAS.
+#[derive(Debug, Default)]
+pub struct HybridClock {
+    last: AtomicU64,
+}
+
+impl HybridClock {
+    fn physical() -> u64 {
+        (Utc::now().timestamp_nanos() - EPOCH_NANOS).max(0) as u64 >> TICK_SHIFT
+    }
+}
+
+impl HybridClock {
+    /// The tick at wall clock `physical`: the clock if it moved past the last
+    /// tick, the one after the last otherwise. Either way the tick is the
+    /// value `last` was raised to, so ticks follow the order of the updates.
+    fn tick_at(&self, physical: u64) -> u64 {
+        let last = self.last.fetch_max(physical, Ordering::SeqCst);
+        if last < physical {
+            physical
+        } else {
+            self.last.fetch_add(1, Ordering::SeqCst) + 1
+        }
+    }
+}
+
+impl TimestampOracle for HybridClock {
+    fn now(&self) -> u64 {
+        self.tick_at(Self::physical())
+    }
+    fn seed(&self, tick: u64) {
+        self.last.fetch_max(tick, Ordering::SeqCst);
+    }
+    fn ticks(&self, duration: Duration) -> u64 {
+        nanos_ticks(duration)
+    }
+}
+
+/// The oracles a database can be opened with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OracleKind {
+    Clock,
+    Atomic,
+    Tsc,
+    Hybrid,
+}
+
+impl Default for OracleKind {
+    /// The hybrid clock, the real time in order across threads whatever the
+    /// wall clock does.
+    fn default() -> Self {
+        OracleKind::Hybrid
+    }
+}
+
+impl OracleKind {
+    pub const ALL: [OracleKind; 4] = [
+        OracleKind::Clock,
+        OracleKind::Atomic,
+        OracleKind::Tsc,
+        OracleKind::Hybrid,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OracleKind::Clock => "clock",
+            OracleKind::Atomic => "atomic",
+            OracleKind::Tsc => "tsc",
+            OracleKind::Hybrid => "hlc",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+    /// The oracle named by the `N2DB_TIMESTAMP_ORACLE` environment variable.
+    pub fn from_env() -> Self {
+        std::env::var("N2DB_TIMESTAMP_ORACLE")
+            .ok()
+            .and_then(|name| Self::from_name(&name))
+            .unwrap_or_default()
+    }
+    pub fn build(self) -> Box<dyn TimestampOracle> {
+        match self {
+            OracleKind::Clock => Box::new(ClockOracle::default()),
+            OracleKind::Atomic => Box::new(AtomicOracle::default()),
+            OracleKind::Tsc => Box::new(TscOracle::default()),
+            OracleKind::Hybrid => Box::new(HybridClock::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oracle_seed() {
+        for kind in OracleKind::ALL.iter() {
+            assert_eq!(OracleKind::from_name(kind.name()), Some(*kind));
+            let oracle = kind.build();
+            let first = oracle.now();
+            assert!(oracle.now() >= first, "{}", kind.name());
+            // a restart after ticks far ahead of the clock
+            let seed = first + (1 << 40);
+            oracle.seed(seed);
+            let tick = oracle.now();
+            assert!(tick > seed && tick <= MAX_TICK, "{}", kind.name());
+            assert!(oracle.now() >= tick, "{}", kind.name());
+        }
+        assert_eq!(OracleKind::from_name("wall"), None);
+    }
+
+    #[test]
+    fn test_hybrid_step_back() {
+        let oracle = HybridClock::default();
+        let physical = HybridClock::physical();
+        let (committed, later) = std::thread::scope(|scope| {
+            let committed = scope.spawn(|| oracle.tick_at(physical + 1000)).join().unwrap();
+            // the wall clock stepped back meanwhile
+            let later = scope.spawn(|| oracle.tick_at(physical)).join().unwrap();
+            (committed, later)
+        });
+        assert!(later > committed);
+        assert!(oracle.now() > later);
+    }
+
+    #[test]
+    fn test_oracle_ticks() {
+        for kind in OracleKind::ALL.iter() {
+            let oracle = kind.build();
+            let second = oracle.ticks(Duration::from_secs(1));
+            assert!(second > 0, "{}", kind.name());
+            assert!(oracle.ticks(Duration::from_secs(2)) >= second, "{}", kind.name());
+            assert_eq!(oracle.ticks(Duration::MAX), MAX_TICK, "{}", kind.name());
+        }
+    }
+}
@@ -1,29 +1,93 @@
-use crate::config::POW_2_63;
+use crate::config::{Address, POW_2_63};
+use crate::mvcc_config::THREAD_ID_BITS;
 use crate::storage::row::TupleError;
 use crate::storage::timestamp::TimeStamp;
 use crate::transaction::TxStatus;
+use crate::utils::{file, io};
 use crate::{storage::catalog::Catalog, transaction::clog::Clog};
-use chrono::prelude::*;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use super::oracle::{OracleKind, TimestampOracle, MAX_TICK};
 
 const ABORT: u64 = 1;
+/// Ticks the high-water mark is raised ahead of the oracle, one flush each.
+const TICK_WINDOW: u64 = 1 << 20;
+
+/// Tids are ticks of the oracle over the thread id.
 #[derive(Debug)]
 pub struct ToSnapShot {
-    clock: AtomicU64,
+    oracle: Box<dyn TimestampOracle>,
+    /// No tick above it is handed out before the mark on NVM is raised,
+    /// `u64::MAX` while there is no mark.
+    high_water: AtomicU64,
+    high_water_address: AtomicU64,
 }
 // use real clock instead of share counter for better scalibility
 impl ToSnapShot {
+    /// Ticks from the oracle named by `N2DB_TIMESTAMP_ORACLE`, see `OracleKind`.
     pub fn new() -> Self {
+        Self::with_oracle(OracleKind::from_env().build())
+    }
+    pub fn with_oracle(oracle: Box<dyn TimestampOracle>) -> Self {
         ToSnapShot {
-            clock: AtomicU64::new(2),
+            oracle,
+            high_water: AtomicU64::new(u64::MAX),
+            high_water_address: AtomicU64::new(0),
+        }
+    }
+    /// Keep the high-water tick in the word at `address`, which a reloaded
+    /// database seeds the oracle with, so that ticks never go back across a
+    /// restart. `reload` to keep the mark found there.
+    pub fn persist_ticks(&self, address: Address, reload: bool) {
+        let mark = if reload {
+            unsafe { io::read::<u64>(address) }
+        } else {
+            0
+        };
+        self.oracle.seed(mark);
+        unsafe {
+            io::write(address, mark);
+            io::clwb(address as *const u8);
+        }
+        file::sfence();
+        self.high_water_address.store(address, Ordering::SeqCst);
+        self.high_water.store(mark, Ordering::SeqCst);
+    }
+    fn now(&self) -> u64 {
+        let tick = self.oracle.now();
+        if tick > self.high_water.load(Ordering::Relaxed) {
+            self.raise_high_water(tick);
         }
+        tick << THREAD_ID_BITS
+    }
+    #[cold]
+    fn raise_high_water(&self, tick: u64) {
+        let address = self.high_water_address.load(Ordering::SeqCst);
+        let mark = (tick + TICK_WINDOW).min(MAX_TICK);
+        let persisted = unsafe { &*(address as *const AtomicU64) };
+        persisted.fetch_max(mark, Ordering::SeqCst);
+        // flushed by whoever gets here, another thread may have raised it
+        // without its flush done yet
+        unsafe {
+            io::clwb(address as *const u8);
+        }
+        file::sfence();
+        self.high_water.fetch_max(mark, Ordering::SeqCst);
+    }
+    /// The span of `duration` on the clock of the snapshots.
+    pub fn span(&self, duration: Duration) -> u64 {
+        self.oracle.ticks(duration) << THREAD_ID_BITS
     }
     pub fn new_txn(&self, thread_id: u64) -> u64 {
-        // self.clock.fetch_add(1, Ordering::Relaxed)
-        ((Local::now().timestamp_nanos() as u64) << 6) | thread_id
+        self.now() | thread_id
     }
+    pub fn thread_of(tid: u64) -> usize {
+        (tid & ((1 << THREAD_ID_BITS) - 1)) as usize
+    }
+    /// Hand out tids from `new_clock` on only.
     pub fn reload_clock(&self, new_clock: u64) {
-        self.clock.store(new_clock, Ordering::Relaxed)
+        self.oracle.seed(new_clock >> THREAD_ID_BITS)
     }
 
     pub fn finish_txn(&self, _: TimeStamp, _: bool) {
@@ -37,7 +101,7 @@ impl ToSnapShot {
         false
     }
     pub fn get_snapshot(&self, snapshot: &mut ToSnapShotEntity) {
-        snapshot.clock = (Local::now().timestamp_nanos() as u64) << 6;
+        snapshot.clock = self.now();
         // snapshot.clock =self.clock.load(Ordering::Relaxed);
     }
 }
@@ -57,3 +121,25 @@ impl ToSnapShotEntity {
         tuple_ts.tid <= self.clock
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::nvm_file::NVMTableStorage;
+
+    #[test]
+    fn test_high_water() {
+        NVMTableStorage::init_test_database();
+        let address = NVMTableStorage::global_mut()
+            .alloc_page()
+            .unwrap()
+            .page_start;
+        let snapshot = ToSnapShot::with_oracle(OracleKind::Atomic.build());
+        snapshot.persist_ticks(address, false);
+        let tid = snapshot.new_txn(1);
+        // a restart that lost the tids of the buffers
+        let restarted = ToSnapShot::with_oracle(OracleKind::Atomic.build());
+        restarted.persist_ticks(address, true);
+        assert!(restarted.new_txn(0) > tid);
+    }
+}
@@ -0,0 +1,125 @@
+//! Serializable snapshot isolation.
+//!
+//! An SSI transaction reads its snapshot like snapshot isolation and aborts
+//! only when it is the pivot of a dangerous structure, with both an incoming
+//! and an outgoing rw-antidependency to concurrent transactions.
+//!
+//! A reader finds its outgoing edges itself, at read time when the latest
+//! version is too new for its snapshot and at commit when a tuple it read
+//! got a newer version or is locked by a writer. At commit it also leaves
+//! its commit timestamp in the read-ts word of the tuples it read, where a
+//! writer committing later finds its incoming edge.
+use dashmap::DashMap;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+const IN_CONFLICT: u8 = 1;
+const OUT_CONFLICT: u8 = 2;
+/// Finished transactions between two sweeps of the committed ones.
+const GC_INTERVAL: u64 = 256;
+
+#[derive(Debug, Default)]
+struct SsiTxn {
+    flags: AtomicU8,
+    /// Commit timestamp, 0 while running.
+    commit: AtomicU64,
+}
+
+/// Conflict flags of the SSI transactions by transaction id. A committed
+/// transaction is kept while a transaction that began before it commits runs.
+#[derive(Debug, Default)]
+pub struct Ssi {
+    txns: DashMap<u64, SsiTxn>,
+    finished: AtomicU64,
+}
+
+impl Ssi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn begin(&self, txn: u64) {
+        self.txns.insert(txn, SsiTxn::default());
+    }
+    fn flag(&self, txn: u64, flag: u8) -> u8 {
+        match self.txns.get(&txn) {
+            Some(t) => t.flags.fetch_or(flag, Ordering::SeqCst) | flag,
+            None => 0,
+        }
+    }
+    /// Record `reader` -rw-> `writer`, the reader missed a version of the
+    /// writer. False if the reader has to abort since the writer has an
+    /// outgoing edge too and may have committed as a pivot.
+    pub fn read_over(&self, reader: u64, writer: u64) -> bool {
+        if reader == writer {
+            return true;
+        }
+        self.flag(reader, OUT_CONFLICT);
+        self.flag(writer, IN_CONFLICT) & OUT_CONFLICT == 0
+    }
+    /// Record an edge into `writer` from a reader that committed after the
+    /// writer began.
+    pub fn written_over(&self, writer: u64) {
+        self.flag(writer, IN_CONFLICT);
+    }
+    pub fn is_pivot(&self, txn: u64) -> bool {
+        self.flag(txn, 0) == IN_CONFLICT | OUT_CONFLICT
+    }
+    pub fn commit(&self, txn: u64, ts: u64) {
+        if let Some(t) = self.txns.get(&txn) {
+            t.commit.store(ts, Ordering::SeqCst);
+        }
+        self.gc();
+    }
+    pub fn abort(&self, txn: u64) {
+        self.txns.remove(&txn);
+        self.gc();
+    }
+    fn gc(&self) {
+        if self.finished.fetch_add(1, Ordering::Relaxed) % GC_INTERVAL != GC_INTERVAL - 1 {
+            return;
+        }
+        let oldest = self
+            .txns
+            .iter()
+            .filter(|t| t.commit.load(Ordering::SeqCst) == 0)
+            .map(|t| *t.key())
+            .min()
+            .unwrap_or(u64::MAX);
+        self.txns.retain(|_, t| {
+            let commit = t.commit.load(Ordering::SeqCst);
+            commit == 0 || commit > oldest
+        });
+    }
+    pub fn len(&self) -> usize {
+        self.txns.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ssi_pivot() {
+        let ssi = Ssi::new();
+        for txn in 1..=3 {
+            ssi.begin(txn);
+        }
+        // 1 -rw-> 2 -rw-> 3, 2 is the pivot
+        assert!(ssi.read_over(2, 3));
+        assert!(!ssi.is_pivot(2));
+        assert!(!ssi.read_over(1, 2));
+        assert!(ssi.is_pivot(2));
+        assert!(!ssi.is_pivot(1));
+        assert!(!ssi.is_pivot(3));
+        assert!(ssi.read_over(3, 3));
+
+        ssi.abort(2);
+        ssi.commit(1, 10);
+        ssi.commit(3, 11);
+        for txn in 4..GC_INTERVAL + 4 {
+            ssi.begin(txn);
+            ssi.commit(txn, txn + 10);
+        }
+        assert_eq!(ssi.len(), 0);
+    }
+}
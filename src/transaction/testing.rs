@@ -0,0 +1,108 @@
+//! Fixture of the transaction tests: a two column table on the global
+//! catalog and buffers on thread ids no other test uses.
+use crate::mvcc_config::{thread_count, TRANSACTION_COUNT};
+use crate::storage::catalog::Catalog;
+use crate::storage::nvm_file::NVMTableStorage;
+use crate::storage::schema::{ColumnType, TableSchema};
+use crate::storage::table::Table;
+use crate::transaction::cc::{ConcurrencyControl, LockPolicy};
+use crate::transaction::transaction_buffer::TransactionBuffer;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Ids handed out so far, above the `0..thread_count()` left to the
+/// benchmark tests.
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    /// Held for reading by every fixture and for writing by the tests that
+    /// switch the concurrency control of the global catalog.
+    static ref CC_LOCK: RwLock<()> = RwLock::new(());
+}
+
+/// A thread id no other test uses.
+pub fn thread_id() -> u64 {
+    thread_ids(1).start
+}
+
+/// `count` consecutive thread ids no other test uses.
+pub fn thread_ids(count: u64) -> Range<u64> {
+    let start = thread_count() as u64 + NEXT_THREAD_ID.fetch_add(count, Ordering::Relaxed);
+    assert!(start + count <= TRANSACTION_COUNT as u64, "out of test thread ids");
+    start..start + count
+}
+
+enum CcGuard {
+    Shared(RwLockReadGuard<'static, ()>),
+    Exclusive(RwLockWriteGuard<'static, ()>),
+}
+
+pub struct Fixture {
+    pub catalog: &'static Catalog,
+    /// Table `name` with Int64 columns "a" and "b", keyed by "a".
+    pub table: Arc<Table>,
+    name: String,
+    guard: CcGuard,
+}
+
+impl Fixture {
+    /// Creates table `name`, which must be unique among the tests, running
+    /// under the default protocol.
+    pub fn new(name: &str) -> Self {
+        let guard = CC_LOCK.read().unwrap_or_else(|e| e.into_inner());
+        Fixture::with_guard(name, CcGuard::Shared(guard))
+    }
+    /// `new` running under `cc`, no other fixture runs meanwhile. The lock
+    /// policy may be changed too, both are reset on drop.
+    pub fn with_cc(name: &str, cc: ConcurrencyControl) -> Self {
+        let guard = CC_LOCK.write().unwrap_or_else(|e| e.into_inner());
+        let fixture = Fixture::with_guard(name, CcGuard::Exclusive(guard));
+        fixture.set_cc(cc);
+        fixture
+    }
+    fn with_guard(name: &str, guard: CcGuard) -> Self {
+        NVMTableStorage::init_test_database();
+        Catalog::init_catalog();
+        let catalog = Catalog::global();
+        let mut schema = TableSchema::new();
+        schema.push(ColumnType::Int64, "a");
+        schema.push(ColumnType::Int64, "b");
+        catalog.add_table(name, schema).unwrap();
+        catalog.set_primary_key(name, &[0]);
+        Fixture {
+            catalog,
+            table: catalog.get_table(name),
+            name: name.to_string(),
+            guard,
+        }
+    }
+    /// Keys the table by "a" on a range index instead.
+    pub fn range_key(mut self) -> Self {
+        self.catalog.set_range_primary_key(&self.name, &[0]);
+        self.table = self.catalog.get_table(&self.name);
+        self
+    }
+    /// A buffer on a fresh thread id.
+    pub fn buffer(&self) -> TransactionBuffer {
+        TransactionBuffer::new(self.catalog, thread_id())
+    }
+    /// Runs the transactions begun from now on under `cc`, only for a
+    /// fixture made by `with_cc`.
+    pub fn set_cc(&self, cc: ConcurrencyControl) {
+        assert!(
+            matches!(self.guard, CcGuard::Exclusive(_)),
+            "switching cc needs Fixture::with_cc"
+        );
+        self.catalog.set_concurrency_control(cc);
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        if let CcGuard::Exclusive(_) = self.guard {
+            self.catalog.set_concurrency_control(ConcurrencyControl::default());
+            self.catalog.lock_waits().set_policy(LockPolicy::default());
+        }
+    }
+}
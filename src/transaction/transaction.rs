@@ -1,4 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::Bound;
+use std::sync::atomic::{fence, Ordering};
 
 use crate::config::Address;
 use crate::config::POW_2_63;
@@ -8,19 +10,45 @@ use crate::storage::delta::TupleDelta;
 use crate::storage::global::*;
 use crate::storage::row::Tuple;
 use crate::storage::row::*;
-use crate::storage::table::{Table, TupleId};
+use crate::storage::table::{IndexType, RangeCursor, Table, TupleId};
 use crate::storage::timestamp::TimeStamp;
-use crate::transaction::access::{AccessStruct, WriteSetStruct, DELETE_COLUMN_FLAG};
+use crate::transaction::access::{AccessStruct, ScanSetStruct, WriteSetStruct, DELETE_COLUMN_FLAG};
+use crate::transaction::cc::{ConcurrencyControl, LockStats};
 use crate::transaction::clog::*;
 use crate::transaction::snapshot::SnapShotEntity;
 use crate::transaction::transaction_buffer::*;
 use crate::transaction::*;
 use crate::utils::executor::mem;
 use crate::utils::file;
-use crate::Result;
+use crate::{Error, Result};
 
 use super::access::FlushCache;
 
+/// A range scan of a transaction, advanced with `Transaction::next_in` so
+/// that the transaction may read and write between its rows.
+pub struct ScanCursor<'a> {
+    cursor: RangeCursor<'a>,
+    lower: Bound<IndexType>,
+    upper: Bound<IndexType>,
+    descending: bool,
+    /// Entry of the scan set of a transaction that validates its scans.
+    scan: Option<usize>,
+}
+
+impl<'a> ScanCursor<'a> {
+    /// Scan from the high end of the range.
+    pub fn descending(mut self) -> Self {
+        self.cursor = self.cursor.descending();
+        self.descending = true;
+        self
+    }
+    /// Stop after `count` rows.
+    pub fn limit(mut self, count: usize) -> Self {
+        self.cursor = self.cursor.limit(count);
+        self
+    }
+}
+
 #[derive(Debug)]
 pub struct Transaction<'a> {
     pub ts: TimeStamp,
@@ -29,8 +57,15 @@ pub struct Transaction<'a> {
     pub txn_buffer: &'a mut TransactionBuffer,
     pub snapshot: SnapShotEntity,
     read_set: Vec<AccessStruct<'a>>,
+    scan_set: Vec<ScanSetStruct<'a>>,
     write_set: Vec<WriteSetStruct<'a>>,
+    /// Set before `begin`: the transaction reads a pinned snapshot through
+    /// the version chains (with `mvcc`), without a read set, validation or
+    /// read locks, and commits without aborting.
     pub read_only: bool,
+    isolation: IsolationLevel,
+    cc: ConcurrencyControl,
+    lock_stats: LockStats,
     thread_id: usize,
     cur_min_txn: u64,
     #[cfg(feature = "hot_unflush")]
@@ -55,8 +90,12 @@ impl<'a> Transaction<'a> {
             txn_buffer: buffer,
             snapshot: SnapShotEntity::new(),
             read_set: Vec::new(),
+            scan_set: Vec::new(),
             write_set: Vec::new(),
             read_only,
+            isolation: IsolationLevel::default(),
+            cc: Catalog::global().concurrency_control(),
+            lock_stats: LockStats::default(),
             cur_min_txn: 1,
             #[cfg(feature = "hot_unflush")]
             flush_cache: FlushCache::new(),
@@ -66,24 +105,87 @@ impl<'a> Transaction<'a> {
     }
 
     pub fn begin(&mut self) {
-        let snapshot = Catalog::global().get_snapshot();
+        // the default levels run under every protocol
+        self.begin_with(IsolationLevel::default()).unwrap();
+    }
+    /// Begin at `isolation`, `begin` takes the level of the `serializable`
+    /// feature. Serializable snapshot isolation needs OCC without the buffer
+    /// pool, `Error::IsolationNotSupported` otherwise.
+    pub fn begin_with(&mut self, isolation: IsolationLevel) -> Result {
+        if isolation == IsolationLevel::SerializableSnapshot
+            && (self.cc != ConcurrencyControl::Occ || cfg!(feature = "buffer_pool"))
+        {
+            return Err(Error::IsolationNotSupported);
+        }
+        self.isolation = isolation;
+        let catalog = Catalog::global();
+        let snapshot = catalog.get_snapshot();
         snapshot.get_snapshot(&mut self.snapshot);
+        if self.read_only {
+            // pinned before it moves back so that nothing it reads is
+            // collected meanwhile
+            catalog.pin_snapshot(self.thread_id, self.snapshot.clock);
+            self.snapshot.clock = catalog.stable_snapshot(self.snapshot.clock);
+            catalog.pin_snapshot(self.thread_id, self.snapshot.clock);
+        } else {
+            // a bound below the tid taken after it, see `stable_snapshot`
+            catalog.set_active(self.thread_id, self.snapshot.clock);
+        }
         self.ts.tid = snapshot.new_txn(self.thread_id as u64);
-        #[cfg(feature = "read_ts")]
-        {
-            self.snapshot.clock = self.ts.tid;
+        if !self.read_only {
+            catalog.set_active(self.thread_id, self.ts.tid);
+            if self.cc.orders_by_tid() {
+                self.snapshot.clock = self.ts.tid;
+            }
+        }
+        if self.is_ssi() {
+            Catalog::global().get_ssi().begin(self.ts.tid);
         }
         // #[cfg(feature = "cc_cfg_occ")]
         // self.clog.save(self.ts, 0);
 
-        // if self.read_only {
-        //     self.cur_min_txn = Catalog::get_min_txn();
-        //     self.ts.tid = self.cur_min_txn;
-        // }
         if self.ts.tid - self.cur_min_txn > 2000 {
             self.cur_min_txn = Catalog::get_min_txn();
         }
-        self.txn_buffer.begin();
+        self.txn_buffer.begin(self.ts.tid);
+        Ok(())
+    }
+
+    pub fn isolation(&self) -> IsolationLevel {
+        self.isolation
+    }
+    /// Lock conflicts and waits of the last finished transaction under 2PL.
+    pub fn lock_stats(&self) -> LockStats {
+        self.lock_stats
+    }
+    /// Whether the read set is validated at commit.
+    fn validates_reads(&self) -> bool {
+        !self.read_only && self.isolation == IsolationLevel::Serializable
+    }
+    /// Whether reads go to the read set, for validation or for SSI to find
+    /// the writers of the tuples it read.
+    fn tracks_reads(&self) -> bool {
+        self.validates_reads() || self.is_ssi()
+    }
+    fn is_ssi(&self) -> bool {
+        !self.read_only && self.isolation == IsolationLevel::SerializableSnapshot
+    }
+    /// Under read committed every read sees what committed before it, a
+    /// read-only transaction keeps the snapshot it pinned.
+    fn refresh_snapshot(&mut self) {
+        if !self.read_only && self.isolation == IsolationLevel::ReadCommitted {
+            let snapshot = Catalog::global().get_snapshot();
+            snapshot.get_snapshot(&mut self.snapshot);
+        }
+    }
+    /// Checked after following a version chain: the delta read may be in a
+    /// region reused since, if the snapshot fell behind the gc horizon.
+    #[cfg(feature = "mvcc")]
+    fn check_snapshot_age(&self) -> Result {
+        if Catalog::global().get_gc().too_old(self.snapshot.clock) {
+            return Err(TupleError::SnapshotTooOld.into());
+        }
+        Ok(())
     }
 
     pub fn finish(&mut self, commited: bool) {
@@ -95,17 +197,31 @@ impl<'a> Transaction<'a> {
         // println!("== {}, commit = {}", self.ts.tid, commited);
 
         Catalog::update_ts(self.thread_id as u64, self.ts.tid);
+        Catalog::global().clear_active(self.thread_id);
+        if self.cc.is_locking() {
+            self.lock_stats = Catalog::global().lock_waits().take_stats(self.thread_id);
+        }
         for access in &self.read_set {
-            access.finish(self.thread_id, self.cur_min_txn);
+            access.finish(self.cc, self.thread_id, self.cur_min_txn);
         }
         for access in &self.write_set {
-            access.finish(self.thread_id, self.cur_min_txn);
+            access.finish(self.cc, self.thread_id, self.cur_min_txn);
+        }
+        if self.is_ssi() {
+            let ssi = Catalog::global().get_ssi();
+            if commited {
+                let snapshot = Catalog::global().get_snapshot();
+                ssi.commit(self.ts.tid, snapshot.new_txn(self.thread_id as u64));
+            } else {
+                ssi.abort(self.ts.tid);
+            }
         }
         self.write_set.clear();
         self.read_set.clear();
+        self.scan_set.clear();
     }
 
-    pub fn validation(&mut self) -> bool {
+    pub fn validation(&mut self) -> std::result::Result<(), AbortReason> {
         for access in &self.read_set {
             #[cfg(not(feature = "buffer_pool"))]
             let tuple = access.table.get_tuple(&access.tuple_id);
@@ -123,20 +239,110 @@ impl<'a> Transaction<'a> {
                 .0
                 .data
                 .read();
+            let failed = || AbortReason::on(AbortKind::Validation, access.table.id, access.tuple_id.get_address());
             if !access.valid(&tuple.ts()) {
-                return false;
+                return Err(failed());
             }
             let mut ts = TimeStamp::default();
             ts.tid = tuple.lock_tid();
 
             if !self.snapshot.access(ts, self.ts.tid, self.cur_min_txn) {
-                return false;
+                return Err(failed());
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail if a concurrent insert took over a unique key of one of ours.
+    /// Index inserts are not isolated, so every isolation level and
+    /// protocol checks this before commit.
+    fn validate_inserts(&self) -> std::result::Result<(), AbortReason> {
+        for ws in &self.write_set {
+            if ws.is_insert && !ws.table.owns_unique_keys(&ws.tuple_id, &ws.table.get_tuple(&ws.tuple_id)) {
+                return Err(AbortReason::on(
+                    AbortKind::Validation,
+                    ws.table.id,
+                    ws.tuple_id.get_address(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rescan the ranges the transaction scanned, failing on a phantom, a
+    /// tuple entering or leaving a range since. Under SSI a phantom is a
+    /// rw-antidependency to its writer instead.
+    fn validate_scans(&self) -> std::result::Result<(), AbortReason> {
+        let inserted: HashSet<u64> = self
+            .write_set
+            .iter()
+            .filter(|ws| ws.is_insert)
+            .map(|ws| ws.tuple_id.get_address())
+            .collect();
+        for scan in &self.scan_set {
+            let phantoms = match scan.phantoms(&inserted) {
+                Ok(phantoms) => phantoms,
+                Err(_) => return Err(AbortReason::on_table(AbortKind::Validation, scan.table.id)),
+            };
+            for tuple_id in phantoms {
+                let failed = || AbortReason::on(AbortKind::Validation, scan.table.id, tuple_id.get_address());
+                if !self.is_ssi() {
+                    return Err(failed());
+                }
+                let tuple = scan.table.get_tuple(&tuple_id);
+                let writer = match tuple.lock_tid() {
+                    0 => tuple.ts().tid,
+                    lock_tid => lock_tid,
+                };
+                if !Catalog::global().get_ssi().read_over(self.ts.tid, writer) {
+                    return Err(failed());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the rw-antidependencies of an SSI transaction once its writes
+    /// are locked, failing if it is the pivot of a dangerous structure.
+    fn ssi_validation(&mut self) -> std::result::Result<(), AbortReason> {
+        let ssi = Catalog::global().get_ssi();
+        // readers that committed after this transaction began
+        for ws in &self.write_set {
+            if ws.is_insert {
+                continue;
+            }
+            let tuple = ws.table.get_tuple(&ws.tuple_id);
+            fence(Ordering::SeqCst);
+            if tuple.ts().read_ts > self.ts.tid {
+                ssi.written_over(self.ts.tid);
+            }
+        }
+        // writers of the tuples it read, committed or about to
+        let stamp = Catalog::global()
+            .get_snapshot()
+            .new_txn(self.thread_id as u64);
+        for access in &self.read_set {
+            let tuple = access.table.get_tuple(&access.tuple_id);
+            tuple.set_read_ts(stamp);
+            fence(Ordering::SeqCst);
+            let failed = || AbortReason::on(AbortKind::Validation, access.table.id, access.tuple_id.get_address());
+            let lock_tid = tuple.lock_tid();
+            if lock_tid != 0 && !ssi.read_over(self.ts.tid, lock_tid) {
+                return Err(failed());
+            }
+            let ts = tuple.ts();
+            if !access.valid(&ts) && !ssi.read_over(self.ts.tid, ts.tid) {
+                return Err(failed());
             }
         }
-        true
+        if ssi.is_pivot(self.ts.tid) {
+            return Err(AbortReason::new(AbortKind::Validation));
+        }
+        Ok(())
     }
 
-    pub fn commit(&mut self) -> bool {
+    /// Commit, or abort and tell why.
+    pub fn commit(&mut self) -> std::result::Result<CommitInfo, AbortReason> {
         // println!("111");
 
         #[cfg(feature = "clock")]
@@ -149,10 +355,9 @@ impl<'a> Transaction<'a> {
             #[cfg(feature = "ilog")]
             let column_id = ws.column_id;
 
-            #[cfg(feature = "local_cc_cfg_2pl")]
+            // read locks the transaction holds on the tuple, upgraded below
             let mut counter = 0;
-            #[cfg(feature = "local_cc_cfg_2pl")]
-            if !ws.is_insert {
+            if self.cc.is_locking() && !ws.is_insert {
                 for access_item in &self.read_set {
                     // println!("read_tuple_id0: {}, update_tuple_id: {}",  access_item.tuple_id.get_address(), ws.tuple_id.get_address());
                     #[cfg(feature = "buffer_pool")]
@@ -184,9 +389,10 @@ impl<'a> Transaction<'a> {
             #[cfg(feature = "clock")]
             self.timer.start(UPDATING);
 
-            if !ws.is_insert
-                && ws
-                    .table
+            let updated = if ws.is_insert {
+                Ok(())
+            } else {
+                ws.table
                     .update_tuple_ts(
                         &ws.tuple_id,
                         &self.snapshot,
@@ -203,14 +409,16 @@ impl<'a> Transaction<'a> {
                         ws.data.as_slice(),
                         #[cfg(feature = "ilog")]
                         &mut self.txn_buffer,
-                        #[cfg(feature = "local_cc_cfg_2pl")]
+                        self.cc,
                         counter,
                     )
-                    .is_err()
-            {
+                    .map(|_| ())
+            };
+            if let Err(e) = updated {
                 // println!("222");
-                self.abort();
-                return false;
+                let reason = AbortReason::on(AbortKind::from_error(&e), ws.table.id, ws.tuple_id.get_address());
+                self.rollback();
+                return Err(reason);
             } else {
                 #[cfg(feature = "clock")]
                 {
@@ -219,8 +427,8 @@ impl<'a> Transaction<'a> {
                 }
             }
         }
-        #[cfg(all(not(feature = "cc_cfg_occ"), feature = "update_direct"))]
-        {
+        #[cfg(feature = "update_direct")]
+        if self.cc != ConcurrencyControl::Occ {
             for ws in &self.write_set {
                 let tuple = ws.table.get_tuple(&ws.tuple_id);
                 tuple.set_lock_tid(0);
@@ -228,14 +436,26 @@ impl<'a> Transaction<'a> {
             }
         }
 
-        #[cfg(feature = "serializable")]
-        {
-            #[cfg(feature = "cc_cfg_occ")]
-            if !self.read_only && !self.validation() {
-                self.abort();
-                return false;
+        let mut validated = self.validate_inserts();
+        if validated.is_ok() && self.cc.validates() && self.validates_reads() {
+            validated = self.validation();
+        }
+        if validated.is_ok() && self.tracks_reads() {
+            validated = self.validate_scans();
+        }
+        if validated.is_ok() && self.is_ssi() {
+            validated = self.ssi_validation();
+        }
+        // a wounded holder may have no lock left to request
+        if validated.is_ok() && self.cc.is_locking() {
+            if let Err(e) = Catalog::global().lock_waits().check_wound(self.ts.tid) {
+                validated = Err(AbortReason::new(AbortKind::from_error(&e)));
             }
         }
+        if let Err(reason) = validated {
+            self.rollback();
+            return Err(reason);
+        }
         #[cfg(feature = "zen")]
         let mut address = 0;
 
@@ -253,13 +473,15 @@ impl<'a> Transaction<'a> {
                 }
                 let delta_address = self.txn_buffer.alloc();
                 let mut insert_delta = TupleDelta::new(delta_address, 0, ws.tuple_id.get_address()).unwrap();
-                insert_delta.set_meta_data(delta::DELTA_COLUMN_OFFSET, 0 as u32);
-                insert_delta.set_meta_data(delta::DELTA_COLUMN_OFFSET, 0 as u32);
+                insert_delta.set_meta_data(delta::DELTA_COLUMN_OFFSET, delta::INSERT_COLUMN_OFFSET);
                 insert_delta.set_meta_data(delta::DELTA_TABLE_ID, ws.table.id);
                 insert_delta.set_meta_data(delta::TID, self.ts);
                 #[cfg(not(feature = "ilog"))]
                 insert_delta.clwb();
                 self.txn_buffer.add_delta(insert_delta.len());
+                // recovery sets the commit mark of the insert from it
+                #[cfg(feature = "ilog")]
+                self.txn_buffer.save_redo(insert_delta.len() + crate::config::U64_OFFSET);
                 continue;
             }
             #[cfg(feature = "zen")]
@@ -267,6 +489,7 @@ impl<'a> Transaction<'a> {
                 address = ws.do_update(
                     self.txn_buffer.alloc(),
                     ws.column_id,
+                    self.cc,
                     self.thread_id,
                     self.cur_min_txn,
                     #[cfg(feature = "clock")]
@@ -288,6 +511,7 @@ impl<'a> Transaction<'a> {
                     #[cfg(all(feature = "mvcc", feature = "ilog"))]
                     d_delta_address,
                     column_id,
+                    self.cc,
                     self.thread_id,
                     self.cur_min_txn,
                     #[cfg(feature = "clock")]
@@ -301,6 +525,7 @@ impl<'a> Transaction<'a> {
                     #[cfg(all(feature = "mvcc", feature = "ilog"))]
                     d_delta_address,
                     column_id,
+                    self.cc,
                     self.thread_id,
                     self.cur_min_txn,
                     #[cfg(feature = "clock")]
@@ -314,6 +539,7 @@ impl<'a> Transaction<'a> {
                     #[cfg(all(feature = "mvcc", feature = "ilog"))]
                     d_delta_address,
                     column_id,
+                    self.cc,
                     self.thread_id,
                     self.cur_min_txn,
                     #[cfg(feature = "clock")]
@@ -336,11 +562,65 @@ impl<'a> Transaction<'a> {
         }
         // file::sfence();
         self.txn_buffer.commit(true);
+        // recovery only indexes rows with the commit mark, it sets the mark
+        // again from the insert records of a committed transaction if a
+        // crash comes first
+        #[cfg(not(feature = "buffer_pool"))]
+        for ws in &self.write_set {
+            if ws.is_insert {
+                ws.table.get_tuple(&ws.tuple_id).commit();
+            }
+        }
         self.finish(true);
-        true
+        Ok(CommitInfo {
+            commit_ts: self.ts.tid,
+        })
+    }
+
+    /// Mark the current point of the transaction, see `rollback_to`.
+    pub fn savepoint(&self) -> Savepoint {
+        Savepoint {
+            tid: self.ts.tid,
+            writes: self.write_set.len(),
+        }
     }
 
-    pub fn abort(&mut self) {
+    /// Undo the updates, deletes and inserts (with their index entries) made
+    /// since `savepoint` and keep going. Reads since stay in the read set.
+    /// `Error::NotFound` for a savepoint of another transaction or one
+    /// already rolled back past.
+    pub fn rollback_to(&mut self, savepoint: Savepoint) -> Result {
+        if savepoint.tid != self.ts.tid || savepoint.writes > self.write_set.len() {
+            return Err(Error::NotFound);
+        }
+        let undone = self.write_set.split_off(savepoint.writes);
+        for ws in undone.iter().rev() {
+            ws.abort(
+                self.thread_id,
+                self.cur_min_txn,
+                #[cfg(feature = "clock")]
+                &mut self.timer,
+            );
+            // locks taken at update time stay with the writes kept
+            #[cfg(feature = "update_direct")]
+            if !self
+                .write_set
+                .iter()
+                .any(|kept| kept.table.id == ws.table.id && kept.tuple_id.eq(&ws.tuple_id))
+            {
+                ws.finish(self.cc, self.thread_id, self.cur_min_txn);
+            }
+        }
+        Ok(())
+    }
+
+    /// Roll back, a user abort.
+    pub fn abort(&mut self) -> AbortReason {
+        self.rollback();
+        AbortReason::new(AbortKind::User)
+    }
+
+    fn rollback(&mut self) {
         for ws in &self.write_set {
             // debug!("txn abort tuple_id {}", ws.tuple_id.id);
             ws.abort(
@@ -353,7 +633,8 @@ impl<'a> Transaction<'a> {
         self.finish(false);
     }
 
-    pub fn insert(&mut self, table: &'a Table, data: &str) -> TupleId {
+    /// Insert a tuple, `Error::Exist` if one of its unique keys is taken.
+    pub fn insert(&mut self, table: &'a Table, data: &str) -> Result<TupleId> {
         let tuple_id = table.allocate_tuple(self.thread_id).unwrap();
         //println!("insert id {}", tuple_id.id);
         let tuple_address = table.get_address(&tuple_id);
@@ -373,11 +654,15 @@ impl<'a> Transaction<'a> {
             data: Vec::new(),
         });
         let tuple = &Tuple::new(tuple_address, data, &table.schema, self.ts).unwrap();
-        table.index_insert_by_tuple(&tuple_id, &tuple).unwrap();
+        if let Err(e) = table.index_insert_by_tuple(&tuple_id, &tuple) {
+            self.write_set.pop();
+            table.free_tuple(&tuple_id, self.thread_id);
+            return Err(e);
+        }
         tuple.clwb_len(table.schema.tuple_size() as u64);
         // debug!("txn {} insert tuple_id {}", self.ts, tuple_id.id);
 
-        tuple_id.clone()
+        Ok(tuple_id.clone())
     }
     pub fn alloc(&mut self, table: &'a Table) -> Tuple {
         let tuple_id = table.allocate_tuple(self.thread_id).unwrap();
@@ -401,8 +686,9 @@ impl<'a> Transaction<'a> {
         let tuple = Tuple::reload(tuple_address);
         tuple.set_ts(self.ts);
         tuple.set_next(0);
-        #[cfg(feature = "cc_cfg_2pl")]
-        tuple.lock_write(self.ts.tid, 0).unwrap();
+        if self.cc.is_locking() {
+            tuple.lock_write(self.ts.tid, 0).unwrap();
+        }
         tuple.set_lock_tid(self.ts.tid);
         tuple
     }
@@ -414,6 +700,7 @@ impl<'a> Transaction<'a> {
     pub fn read(&mut self, table: &'a Table, tuple_id: &TupleId) -> Result<TupleVec> {
         #[cfg(feature = "clock")]
         self.timer.start(READING);
+        self.refresh_snapshot();
 
         #[cfg(not(feature = "buffer_pool"))]
         let tuple_nvm = table.get_tuple(tuple_id);
@@ -454,22 +741,14 @@ impl<'a> Transaction<'a> {
                 tuple_ts = tuple.get_ts();
             }
 
-            #[cfg(all(feature = "serializable", feature = "cc_cfg_2pl"))]
-            {
-                if !self.read_only {
-                    if tuple_nvm.lock_read(self.ts.tid).is_err() {
-                        return Err(TupleError::AcquireReadLockFalse.into());
-                    } else {
-                        break;
-                    }
-                }
+            if self.validates_reads() && self.cc.read(&tuple_nvm, self.ts.tid)? {
+                break;
             }
 
             if !self.read_only && tuple_nvm.lock_tid() != 0 && tuple_nvm.lock_tid() < self.ts.tid {
                 // println!("{:?} {} {}", tuple_nvm.lock_tid(), self.ts.tid, self.cur_min_txn);
                 return Err(TupleError::TupleChanged { conflict_tid: 0 }.into());
             }
-            #[cfg(all(feature = "serializable"))]
             if self
                 .snapshot
                 .access(tuple_ts, self.ts.tid, self.cur_min_txn)
@@ -483,9 +762,14 @@ impl<'a> Transaction<'a> {
             }
             // debug!("txn {} read old {}", self.ts.tid, tuple_ts.tid);
 
-            #[cfg(all(feature = "serializable", not(feature = "cc_cfg_mvto")))]
+            if self.is_ssi() && !Catalog::global().get_ssi().read_over(self.ts.tid, tuple_ts.tid) {
+                #[cfg(feature = "clock")]
+                self.timer.end(READING, READING);
+                return Err(TupleError::PreValidationFailed.into());
+            }
+            #[cfg(not(feature = "cc_cfg_mvto"))]
             {
-                if !self.read_only {
+                if self.validates_reads() {
                     #[cfg(feature = "clock")]
                     self.timer.end(READING, READING);
                     return Err(TupleError::PreValidationFailed.into());
@@ -503,6 +787,7 @@ impl<'a> Transaction<'a> {
                     self.timer.end(READING, READING);
                     return Err(TupleError::TupleNotExists.into());
                 }
+                self.check_snapshot_age()?;
                 latest = false;
             }
         }
@@ -530,8 +815,7 @@ impl<'a> Transaction<'a> {
         }
         #[cfg(feature = "clock")]
         self.timer.end(READING, READING);
-        #[cfg(feature = "serializable")]
-        if !self.read_only {
+        if self.tracks_reads() {
             #[cfg(not(feature = "buffer_pool"))]
             self.read_set
                 .push(AccessStruct::new(table, tuple_id.clone(), tuple.ts()));
@@ -542,6 +826,59 @@ impl<'a> Transaction<'a> {
 
         Ok(tuple)
     }
+    /// The version of `tuple_id` committed as of `ts`, `TupleNotExists` if
+    /// it was inserted later. A `ts` after the snapshot of the transaction
+    /// reads as of the snapshot, and one a running writer may still commit
+    /// into as of before that writer. Versions are kept back to the gc
+    /// horizon, held back by `VersionGc::set_retention`, and an older `ts`
+    /// gets `SnapshotTooOld`. Nothing is validated at commit.
+    #[cfg(all(feature = "mvcc", not(feature = "buffer_pool")))]
+    pub fn read_as_of(&mut self, table: &'a Table, tuple_id: &TupleId, ts: u64) -> Result<TupleVec> {
+        let catalog = Catalog::global();
+        let ts = catalog.stable_snapshot(ts.min(self.snapshot.clock));
+        if catalog.get_gc().too_old(ts) {
+            return Err(TupleError::SnapshotTooOld.into());
+        }
+        let tuple_nvm = table.get_tuple(tuple_id);
+        let mut tuple = loop {
+            let tuple_ts = tuple_nvm.ts();
+            let tuple = tuple_nvm.read(table.tuple_size as usize);
+            if tuple.get_ts() == tuple_ts {
+                break tuple;
+            }
+        };
+        // a pending version has POW_2_63 set
+        while tuple.get_ts().tid > ts {
+            if !tuple.next(table) {
+                return Err(TupleError::TupleNotExists.into());
+            }
+            if catalog.get_gc().too_old(ts) {
+                return Err(TupleError::SnapshotTooOld.into());
+            }
+        }
+        Ok(tuple)
+    }
+    /// The tuples with primary keys in [lower, upper) as of `ts`, see
+    /// `read_as_of`. The range is looked up in the index as it is now, so
+    /// tuples deleted since `ts` are missed.
+    #[cfg(all(feature = "mvcc", not(feature = "buffer_pool")))]
+    pub fn scan_as_of(
+        &mut self,
+        table: &'a Table,
+        lower: Bound<IndexType>,
+        upper: Bound<IndexType>,
+        ts: u64,
+    ) -> Result<Vec<(TupleId, TupleVec)>> {
+        let mut tuples = Vec::new();
+        for tuple_id in table.range_cursor_on_index(lower, upper, table.get_primary_key())? {
+            match self.read_as_of(table, &tuple_id, ts) {
+                Ok(tuple) => tuples.push((tuple_id, tuple)),
+                Err(Error::Tuple(TupleError::TupleNotExists)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(tuples)
+    }
 
     pub fn update(
         &mut self,
@@ -565,6 +902,7 @@ impl<'a> Transaction<'a> {
                 update_data,
                 &self.snapshot,
                 self.ts,
+                self.cc,
                 self.thread_id as usize,
                 self.cur_min_txn,
                 #[cfg(feature = "clock")]
@@ -643,6 +981,7 @@ impl<'a> Transaction<'a> {
                 update_column_id,
                 update_data,
                 self.ts,
+                self.cc,
                 self.thread_id as usize,
                 self.cur_min_txn,
                 #[cfg(feature = "clock")]
@@ -695,6 +1034,137 @@ impl<'a> Transaction<'a> {
         // self.timer.end(UPDATING, UPDATING);
         return Ok(());
     }
+    /// Tuples with primary keys in [lower, upper), see `range_tuple_id_on_index`.
+    pub fn range_tuple_id(
+        &mut self,
+        table: &'a Table,
+        lower: Bound<IndexType>,
+        upper: Bound<IndexType>,
+    ) -> Result<Vec<TupleId>> {
+        self.range_tuple_id_on_index(table, lower, upper, table.get_primary_key())
+    }
+    /// Tuples of ordered index `columns` with keys within the bounds. A
+    /// serializable transaction remembers the range and aborts at commit if
+    /// a tuple was inserted into or removed from it meanwhile.
+    pub fn range_tuple_id_on_index(
+        &mut self,
+        table: &'a Table,
+        lower: Bound<IndexType>,
+        upper: Bound<IndexType>,
+        columns: usize,
+    ) -> Result<Vec<TupleId>> {
+        let mut cursor = self.scan_cursor_on_index(table, lower, upper, columns)?;
+        let mut tuple_ids = Vec::new();
+        while let Some(tuple_id) = self.next_in(&mut cursor) {
+            tuple_ids.push(tuple_id);
+        }
+        Ok(tuple_ids)
+    }
+    /// A streaming scan of the primary key within the bounds, see
+    /// `scan_cursor_on_index`.
+    pub fn scan_cursor(
+        &mut self,
+        table: &'a Table,
+        lower: Bound<IndexType>,
+        upper: Bound<IndexType>,
+    ) -> Result<ScanCursor<'a>> {
+        self.scan_cursor_on_index(table, lower, upper, table.get_primary_key())
+    }
+    /// A streaming scan of ordered index `columns` within the bounds, its
+    /// rows taken with `next_in`. A serializable transaction validates the
+    /// part of the range taken so far, the whole range once the cursor ran
+    /// out.
+    pub fn scan_cursor_on_index(
+        &mut self,
+        table: &'a Table,
+        lower: Bound<IndexType>,
+        upper: Bound<IndexType>,
+        columns: usize,
+    ) -> Result<ScanCursor<'a>> {
+        let cursor = table.range_cursor_on_index(lower.clone(), upper.clone(), columns)?;
+        let scan = if self.tracks_reads() {
+            self.scan_set.push(ScanSetStruct::new(
+                table,
+                columns,
+                lower.clone(),
+                upper.clone(),
+                Vec::new(),
+            ));
+            Some(self.scan_set.len() - 1)
+        } else {
+            None
+        };
+        Ok(ScanCursor {
+            cursor,
+            lower,
+            upper,
+            descending: false,
+            scan,
+        })
+    }
+    /// The next row of `cursor`, narrowing the range kept for validation
+    /// to the rows taken.
+    pub fn next_in(&mut self, cursor: &mut ScanCursor<'a>) -> Option<TupleId> {
+        let tuple_id = cursor.cursor.next();
+        if let Some(scan) = cursor.scan {
+            let scan = &mut self.scan_set[scan];
+            if let Some(tuple_id) = &tuple_id {
+                scan.tuple_ids.push(tuple_id.clone());
+            }
+            let end = cursor.cursor.continuation().map(Bound::Included);
+            if cursor.descending {
+                scan.lower = end.unwrap_or_else(|| cursor.lower.clone());
+            } else {
+                scan.upper = end.unwrap_or_else(|| cursor.upper.clone());
+            }
+        }
+        tuple_id
+    }
+    /// Tuples whose primary key starts with the composite key `prefix`.
+    pub fn prefix_tuple_id(&mut self, table: &'a Table, prefix: &IndexType) -> Result<Vec<TupleId>> {
+        let columns = table.get_primary_key();
+        let (lower, upper) = table.prefix_bounds(prefix, columns)?;
+        self.range_tuple_id_on_index(table, lower, upper, columns)
+    }
+    /// The tuple with the greatest primary key starting with `prefix`.
+    pub fn prefix_last_tuple_id(
+        &mut self,
+        table: &'a Table,
+        prefix: &IndexType,
+    ) -> Result<Option<TupleId>> {
+        let (lower, upper) = table.prefix_bounds(prefix, table.get_primary_key())?;
+        self.last_tuple_id(table, lower, upper)
+    }
+    /// The tuple with the greatest primary key within the bounds, the range
+    /// kept for validation is the keys from it on.
+    pub fn last_tuple_id(
+        &mut self,
+        table: &'a Table,
+        lower: Bound<IndexType>,
+        upper: Bound<IndexType>,
+    ) -> Result<Option<TupleId>> {
+        let columns = table.get_primary_key();
+        let mut cursor = table
+            .range_cursor_on_index(lower.clone(), upper.clone(), columns)?
+            .descending()
+            .limit(1);
+        let last = cursor.next();
+        if self.tracks_reads() {
+            let lower = match cursor.continuation() {
+                Some(key) => Bound::Included(key),
+                None => lower,
+            };
+            self.scan_set.push(ScanSetStruct::new(
+                table,
+                columns,
+                lower,
+                upper,
+                last.iter().cloned().collect(),
+            ));
+        }
+        Ok(last)
+    }
+
     pub fn read_column(
         &mut self,
         table: &'a Table,
@@ -704,6 +1174,7 @@ impl<'a> Transaction<'a> {
         // #[cfg(feature = "clock")]
         // self.timer.start(READING);
         assert!(tuple_id.get_address() > 0);
+        self.refresh_snapshot();
         #[cfg(not(feature = "buffer_pool"))]
         let tuple_nvm = table.get_tuple(tuple_id);
         // tuple_nvm.apply_next(self.clog);
@@ -740,21 +1211,13 @@ impl<'a> Transaction<'a> {
                 tuple_ts = tuple.get_ts();
             }
 
-            #[cfg(all(feature = "serializable", feature = "cc_cfg_2pl"))]
-            {
-                if !self.read_only {
-                    if tuple_nvm.lock_read(self.ts.tid).is_err() {
-                        return Err(TupleError::AcquireReadLockFalse.into());
-                    } else {
-                        break;
-                    }
-                }
+            if self.validates_reads() && self.cc.read(&tuple_nvm, self.ts.tid)? {
+                break;
             }
             // println!("lock_tid {}", tuple_nvm.lock_tid());
             if !self.read_only && tuple_nvm.lock_tid() != 0 && tuple_nvm.lock_tid() < self.ts.tid {
                 return Err(TupleError::TupleChanged { conflict_tid: 0 }.into());
             }
-            #[cfg(all(feature = "serializable"))]
             if self
                 .snapshot
                 .access(tuple_ts, self.ts.tid, self.cur_min_txn)
@@ -768,9 +1231,11 @@ impl<'a> Transaction<'a> {
             }
 
             // debug!("txn {} read old {}", self.ts.tid, tuple_ts.tid);
-            #[cfg(all(feature = "serializable", feature = "cc_cfg_occ"))]
-            {
-                if !self.read_only {
+            if self.is_ssi() && !Catalog::global().get_ssi().read_over(self.ts.tid, tuple_ts.tid) {
+                return Err(TupleError::PreValidationFailed.into());
+            }
+            if self.cc.validates() {
+                if self.validates_reads() {
                     // println!("txn {} read {}", self.ts.tid, tuple_ts.tid);
                     // println!("2222");
                     #[cfg(feature = "clock")]
@@ -791,6 +1256,7 @@ impl<'a> Transaction<'a> {
                     // println!("1111");
                     return Err(TupleError::TupleNotExists.into());
                 }
+                self.check_snapshot_age()?;
                 latest = false;
             }
         }
@@ -816,8 +1282,7 @@ impl<'a> Transaction<'a> {
         // #[cfg(feature = "clock")]
         // self.timer.end(READING, READING);
 
-        #[cfg(feature = "serializable")]
-        if !self.read_only {
+        if self.tracks_reads() {
             #[cfg(not(feature = "buffer_pool"))]
             self.read_set
                 .push(AccessStruct::new(table, tuple_id.clone(), tuple.ts()));
@@ -835,6 +1300,9 @@ mod tests {
     use crate::storage::nvm_file::*;
     use crate::storage::schema::*;
     use crate::storage::table::IndexType;
+    use crate::transaction::testing::{self, Fixture};
+    use std::convert::TryInto;
+    use std::ops::Bound;
     #[test]
     fn test_insert_and_update_and_read() {
         let thread_id = 0;
@@ -851,7 +1319,7 @@ mod tests {
         let table_name = "table1";
         catalog.add_table(table_name, schema).unwrap();
 
-        catalog.set_primary_key(table_name, 1);
+        catalog.set_primary_key(table_name, &[1]);
         let table = &catalog.get_table(table_name);
 
         // txn1 add t1, t2
@@ -860,14 +1328,14 @@ mod tests {
         transaction1.begin();
         println!("txn1 begin");
 
-        let tuple1_id = transaction1.insert(table, "233,666");
+        let tuple1_id = transaction1.insert(table, "233,666").unwrap();
         println!("insert t1 233 666");
-        let tuple2_id = transaction1.insert(table, "666,233");
+        let tuple2_id = transaction1.insert(table, "666,233").unwrap();
         println!("insert t2 666 233");
         println!("tuple1 insert address: {:x}", tuple1_id.get_address());
         println!("tuple2 insert address: {:x}", tuple2_id.get_address());
 
-        transaction1.commit();
+        transaction1.commit().unwrap();
 
         // let tuple_id = table.search_tuple_id(&IndexType::Int64(666)).unwrap();
         // let tuple_id2 = tuple_id.clone();
@@ -887,7 +1355,7 @@ mod tests {
             .unwrap();
         println!("transaction id {} update t1 666 666", transaction2.ts.tid);
 
-        assert!(transaction2.commit());
+        assert!(transaction2.commit().is_ok());
         let tuple1_id = table.search_tuple_id(&IndexType::Int64(666)).unwrap();
         println!("tuple1 new address: {:x}", tuple1_id.get_address());
         #[cfg(feature = "clock")]
@@ -934,7 +1402,7 @@ mod tests {
         let mut tuple2_1 = transaction4.read(table, &tuple2_id).unwrap();
         println!("update t2 233 233");
 
-        assert!(transaction4.commit());
+        assert!(transaction4.commit().is_ok());
         println!("transaction4 commit");
 
         // txn2 update t1
@@ -1002,7 +1470,7 @@ mod tests {
         let table_name = "table1";
         catalog.add_table(table_name, schema).unwrap();
 
-        catalog.set_range_primary_key(table_name, 0);
+        catalog.set_range_primary_key(table_name, &[0]);
         let table = &catalog.get_table(table_name);
         // catalog.add_range_index_by_name(table_name, 1);
         // txn1 add t1, t2
@@ -1011,21 +1479,39 @@ mod tests {
         transaction1.begin();
         println!("txn1 begin");
 
-        let tuple1_id = transaction1.insert(table, "2,6");
+        let tuple1_id = transaction1.insert(table, "2,6").unwrap();
         println!("insert t1 233 666");
-        let tuple2_id = transaction1.insert(table, "6,3");
+        let tuple2_id = transaction1.insert(table, "6,3").unwrap();
         println!("insert t2 666 233");
-        let tuple3_id = transaction1.insert(table, "3,2");
+        let tuple3_id = transaction1.insert(table, "3,2").unwrap();
         let t3_id = tuple3_id.clone();
         println!("insert t3 233 666");
-        let tuple4_id = transaction1.insert(table, "5,4");
+        let tuple4_id = transaction1.insert(table, "5,4").unwrap();
         println!("insert t4 666 233");
         println!("tuple1 insert address: {}", tuple1_id.get_address());
         println!("tuple2 insert address: {}", tuple2_id.get_address());
         println!("tuple3 insert address: {}", tuple3_id.get_address());
         println!("tuple4 insert address: {}", tuple4_id.get_address());
-        transaction1.commit();
-        
+        transaction1.commit().unwrap();
+
+        let r: Vec<TupleId> = table
+            .range_cursor(Bound::Excluded(IndexType::Int64(2)), Bound::Included(IndexType::Int64(6)))
+            .unwrap()
+            .descending()
+            .collect();
+        assert_eq!(r, vec![tuple2_id.clone(), tuple4_id.clone(), tuple3_id.clone()]);
+
+        let mut cursor = table.range_cursor(Bound::Unbounded, Bound::Unbounded).unwrap().limit(2);
+        let r: Vec<TupleId> = cursor.by_ref().collect();
+        assert_eq!(r, vec![tuple1_id.clone(), tuple3_id.clone()]);
+        let key = cursor.continuation().unwrap();
+        let r: Vec<TupleId> = table
+            .range_cursor(Bound::Unbounded, Bound::Unbounded)
+            .unwrap()
+            .resume_after(key)
+            .collect();
+        assert_eq!(r, vec![tuple4_id.clone(), tuple2_id.clone()]);
+
         let r = table.range_tuple_id(&IndexType::Int64(1), &IndexType::Int64(6)).unwrap();
         let ret = vec![tuple1_id, tuple3_id, tuple4_id];
         println!("{:?}", r);
@@ -1035,9 +1521,495 @@ mod tests {
         transaction2.begin();
         println!("txn2 begin");
         transaction2.delete(table, &t3_id).unwrap();
-        transaction2.commit();
+        transaction2.commit().unwrap();
         let r = table.range_tuple_id(&IndexType::Int64(1), &IndexType::Int64(6)).unwrap();
         println!("{:?}", r);
 
     }
+
+    #[test]
+    fn test_runtime_cc() {
+        let fixture = Fixture::with_cc("table_cc", ConcurrencyControl::default());
+        let table = &fixture.table;
+        let mut buffer1 = fixture.buffer();
+        let mut buffer2 = fixture.buffer();
+
+        for (i, cc) in ConcurrencyControl::ALL.iter().enumerate() {
+            fixture.set_cc(*cc);
+            let mut insert = Transaction::new(&mut buffer1, false);
+            insert.begin();
+            let tuple_id = insert.insert(table, &format!("{},0", i)).unwrap();
+            assert!(insert.commit().is_ok());
+
+            // txn1 reads the row, txn2 writes it, then txn1 writes it
+            let mut transaction1 = Transaction::new(&mut buffer1, false);
+            transaction1.begin();
+            let mut transaction2 = Transaction::new(&mut buffer2, false);
+            transaction2.begin();
+            transaction1.read(table, &tuple_id).unwrap();
+            transaction2
+                .update(table, &tuple_id, 1, &1u64.to_le_bytes())
+                .unwrap();
+            let committed2 = transaction2.commit().is_ok();
+            transaction1
+                .update(table, &tuple_id, 1, &2u64.to_le_bytes())
+                .unwrap();
+            let committed1 = transaction1.commit().is_ok();
+            // 2PL holds the writer off the row read, the others abort the
+            // reader whose read went stale
+            assert_eq!(committed2, !cc.is_locking(), "{}", cc.name());
+            assert_eq!(committed1, cc.is_locking(), "{}", cc.name());
+
+            let mut transaction = Transaction::new(&mut buffer1, false);
+            transaction.begin();
+            let tuple = transaction.read(table, &tuple_id).unwrap();
+            let expected: u64 = if cc.is_locking() { 2 } else { 1 };
+            assert_eq!(
+                tuple.get_column_by_id(&table.schema, 1),
+                &expected.to_le_bytes()[..]
+            );
+            assert!(transaction.commit().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_isolation_level() {
+        let fixture = Fixture::with_cc("table_isolation", ConcurrencyControl::Occ);
+        let table = &fixture.table;
+        let mut buffer1 = fixture.buffer();
+        let mut buffer2 = fixture.buffer();
+
+        let mut insert = Transaction::new(&mut buffer1, false);
+        insert.begin();
+        let x = insert.insert(table, "1,0").unwrap();
+        let y = insert.insert(table, "2,0").unwrap();
+        assert!(insert.commit().is_ok());
+
+        // txn1 reads x and writes y while txn2 writes x, which only
+        // serializable rejects: for SSI a single rw-antidependency is no cycle
+        let levels = [
+            (IsolationLevel::SnapshotIsolation, true),
+            (IsolationLevel::Serializable, false),
+            (IsolationLevel::SerializableSnapshot, true),
+        ];
+        for (value, (isolation, commits)) in levels.iter().enumerate() {
+            let mut transaction1 = Transaction::new(&mut buffer1, false);
+            transaction1.begin_with(*isolation).unwrap();
+            assert_eq!(transaction1.isolation(), *isolation);
+            let mut transaction2 = Transaction::new(&mut buffer2, false);
+            transaction2.begin();
+            transaction1.read(table, &x).unwrap();
+            transaction2
+                .update(table, &x, 1, &(value as u64 + 1).to_le_bytes())
+                .unwrap();
+            assert!(transaction2.commit().is_ok());
+            transaction1
+                .update(table, &y, 1, &(value as u64 + 1).to_le_bytes())
+                .unwrap();
+            assert_eq!(transaction1.commit().is_ok(), *commits);
+        }
+
+        // read committed sees a commit made after it began
+        let mut transaction1 = Transaction::new(&mut buffer1, false);
+        transaction1.begin_with(IsolationLevel::ReadCommitted).unwrap();
+        let mut transaction2 = Transaction::new(&mut buffer2, false);
+        transaction2.begin();
+        transaction2
+            .update(table, &x, 1, &7u64.to_le_bytes())
+            .unwrap();
+        assert!(transaction2.commit().is_ok());
+        let tuple = transaction1.read(table, &x).unwrap();
+        assert_eq!(
+            tuple.get_column_by_id(&table.schema, 1),
+            &7u64.to_le_bytes()[..]
+        );
+        assert!(transaction1.commit().is_ok());
+
+        // SSI is refused under the other protocols, the buffer stays usable
+        fixture.set_cc(ConcurrencyControl::TwoPl);
+        let mut transaction = Transaction::new(&mut buffer1, false);
+        assert!(matches!(
+            transaction.begin_with(IsolationLevel::SerializableSnapshot),
+            Err(Error::IsolationNotSupported)
+        ));
+        transaction.begin();
+        assert!(transaction.commit().is_ok());
+    }
+
+    #[test]
+    fn test_wound_wait() {
+        use crate::transaction::cc::LockPolicy;
+        use std::sync::Barrier;
+
+        let table_name = "table_wound";
+        let fixture = Fixture::with_cc(table_name, ConcurrencyControl::TwoPl);
+        fixture.catalog.lock_waits().set_policy(LockPolicy::WoundWait);
+        let (x, y) = fixture
+            .buffer()
+            .run(|txn| Ok((txn.insert(&fixture.table, "1,0")?, txn.insert(&fixture.table, "2,0")?)))
+            .unwrap();
+
+        // they lock x and y in opposite orders, the younger one gives way
+        let round = Barrier::new(2);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let table = &Catalog::global().get_table(table_name);
+                let mut buffer = TransactionBuffer::new(Catalog::global(), testing::thread_id());
+                for i in 1..=20u64 {
+                    let mut txn = Transaction::new(&mut buffer, false);
+                    txn.begin();
+                    round.wait();
+                    txn.update(table, &y, 1, &i.to_le_bytes()).unwrap();
+                    txn.update(table, &x, 1, &i.to_le_bytes()).unwrap();
+                    let committed = txn.commit();
+                    round.wait();
+                    assert!(committed.is_ok(), "{:?}", committed);
+                }
+            });
+            s.spawn(|| {
+                let table = &Catalog::global().get_table(table_name);
+                let mut buffer = TransactionBuffer::new(Catalog::global(), testing::thread_id());
+                for _ in 1..=20u64 {
+                    round.wait();
+                    let mut txn = Transaction::new(&mut buffer, false);
+                    txn.begin();
+                    txn.update(table, &x, 1, &0u64.to_le_bytes()).unwrap();
+                    txn.update(table, &y, 1, &0u64.to_le_bytes()).unwrap();
+                    let committed = txn.commit();
+                    round.wait();
+                    if let Err(reason) = committed {
+                        assert_eq!(reason.kind, AbortKind::LockTimeout);
+                    }
+                }
+            });
+        });
+
+        let table = &fixture.table;
+        let b = |tuple: TupleVec| u64::from_le_bytes(tuple.get_column_by_id(&table.schema, 1).try_into().unwrap());
+        let mut buffer = fixture.buffer();
+        let mut transaction = Transaction::new(&mut buffer, false);
+        transaction.begin();
+        let bx = b(transaction.read(table, &x).unwrap());
+        let by = b(transaction.read(table, &y).unwrap());
+        assert!(transaction.commit().is_ok());
+        assert_eq!(bx, by);
+    }
+
+    #[test]
+    fn test_phantom() {
+        let fixture = Fixture::with_cc("table_phantom", ConcurrencyControl::Occ).range_key();
+        let table = &fixture.table;
+        let mut buffer1 = fixture.buffer();
+        let mut buffer2 = fixture.buffer();
+
+        let mut insert = Transaction::new(&mut buffer1, false);
+        insert.begin();
+        let x = insert.insert(table, "1,0").unwrap();
+        insert.insert(table, "3,0").unwrap();
+        assert!(insert.commit().is_ok());
+
+        // txn1 scans [0, 10) and writes x while txn2 inserts into the range,
+        // a phantom only serializable rejects
+        let levels = [
+            (IsolationLevel::SnapshotIsolation, true),
+            (IsolationLevel::Serializable, false),
+        ];
+        for (i, (isolation, commits)) in levels.iter().enumerate() {
+            let mut transaction1 = Transaction::new(&mut buffer1, false);
+            transaction1.begin_with(*isolation).unwrap();
+            let scanned = transaction1
+                .range_tuple_id(
+                    table,
+                    Bound::Included(IndexType::Int64(0)),
+                    Bound::Excluded(IndexType::Int64(10)),
+                )
+                .unwrap();
+            assert_eq!(scanned.len(), 2 + i);
+            for tuple_id in &scanned {
+                transaction1.read(table, tuple_id).unwrap();
+            }
+            let mut transaction2 = Transaction::new(&mut buffer2, false);
+            transaction2.begin();
+            transaction2.insert(table, &format!("{},0", 5 + i)).unwrap();
+            assert!(transaction2.commit().is_ok());
+            transaction1.update(table, &x, 1, &1u64.to_le_bytes()).unwrap();
+            assert_eq!(transaction1.commit().is_ok(), *commits);
+        }
+
+        // its own inserts are no phantoms
+        let mut transaction1 = Transaction::new(&mut buffer1, false);
+        transaction1.begin_with(IsolationLevel::Serializable).unwrap();
+        let scanned = transaction1
+            .range_tuple_id(
+                table,
+                Bound::Included(IndexType::Int64(0)),
+                Bound::Excluded(IndexType::Int64(10)),
+            )
+            .unwrap();
+        for tuple_id in &scanned {
+            transaction1.read(table, tuple_id).unwrap();
+        }
+        transaction1.insert(table, "8,0").unwrap();
+        assert!(transaction1.commit().is_ok());
+
+        // a cursor validates the rows it took: an insert past them is no
+        // phantom, one before them is
+        for (key, commits) in [(4, true), (2, false)].iter() {
+            let mut transaction1 = Transaction::new(&mut buffer1, false);
+            transaction1.begin_with(IsolationLevel::Serializable).unwrap();
+            let mut cursor = transaction1
+                .scan_cursor(table, Bound::Included(IndexType::Int64(2)), Bound::Unbounded)
+                .unwrap()
+                .limit(1);
+            let tuple_id = transaction1.next_in(&mut cursor).unwrap();
+            transaction1.read(table, &tuple_id).unwrap();
+            assert!(transaction1.next_in(&mut cursor).is_none());
+            let mut transaction2 = Transaction::new(&mut buffer2, false);
+            transaction2.begin();
+            transaction2.insert(table, &format!("{},0", key)).unwrap();
+            assert!(transaction2.commit().is_ok());
+            transaction1.update(table, &x, 1, &2u64.to_le_bytes()).unwrap();
+            assert_eq!(transaction1.commit().is_ok(), *commits);
+        }
+    }
+
+    #[test]
+    fn test_read_only() {
+        let fixture = Fixture::with_cc("table_read_only", ConcurrencyControl::Occ);
+        let table = &fixture.table;
+        let mut buffer1 = fixture.buffer();
+        let mut buffer2 = fixture.buffer();
+
+        let mut insert = Transaction::new(&mut buffer1, false);
+        insert.begin();
+        let x = insert.insert(table, "1,0").unwrap();
+        assert!(insert.commit().is_ok());
+
+        // the snapshot of a reader stops before the running writers
+        let mut transaction2 = Transaction::new(&mut buffer2, false);
+        transaction2.begin();
+        let mut transaction1 = Transaction::new(&mut buffer1, true);
+        transaction1.begin_with(IsolationLevel::Serializable).unwrap();
+        assert!(transaction1.snapshot.clock < transaction2.ts.tid);
+        assert!(Catalog::get_min_txn() <= transaction1.snapshot.clock);
+
+        // a read overwritten before commit does not abort it
+        let tuple = transaction1.read(table, &x).unwrap();
+        assert_eq!(tuple.get_column_by_id(&table.schema, 1), &0u64.to_le_bytes()[..]);
+        transaction2.update(table, &x, 1, &1u64.to_le_bytes()).unwrap();
+        assert!(transaction2.commit().is_ok());
+        #[cfg(feature = "mvcc")]
+        {
+            let tuple = transaction1.read(table, &x).unwrap();
+            assert_eq!(tuple.get_column_by_id(&table.schema, 1), &0u64.to_le_bytes()[..]);
+        }
+        assert!(transaction1.commit().is_ok());
+    }
+
+    #[test]
+    fn test_run() {
+        let fixture = Fixture::new("table_run");
+        let table = &fixture.table;
+        let mut buffer = fixture.buffer();
+
+        let x = buffer.run(|txn| txn.insert(table, "1,0")).unwrap();
+
+        // a conflict runs it again
+        let mut attempts = 0;
+        let b = buffer
+            .run(|txn| {
+                attempts += 1;
+                let tuple = txn.read(table, &x)?;
+                if attempts == 1 {
+                    return Err(TupleError::TupleChanged { conflict_tid: 0 }.into());
+                }
+                let b = u64::from_le_bytes(tuple.get_column_by_id(&table.schema, 1).try_into().unwrap());
+                txn.update(table, &x, 1, &(b + 1).to_le_bytes())?;
+                Ok(b + 1)
+            })
+            .unwrap();
+        assert_eq!((attempts, b), (2, 1));
+
+        // other errors are the caller's
+        let mut attempts = 0;
+        let result: Result<()> = buffer.run(|txn| {
+            attempts += 1;
+            txn.update(table, &x, 1, &5u64.to_le_bytes())?;
+            Err(crate::Error::NotFound)
+        });
+        assert!(matches!(result, Err(crate::Error::NotFound)));
+        assert_eq!(attempts, 1);
+
+        // up to the retry cap
+        let policy = RetryPolicy {
+            max_retries: Some(2),
+            ..RetryPolicy::forever()
+        };
+        let mut attempts = 0;
+        let result: Result<()> = buffer.run_with(policy, |_| {
+            attempts += 1;
+            Err(crate::Error::TxConflict)
+        });
+        match result {
+            Err(crate::Error::Aborted(reason)) => {
+                assert_eq!(reason.kind, AbortKind::WriteConflict { conflict_tid: 0 })
+            }
+            _ => panic!("expected the last abort"),
+        }
+        assert_eq!(attempts, 3);
+
+        // a failed commit that is no conflict is not retried
+        let mut attempts = 0;
+        let result: Result<()> = buffer.run(|_| {
+            attempts += 1;
+            Err(crate::Error::Aborted(AbortReason::new(AbortKind::Internal)))
+        });
+        assert!(matches!(result, Err(crate::Error::Aborted(_))));
+        assert_eq!(attempts, 1);
+
+        let b = buffer
+            .run(|txn| {
+                let tuple = txn.read(table, &x)?;
+                Ok(u64::from_le_bytes(tuple.get_column_by_id(&table.schema, 1).try_into().unwrap()))
+            })
+            .unwrap();
+        assert_eq!(b, 1);
+    }
+
+    #[test]
+    fn test_abort_reason() {
+        let fixture = Fixture::with_cc("table_abort", ConcurrencyControl::Occ);
+        let table = &fixture.table;
+        let mut buffer1 = fixture.buffer();
+        let mut buffer2 = fixture.buffer();
+
+        let mut insert = Transaction::new(&mut buffer1, false);
+        insert.begin();
+        let tuple_id = insert.insert(table, "1,0").unwrap();
+        let tid = insert.ts.tid;
+        assert_eq!(insert.commit().unwrap(), CommitInfo { commit_ts: tid });
+
+        // both write the row, the second to commit aborts
+        let mut transaction1 = Transaction::new(&mut buffer1, false);
+        transaction1.begin();
+        let mut transaction2 = Transaction::new(&mut buffer2, false);
+        transaction2.begin();
+        transaction1.update(table, &tuple_id, 1, &1u64.to_le_bytes()).unwrap();
+        transaction2.update(table, &tuple_id, 1, &2u64.to_le_bytes()).unwrap();
+        assert!(transaction1.commit().is_ok());
+        let reason = transaction2.commit().unwrap_err();
+        assert!(matches!(reason.kind, AbortKind::WriteConflict { .. }));
+        assert_eq!(reason.table.as_deref(), Some("table_abort"));
+        assert_eq!(reason.tuple, Some(tuple_id.get_address()));
+
+        let mut transaction = Transaction::new(&mut buffer1, false);
+        transaction.begin();
+        assert_eq!(transaction.abort(), AbortReason::new(AbortKind::User));
+    }
+
+    #[test]
+    fn test_savepoint() {
+        let fixture = Fixture::new("table_savepoint");
+        let table = &fixture.table;
+        let mut buffer = fixture.buffer();
+        let b = |tuple: TupleVec| u64::from_le_bytes(tuple.get_column_by_id(&table.schema, 1).try_into().unwrap());
+
+        let x = buffer.run(|txn| txn.insert(table, "1,0")).unwrap();
+
+        let mut transaction = Transaction::new(&mut buffer, false);
+        transaction.begin();
+        transaction.update(table, &x, 1, &1u64.to_le_bytes()).unwrap();
+        let savepoint = transaction.savepoint();
+        transaction.update(table, &x, 1, &2u64.to_le_bytes()).unwrap();
+        transaction.insert(table, "2,0").unwrap();
+        assert_eq!(b(transaction.read(table, &x).unwrap()), 2);
+        transaction.rollback_to(savepoint).unwrap();
+        assert_eq!(b(transaction.read(table, &x).unwrap()), 1);
+        assert!(table.search_tuple_id(&IndexType::Int64(2)).is_err());
+
+        // savepoints can be rolled back to again, later ones are gone
+        let savepoint = transaction.savepoint();
+        transaction.update(table, &x, 1, &3u64.to_le_bytes()).unwrap();
+        let later = transaction.savepoint();
+        transaction.rollback_to(savepoint).unwrap();
+        assert!(matches!(transaction.rollback_to(later), Err(crate::Error::NotFound)));
+        transaction.rollback_to(savepoint).unwrap();
+        transaction.commit().unwrap();
+
+        let mut transaction = Transaction::new(&mut buffer, false);
+        transaction.begin();
+        assert!(matches!(transaction.rollback_to(savepoint), Err(crate::Error::NotFound)));
+        assert_eq!(b(transaction.read(table, &x).unwrap()), 1);
+        transaction.commit().unwrap();
+    }
+
+    #[cfg(not(feature = "buffer_pool"))]
+    #[test]
+    fn test_recover_insert_mark() {
+        use crate::storage::row::{COMMIT_MASK, DELETE_FLAG};
+        use std::sync::atomic::AtomicU64;
+
+        let fixture = Fixture::new("table_insert_mark");
+        let table = &fixture.table;
+        let mut buffer = fixture.buffer();
+        let thread_id = buffer.get_thread_id();
+        let x = buffer.run(|txn| txn.insert(table, "1,0")).unwrap();
+        drop(buffer);
+
+        // a crash after the commit point, before the mark is set
+        let tuple = table.get_tuple(&x);
+        let flag = unsafe { &*((tuple._address() + DELETE_FLAG.start) as *const AtomicU64) };
+        flag.fetch_and(!COMMIT_MASK, Ordering::SeqCst);
+        assert!(TransactionBuffer::recover(fixture.catalog, thread_id) > 0);
+        assert_ne!(tuple.delete_flag() & COMMIT_MASK, 0);
+    }
+
+    #[cfg(all(feature = "mvcc", not(feature = "buffer_pool")))]
+    #[test]
+    fn test_read_as_of() {
+        let fixture = Fixture::new("table_read_as_of");
+        let table = &fixture.table;
+        let mut buffer = fixture.buffer();
+        let mut pin_buffer = fixture.buffer();
+        let b = |tuple: &TupleVec| u64::from_le_bytes(tuple.get_column_by_id(&table.schema, 1).try_into().unwrap());
+
+        // a running reader keeps the versions from here on
+        let mut pin = Transaction::new(&mut pin_buffer, true);
+        pin.begin();
+        let x = buffer.run(|txn| txn.insert(table, "1,0")).unwrap();
+        let inserted = fixture.catalog.get_snapshot().new_txn(0);
+        let mut transaction = Transaction::new(&mut buffer, false);
+        transaction.begin();
+        transaction.update(table, &x, 1, &1u64.to_le_bytes()).unwrap();
+        let t1 = transaction.commit().unwrap().commit_ts;
+        transaction.begin();
+        transaction.update(table, &x, 1, &2u64.to_le_bytes()).unwrap();
+        let t2 = transaction.commit().unwrap().commit_ts;
+        buffer.run(|txn| txn.insert(table, "2,0")).unwrap();
+
+        let mut reader = Transaction::new(&mut buffer, true);
+        reader.begin();
+        assert_eq!(b(&reader.read_as_of(table, &x, inserted).unwrap()), 0);
+        assert_eq!(b(&reader.read_as_of(table, &x, t1).unwrap()), 1);
+        assert_eq!(b(&reader.read_as_of(table, &x, t2).unwrap()), 2);
+        assert!(matches!(
+            reader.read_as_of(table, &x, pin.snapshot.clock),
+            Err(Error::Tuple(TupleError::TupleNotExists))
+        ));
+        // a ts ahead of the snapshot does not see later commits
+        let future = t2 + (1 << 40);
+        assert_eq!(b(&reader.read_as_of(table, &x, future).unwrap()), 2);
+        let mut writer_buffer = fixture.buffer();
+        writer_buffer
+            .run(|txn| txn.update(table, &x, 1, &3u64.to_le_bytes()))
+            .unwrap();
+        assert_eq!(b(&reader.read_as_of(table, &x, future).unwrap()), 2);
+        let tuples = reader
+            .scan_as_of(table, Bound::Unbounded, Bound::Unbounded, t1)
+            .unwrap();
+        assert_eq!(tuples.len(), 1);
+        assert_eq!(b(&tuples[0].1), 1);
+        assert!(reader.commit().is_ok());
+        assert!(pin.commit().is_ok());
+    }
 }
@@ -10,8 +10,13 @@ use crate::utils::file;
 use crate::utils::io;
 use std::ops::Deref;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
+use super::gc::RetiredRegions;
+use super::session::ThreadIds;
 use super::transaction::Transaction;
+use super::{AbortKind, AbortReason, RetryPolicy};
+use crate::{Error, Result};
 
 #[cfg(feature = "ilog")]
 const MANAGER_PAGE_SIZE: u64 = crate::mvcc_config::YCSB_SIZE as u64 * 2 - 4 * U64_OFFSET;
@@ -31,12 +36,33 @@ pub struct TransactionBuffer {
     thread_id: u64,
     offset: u64,
     pub address: Address,
+    /// Catalog word with the newest page, whose `NEXT_OFFSET` links back to
+    /// the pages the running transaction filled before.
+    head: Address,
     #[cfg(feature = "ilog")]
     pub cur_page: Vec<u8>,
     #[cfg(feature = "ilog")]
     pub d_address: Address,
     #[cfg(feature = "ilog")]
     pub d_offset: u64,
+    /// Tid of the running transaction, the epoch of the regions it fills.
+    epoch: u64,
+    /// Full pages of deltas, see `gc`.
+    #[cfg(not(feature = "ilog"))]
+    retired: RetiredRegions<Address>,
+    #[cfg(all(feature = "ilog", feature = "mvcc"))]
+    retired_dram: RetiredRegions<Vec<u8>>,
+    /// Registry of the catalog `thread_id` is claimed from, None for the
+    /// pages `recover` walks.
+    thread_ids: Option<Arc<ThreadIds>>,
+}
+
+impl Drop for TransactionBuffer {
+    fn drop(&mut self) {
+        if let Some(thread_ids) = &self.thread_ids {
+            thread_ids.release(self.thread_id);
+        }
+    }
 }
 
 impl TransactionBuffer {
@@ -53,24 +79,44 @@ impl TransactionBuffer {
             self.d_address = self.cur_page.as_ptr() as u64;
         }
     }
+    /// The buffer of `thread_id`, panics if another buffer holds it.
+    /// `session::SessionPool` leases ids instead.
     pub fn new(catalog: impl Deref<Target = Catalog>, thread_id: u64) -> Self {
+        match TransactionBuffer::try_new(catalog, thread_id) {
+            Ok(buffer) => buffer,
+            Err(_) => panic!("thread id {} is held by another buffer", thread_id),
+        }
+    }
+    /// `new`, `Error::Exist` if another buffer holds `thread_id`.
+    pub fn try_new(catalog: impl Deref<Target = Catalog>, thread_id: u64) -> Result<Self> {
+        let thread_ids = catalog.thread_ids().clone();
+        if !thread_ids.claim(thread_id) {
+            return Err(Error::Exist);
+        }
         let mut storage = NVMTableStorage::global_mut();
         let page = storage.alloc_page().unwrap();
 
         catalog.set_transaction_page_start(thread_id, page.page_start);
         let mut manager = TransactionBuffer {
             thread_id: thread_id,
+            thread_ids: Some(thread_ids),
             offset: 0,
             address: page.page_start,
+            head: catalog.transaction_page_slot(thread_id),
             #[cfg(feature = "ilog")]
             cur_page: vec![0; DRAM_PAGE_SIZE as usize],
             #[cfg(feature = "ilog")]
             d_address: 0,
             #[cfg(feature = "ilog")]
             d_offset: 0,
+            epoch: 0,
+            #[cfg(not(feature = "ilog"))]
+            retired: RetiredRegions::new(),
+            #[cfg(all(feature = "ilog", feature = "mvcc"))]
+            retired_dram: RetiredRegions::new(),
         };
         manager.init();
-        manager
+        Ok(manager)
     }
     pub fn free(&mut self) {
         #[cfg(feature = "ilog")]
@@ -82,6 +128,23 @@ impl TransactionBuffer {
         storage.free_page_list(PageId::get_page_id(self.address))
     }
     pub fn reload(catalog: &Catalog, thread_id: u64) -> Self {
+        TransactionBuffer::recover(catalog, thread_id);
+        TransactionBuffer::new(catalog, thread_id)
+    }
+    /// Roll back the records left in the pages of `thread_id` and free them,
+    /// returning how many were. No buffer may hold the id meanwhile, and the
+    /// thread has none afterwards.
+    pub fn recover(catalog: &Catalog, thread_id: u64) -> u64 {
+        TransactionBuffer::pending(catalog, thread_id).replay(catalog)
+    }
+    /// The records left in the pages of `thread_id`, which stays claimed
+    /// until they are replayed.
+    pub fn pending(catalog: &Catalog, thread_id: u64) -> PendingRecords {
+        assert!(
+            catalog.thread_ids().claim(thread_id),
+            "thread id {} is held by a buffer",
+            thread_id
+        );
         let mut managers: Vec<TransactionBuffer> = Vec::new();
         let mut address = catalog.get_transaction_page_start(thread_id);
 
@@ -90,12 +153,19 @@ impl TransactionBuffer {
                 thread_id: thread_id,
                 offset: 0,
                 address: address,
+                head: catalog.transaction_page_slot(thread_id),
                 #[cfg(feature = "ilog")]
                 cur_page: vec![0; MANAGER_PAGE_SIZE as usize],
                 #[cfg(feature = "ilog")]
                 d_address: 0,
                 #[cfg(feature = "ilog")]
                 d_offset: 0,
+                epoch: 0,
+                #[cfg(not(feature = "ilog"))]
+                retired: RetiredRegions::new(),
+                #[cfg(all(feature = "ilog", feature = "mvcc"))]
+                retired_dram: RetiredRegions::new(),
+                thread_ids: None,
             };
             assert!(PageId::get_page_id(address).id < MAX_PAGE_COUNT);
             manager.offset = manager.get_offset();
@@ -104,14 +174,16 @@ impl TransactionBuffer {
             managers.push(manager);
         }
         managers.reverse();
+        // the pages of a transaction share the state of the newest
+        let committed = managers.last().map_or(0, |manager| manager.get_committed());
+        let mut deltas: Vec<TupleDelta> = Vec::new();
         for manager in &managers {
             let mut offset = manager.offset;
             let commit = manager.get_committed_offset();
 
             if offset == commit && offset != EMPTY {
-                break;
+                continue;
             }
-            let mut deltas: Vec<TupleDelta> = Vec::new();
             if offset + DATA_SIZE > MANAGER_PAGE_SIZE {
                 offset = EMPTY;
             }
@@ -133,21 +205,13 @@ impl TransactionBuffer {
                 deltas.push(delta);
             }
             // deltas.reverse();
-
-            for delta in &deltas {
-                delta.rollback(manager.get_committed());
-            }
         }
-
-        catalog.set_transaction_page_start(thread_id, 0);
-        let mut storage = NVMTableStorage::global_mut();
-        for manager in &managers {
-            let page = PageId::get_page_id(manager.address);
-            storage.free_page_list(page);
+        PendingRecords {
+            thread_id,
+            managers,
+            deltas,
+            committed,
         }
-        drop(storage);
-
-        TransactionBuffer::new(catalog, thread_id)
     }
     pub fn get_thread_id(&self) -> u64 {
         return self.thread_id;
@@ -215,7 +279,16 @@ impl TransactionBuffer {
             self.d_offset += len;
         }
     }
-    pub fn begin(&mut self) {
+    pub fn begin(&mut self, tid: u64) {
+        self.epoch = tid;
+        // the pages before this one hold finished transactions only
+        let older = self.get_next();
+        if older != 0 {
+            self.set_next(0);
+            unsafe { io::clwb((self.address + NEXT_OFFSET) as *const u8) };
+            #[cfg(feature = "ilog")]
+            self.free_chain(older);
+        }
         let u = unsafe { &*(self.address as *const AtomicU64) };
 
         u.store(self.offset, Ordering::Relaxed);
@@ -227,27 +300,67 @@ impl TransactionBuffer {
     }
     pub fn alloc(&mut self) -> BufferAddress {
         if self.offset + DATA_SIZE >= MANAGER_PAGE_SIZE {
-            #[cfg(not(feature = "ilog"))]
-            {
-                let mut storage = NVMTableStorage::global_mut();
-                let page = storage.alloc_page().unwrap();
-                // println!("buffer allocate {}", page.page_start);
-                // if self.get_next() > 0 {
-                //     storage.free_page_list(PageId::get_page_id(self.get_next()));
-                // }
-
-                let cur_page = self.address;
-
-                self.address = page.page_start;
-                // println!("new page");
-                self.init();
+            // a running transaction keeps its records and goes on in a new
+            // page, other undo records are dropped with the ring wrapping
+            let running = self.offset != self.get_offset();
+            if running || cfg!(not(feature = "ilog")) {
+                self.next_page(running);
             }
             self.offset = EMPTY;
-
-            // self.set_next(cur_page);
         }
         self.address + self.offset
     }
+    /// Go on in a new page, linked back to the full one if `link`. Without
+    /// `ilog` the full page keeps deltas readers follow, it is retired to
+    /// the gc.
+    fn next_page(&mut self, link: bool) {
+        #[cfg(not(feature = "ilog"))]
+        let page = match self.retired.reclaim() {
+            Some(page) => page,
+            None => {
+                let mut storage = NVMTableStorage::global_mut();
+                storage.alloc_page().unwrap().page_start
+            }
+        };
+        #[cfg(feature = "ilog")]
+        let page = NVMTableStorage::global_mut()
+            .alloc_page()
+            .unwrap()
+            .page_start;
+        // println!("buffer allocate {}", page);
+
+        let cur_page = self.address;
+        if link {
+            // the records of the full page end here
+            self.commit(false);
+            unsafe { io::clwb((cur_page + COMMITED_OFFSET) as *const u8) };
+        }
+        #[cfg(not(feature = "ilog"))]
+        self.retired.retire(cur_page, self.epoch);
+
+        self.address = page;
+        self.init();
+        if link {
+            self.set_next(cur_page);
+        }
+        unsafe {
+            io::clwb(self.address as *const u8);
+            file::sfence();
+            io::write(self.head, self.address);
+            io::clwb(self.head as *const u8);
+        }
+        file::sfence();
+    }
+    /// Free the pages linked from `page` on.
+    #[cfg(feature = "ilog")]
+    fn free_chain(&self, mut page: Address) {
+        let mut storage = NVMTableStorage::global_mut();
+        while page != 0 {
+            let next = unsafe { io::read(page + NEXT_OFFSET) };
+            storage.free_page_list(PageId::get_page_id(page));
+            page = next;
+        }
+    }
     pub fn commit(&mut self, committed: bool) {
         let committed_address = self.address + COMMITED_OFFSET;
         let u = unsafe { &*(committed_address as *const AtomicU64) };
@@ -258,8 +371,9 @@ impl TransactionBuffer {
         u.store(self.offset, Ordering::Relaxed);
         //T
         // file::sfence();
+        // recovery redoes committed transactions in tid order
         if committed {
-            self.set_committed(1);
+            self.set_committed(self.epoch.max(1));
         }
         // unsafe {
         //     io::clwb(committed_address as *const u8);
@@ -267,12 +381,111 @@ impl TransactionBuffer {
     }
     #[cfg(all(feature = "ilog", feature = "mvcc"))]
     pub fn alloc_dram(&mut self) -> BufferAddress {
-        if self.d_address + DATA_SIZE >= DRAM_PAGE_SIZE {
+        if self.d_offset + DATA_SIZE >= DRAM_PAGE_SIZE {
+            // readers may still follow the deltas of the full page
+            let page = self
+                .retired_dram
+                .reclaim()
+                .unwrap_or_else(|| vec![0; DRAM_PAGE_SIZE as usize]);
+            let full = std::mem::replace(&mut self.cur_page, page);
+            self.retired_dram.retire(full, self.epoch);
+            self.d_address = self.cur_page.as_ptr() as u64;
             self.d_offset = 0;
         }
         self.d_address + self.d_offset
     }
 }
+impl TransactionBuffer {
+    /// Run `f` in a transaction on this buffer and commit it, see `run_with`.
+    pub fn run<'a, T, F>(&'a mut self, f: F) -> Result<T>
+    where
+        F: FnMut(&mut Transaction<'a>) -> Result<T>,
+    {
+        self.run_with(RetryPolicy::default(), f)
+    }
+    /// Begin a transaction, run `f` in it and commit. A conflict, in `f` or
+    /// at commit, aborts and runs it again after the backoff of `policy`
+    /// until its retries run out, then returns the last abort as
+    /// `Error::Aborted`. Any other error of `f` aborts and is returned as is.
+    pub fn run_with<'a, T, F>(&'a mut self, policy: RetryPolicy, mut f: F) -> Result<T>
+    where
+        F: FnMut(&mut Transaction<'a>) -> Result<T>,
+    {
+        let mut txn = Transaction::new(self, false);
+        let mut retry = 0;
+        loop {
+            txn.begin();
+            let reason = match f(&mut txn) {
+                Ok(value) => match txn.commit() {
+                    Ok(_) => return Ok(value),
+                    Err(reason) => reason,
+                },
+                Err(e) => {
+                    txn.abort();
+                    if !e.is_conflict() {
+                        return Err(e);
+                    }
+                    match e {
+                        Error::Aborted(reason) => reason,
+                        e => AbortReason::new(AbortKind::from_error(&e)),
+                    }
+                }
+            };
+            if !reason.kind.is_conflict() {
+                return Err(Error::Aborted(reason));
+            }
+            if policy.max_retries.map_or(false, |max| retry >= max) {
+                return Err(Error::Aborted(reason));
+            }
+            let backoff = policy.backoff(retry);
+            if backoff.as_nanos() == 0 {
+                std::thread::yield_now();
+            } else {
+                std::thread::sleep(backoff);
+            }
+            retry += 1;
+        }
+    }
+}
+
+/// The records a crash left in the pages of a thread, see
+/// `TransactionBuffer::pending`.
+pub struct PendingRecords {
+    thread_id: u64,
+    managers: Vec<TransactionBuffer>,
+    deltas: Vec<TupleDelta>,
+    /// The state of the newest page: the tid of the transaction once it
+    /// committed, 0 before.
+    committed: u64,
+}
+
+impl PendingRecords {
+    /// Tid of the transaction of the records if it committed, they redo its
+    /// writes then.
+    pub fn committed(&self) -> Option<u64> {
+        match self.committed {
+            0 => None,
+            tid => Some(tid),
+        }
+    }
+    /// Roll the records forward or back, free the pages and release the
+    /// thread id, returning how many records there were.
+    pub fn replay(self, catalog: &Catalog) -> u64 {
+        let committed = self.committed().is_some();
+        for delta in &self.deltas {
+            delta.rollback(committed);
+        }
+        catalog.set_transaction_page_start(self.thread_id, 0);
+        let mut storage = NVMTableStorage::global_mut();
+        for manager in &self.managers {
+            let page = PageId::get_page_id(manager.address);
+            storage.free_page_list(page);
+        }
+        catalog.thread_ids().release(self.thread_id);
+        self.deltas.len() as u64
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -300,4 +513,58 @@ mod tests {
         let offset_2 = manager.alloc();
         assert_eq!(offset, offset_2 - manager.address);
     }
+
+    #[test]
+    fn test_manager_chain() {
+        NVMTableStorage::init_test_database();
+        Catalog::init_catalog();
+        let catalog = Catalog::new(NVM_ADDR);
+        let mut manager = TransactionBuffer::new(&catalog, 1);
+        let first = manager.address;
+        manager.begin(1);
+        // a transaction logging over three pages
+        let mut pages = 1;
+        while pages < 3 {
+            let page = manager.address;
+            manager.alloc();
+            if manager.address != page {
+                pages += 1;
+            }
+            if cfg!(feature = "ilog") {
+                manager.save_redo(DATA_SIZE);
+            } else {
+                manager.add_delta(DATA_SIZE);
+            }
+        }
+        assert_ne!(manager.address, first);
+        let head: u64 = unsafe { io::read(catalog.transaction_page_slot(1)) };
+        assert_eq!(head, manager.address);
+        let mut page = manager.get_next();
+        let mut linked = 1;
+        while page != first {
+            assert_ne!(page, 0);
+            page = unsafe { io::read(page + NEXT_OFFSET) };
+            linked += 1;
+        }
+        assert_eq!(linked, pages);
+
+        // the next transaction starts a chain of its own
+        manager.commit(true);
+        manager.begin(2);
+        assert_eq!(manager.get_next(), 0);
+    }
+
+    #[test]
+    fn test_recover() {
+        NVMTableStorage::init_test_database();
+        let catalog = Catalog::new(NVM_ADDR);
+        let mut manager = TransactionBuffer::new(&catalog, 2);
+        manager.begin(1);
+        manager.commit(true);
+        assert_eq!(catalog.get_transaction_page_start(2), manager.address);
+        // gone with the crash
+        drop(manager);
+        assert_eq!(TransactionBuffer::recover(&catalog, 2), 0);
+        assert_eq!(catalog.get_transaction_page_start(2), 0);
+    }
 }
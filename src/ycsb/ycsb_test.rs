@@ -9,7 +9,7 @@ mod test {
 
     use crate::config::{CATALOG_ADDRESS, POOL_PERC};
     use crate::customer_config::YCSB_TOTAL;
-    use crate::mvcc_config::{TEST_THREAD_COUNT, THREAD_COUNT, TRANSACTION_COUNT};
+    use crate::mvcc_config::{TEST_THREAD_COUNT, thread_count, TRANSACTION_COUNT};
     use crate::storage::catalog::{self, Catalog};
     // use crate::storage::index::nbtree::NBTree;
     use crate::storage::nvm_file::NVMTableStorage;
@@ -18,6 +18,7 @@ mod test {
     use crate::tpcc::{string_rand, u64_rand};
     use crate::transaction::transaction::Transaction;
     use crate::transaction::transaction_buffer::TransactionBuffer;
+    use crate::transaction::cc::ConcurrencyControl;
     use crate::utils::executor::executor::Executor;
     use crate::ycsb::ycsb_query::YcsbQuery;
     use crate::ycsb::ycsb_txn::YcsbTxn;
@@ -31,7 +32,7 @@ mod test {
         NVMTableStorage::init_test_database();
     }
     fn init_data(prop: Properties) {
-        Catalog::init_catalog();
+        Catalog::init_catalog_with(ConcurrencyControl::from_env());
         let mut schema = TableSchema::new();
         schema.push(ColumnType::Int64, "KEY");
         for i in 0..prop.field_per_tuple {
@@ -45,16 +46,16 @@ mod test {
         let catalog = Catalog::global();
         catalog.add_table("usertable", schema).unwrap();
         #[cfg(feature = "ycsb_e")]
-        catalog.set_range_primary_key("usertable", 0);
+        catalog.set_range_primary_key("usertable", &[0]);
         #[cfg(not(feature = "ycsb_e"))]
-        catalog.set_primary_key("usertable", 0);
+        catalog.set_primary_key("usertable", &[0]);
 
         #[cfg(feature = "buffer_pool")]
         catalog.set_pool_size("usertable", prop.table_size as usize / POOL_PERC);
-        let mut handles = Vec::with_capacity(THREAD_COUNT);
-        let barrier = Arc::new(Barrier::new(THREAD_COUNT));
+        let mut handles = Vec::with_capacity(thread_count());
+        let barrier = Arc::new(Barrier::new(thread_count()));
 
-        for i in 0..THREAD_COUNT {
+        for i in 0..thread_count() {
             let b = barrier.clone();
             handles.push(thread::spawn(move || {
                 let catalog = Catalog::global();
@@ -65,7 +66,7 @@ mod test {
                 crate::storage::index::nbtree::init_index(i as i32);
                 b.wait();
                 for key in 0..prop.table_size {
-                    if (key as usize) % THREAD_COUNT == i {
+                    if (key as usize) % thread_count() == i {
                         let mut value = (key + 1).to_string();
                         for _ in 0..prop.field_per_tuple {
                             value.push(',');
@@ -81,9 +82,9 @@ mod test {
                 }
                 println!("insert finish");
                 b.wait();
-                let mut k = prop.table_size as usize / POOL_PERC / THREAD_COUNT;
+                let mut k = prop.table_size as usize / POOL_PERC / thread_count();
                 for key in 0..prop.table_size {
-                    if (key as usize) % THREAD_COUNT == i && k > 0 {
+                    if (key as usize) % thread_count() == i && k > 0 {
                         k -= 1;
                         ycsb_txn.begin();
                         ycsb_txn.read(&table, key + 1, 1);
@@ -207,7 +208,7 @@ mod test {
         let theta_set = [0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0];
         
         let key_counter = Arc::new(AtomicU64::new(YCSB_TOTAL));
-        let mut offset: usize = THREAD_COUNT;
+        let mut offset: usize = thread_count();
         for theta in theta_set {
             #[cfg(feature = "ycsb_mvcc")]
             {
@@ -405,41 +406,12 @@ mod test {
         if init {
             NVMTableStorage::reload_test_database();
         }
-        let start0 = SystemTime::now();
-        Catalog::reload(CATALOG_ADDRESS);
-        let catalog = Catalog::global();
-
-        let end = SystemTime::now();
-        println!(
-            "Reload catalog uses {} nano seconds",
-            end.duration_since(start0).unwrap().as_nanos()
-        );
-        let start = SystemTime::now();
-        catalog.set_primary_key("usertable", 0);
-        let end = SystemTime::now();
-        println!(
-            "Reload index uses {} nano seconds",
-            end.duration_since(start).unwrap().as_nanos()
-        );
-        //TODO redo
-        let start = SystemTime::now();
-        catalog.redo_transaction();
-        let end = SystemTime::now();
-        println!(
-            "Redo Transacion uses {} nano seconds",
-            end.duration_since(start).unwrap().as_nanos()
-        );
-        let start = SystemTime::now();
-        catalog.reload_timestamp();
-        let end = SystemTime::now();
-        println!(
-            "Reload timestamp uses {} nano seconds",
-            end.duration_since(start).unwrap().as_nanos()
-        );
-        println!(
-            "Recovery uses {} nano seconds",
-            end.duration_since(start0).unwrap().as_nanos()
-        );
+        let report = Catalog::recover(CATALOG_ADDRESS, |catalog| {
+            catalog.set_primary_key("usertable", &[0]);
+        })
+        .unwrap();
+        Catalog::global().set_concurrency_control(ConcurrencyControl::from_env());
+        println!("Recovery: {}", report);
     }
     #[test]
     pub fn ycsb_test_reload() {
@@ -452,7 +424,7 @@ mod test {
         let (tx0, rx) = mpsc::channel();
         let key_counter = Arc::new(AtomicU64::new(YCSB_TOTAL));
 
-        for i in 0..THREAD_COUNT {
+        for i in 0..thread_count() {
             let tx = tx0.clone();
             let counter = key_counter.clone();
             thread::spawn(move || {
@@ -493,7 +465,7 @@ mod test {
 
         let mut num = 0;
         let mut total = 0;
-        for i in 0..THREAD_COUNT {
+        for i in 0..thread_count() {
             let (num0, total0) = rx.recv().unwrap();
             num += num0;
             total += total0;
@@ -3,8 +3,12 @@ use crate::{
         global::{Timer, READING},
         table::{IndexType, Table, TupleId},
     },
-    transaction::{transaction::Transaction, transaction_buffer::TransactionBuffer},
+    transaction::{
+        transaction::{ScanCursor, Transaction},
+        transaction_buffer::TransactionBuffer,
+    },
 };
+use std::ops::Bound;
 
 use super::ycsb_query::YcsbQuery;
 use super::{Operation, Properties};
@@ -103,12 +107,11 @@ impl<'a> YcsbTxn<'a> {
     pub fn scan(&mut self, table: &'a Table, start_key: u64, scan_len: u64, column: usize) -> bool {
         let max_key = start_key + scan_len;
         let min_key = start_key;
-        let lines:Vec<TupleId>;
+        let mut lines: ScanCursor<'a>;
 
-        match table.range_tuple_id(&IndexType::Int64(min_key), &IndexType::Int64(max_key)) {
+        match self.txn.scan_cursor(table, Bound::Included(IndexType::Int64(min_key)), Bound::Excluded(IndexType::Int64(max_key))) {
             Ok(l) => {
                 lines = l;
-                // println!("{:?}", lines);
             },
             _ => {
                 // println!("read {} old from {} to {}", start_key, min_key, max_key);
@@ -117,7 +120,7 @@ impl<'a> YcsbTxn<'a> {
             }
         }
 
-        for tid in lines {
+        while let Some(tid) = self.txn.next_in(&mut lines) {
             if tid.get_address() < crate::config::NVM_ADDR {
                 return false;
             }
@@ -138,8 +141,13 @@ impl<'a> YcsbTxn<'a> {
     pub fn insert(&mut self, table: &'a Table, start_key: u64, data: &[u8]) -> bool {
         let mut tuple = self.txn.alloc(table);
         tuple.save(data.len() as u64, data);
-        table.index_insert_by_tuple(&TupleId::from_address(tuple._address()), &tuple).unwrap();
-        true
+        match table.index_insert_by_tuple(&TupleId::from_address(tuple._address()), &tuple) {
+            Ok(_) => true,
+            Err(e) => {
+                self.result = Err(e);
+                false
+            }
+        }
     }
     pub fn update(&mut self, table: &'a Table, key: u64, column: usize, data: &[u8]) -> bool {
         match table.search_tuple_id(&IndexType::Int64(key)) {
@@ -188,7 +196,7 @@ impl<'a> YcsbTxn<'a> {
     }
 
     pub fn insert_init(&mut self, table: &'a Table, value: &str) -> bool {
-        self.txn.insert(table, value);
+        self.txn.insert(table, value).unwrap();
         true
     }
 
@@ -203,7 +211,7 @@ impl<'a> YcsbTxn<'a> {
     }
 
     pub fn commit(&mut self) -> bool {
-        if self.txn.commit() {
+        if self.txn.commit().is_ok() {
             #[cfg(feature = "txn_clock")]
             self.timer.end(READING, READING);
             return true;